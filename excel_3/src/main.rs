@@ -1,65 +1,34 @@
 use axum::{
-    body::Body,
-    http::{header, StatusCode, HeaderName},
+    http::StatusCode,
     response::{IntoResponse, Response},
     routing::get,
     Router,
 };
-use std::fs;
+use excel_common::Xlsx;
 use std::path::Path;
 use tokio::net::TcpListener;
 use umya_spreadsheet::*;
-use uuid::Uuid;
 
-async fn edit_download_excel() -> impl IntoResponse {
-    let inPath = Path::new("input.xlsx");
-    let mut book = reader::xlsx::read(inPath).unwrap();  // 普通に読み込み
+async fn edit_download_excel() -> Response {
+    let in_path = Path::new("input.xlsx");
+    let mut book = match reader::xlsx::read(in_path) {
+        Ok(book) => book,  // 普通に読み込み
+        Err(e) => {
+            eprintln!("Failed to read input.xlsx: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read Excel file.").into_response();
+        }
+    };
 
-    let sheet = book.get_sheet_by_name_mut("Sheet1").unwrap();
+    let Some(sheet) = book.get_sheet_by_name_mut("Sheet1") else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Sheet1 not found.").into_response();
+    };
     // セル A1 に文字列
     sheet.get_cell_mut("A2").set_value("こんにちは、Rust!");
     sheet.get_cell_mut("B2").set_value("123");
     sheet.get_cell_mut("C2").set_value("C-123");
 
-    let file_name = format!("{}.xlsx", Uuid::new_v4());
-    let path = Path::new(&file_name);
-
-    match writer::xlsx::write(&book, path) {
-        Ok(_) => {
-            let file_content = match fs::read(path) {
-                Ok(content) => content,
-                Err(_) => {
-                    return Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .body(Body::from("Failed to read Excel file."))
-                        .unwrap();
-                }
-            };
-
-            let _ = fs::remove_file(path);
-
-            let headers = [
-                (
-                    header::CONTENT_TYPE,
-                    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
-                ),
-                (
-                    header::CONTENT_DISPOSITION,
-                    "attachment; filename=\"edit_result.xlsx\"",
-                ),
-            ];
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(headers[0].0.clone(), headers[0].1)
-                .header(headers[1].0.clone(), headers[1].1)
-                .body(Body::from(file_content))
-                .unwrap()
-        }
-        Err(_) => Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(Body::from("Failed to create Excel file."))
-            .unwrap(),
-    }
+    // 一時ファイルを作らずメモリ上で xlsx にして返す
+    Xlsx::render(book, "edit_result.xlsx").await
 }
 
 async fn download_excel() -> impl IntoResponse {
@@ -78,45 +47,7 @@ async fn download_excel() -> impl IntoResponse {
     book.insert_new_row("Sheet1", &2, &1);
     book.insert_new_column("Sheet1", "B", &1);
 
-    let file_name = format!("{}.xlsx", Uuid::new_v4());
-    let path = Path::new(&file_name);
-
-    match writer::xlsx::write(&book, path) {
-        Ok(_) => {
-            let file_content = match fs::read(path) {
-                Ok(content) => content,
-                Err(_) => {
-                    return Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .body(Body::from("Failed to read Excel file."))
-                        .unwrap();
-                }
-            };
-
-            let _ = fs::remove_file(path);
-
-            let headers = [
-                (
-                    header::CONTENT_TYPE,
-                    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
-                ),
-                (
-                    header::CONTENT_DISPOSITION,
-                    "attachment; filename=\"result.xlsx\"",
-                ),
-            ];
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(headers[0].0.clone(), headers[0].1)
-                .header(headers[1].0.clone(), headers[1].1)
-                .body(Body::from(file_content))
-                .unwrap()
-        }
-        Err(_) => Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(Body::from("Failed to create Excel file."))
-            .unwrap(),
-    }
+    Xlsx::render(book, "result.xlsx").await
 }
 
 #[tokio::main]
@@ -0,0 +1,148 @@
+use axum::{
+    body::Body,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use umya_spreadsheet::{writer, Spreadsheet, XlsxError};
+
+pub const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+// ブックをメモリ上で xlsx のバイト列に変換する (一時ファイルを使わない)
+pub fn to_bytes(book: &Spreadsheet) -> Result<Vec<u8>, XlsxError> {
+    let mut buffer = Vec::new();
+    writer::xlsx::write_writer(book, &mut buffer)?;
+    Ok(buffer)
+}
+
+// Content-Disposition (attachment)
+//
+// 古いクライアント向けの ASCII の filename と、RFC 5987 形式の filename* (UTF-8) を併記する。
+pub fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        encode_rfc5987(filename)
+    )
+}
+
+// RFC 5987 の attr-char 以外をパーセントエンコードする
+fn encode_rfc5987(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'!'
+            | b'#'
+            | b'$'
+            | b'&'
+            | b'+'
+            | b'-'
+            | b'.'
+            | b'^'
+            | b'_'
+            | b'`'
+            | b'|'
+            | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// xlsx のダウンロード応答 (書き出し済みのバイト列)
+pub struct Xlsx {
+    bytes: Vec<u8>,
+    filename: String,
+}
+
+impl Xlsx {
+    // ブックを xlsx に書き出して応答にする
+    //
+    // シリアライズと zip 圧縮はブックの大きさに比例して重いため、
+    // async のワーカーを止めないよう blocking スレッド (spawn_blocking) で行う。
+    pub async fn render(book: Spreadsheet, filename: impl Into<String>) -> Response {
+        let filename = filename.into();
+        match tokio::task::spawn_blocking(move || to_bytes(&book)).await {
+            Ok(Ok(bytes)) => Xlsx { bytes, filename }.into_response(),
+            Ok(Err(e)) => {
+                eprintln!("Failed to create Excel file: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create Excel file.").into_response()
+            }
+            Err(e) => {
+                eprintln!("Excel export task failed: {:?}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create Excel file.").into_response()
+            }
+        }
+    }
+}
+
+impl IntoResponse for Xlsx {
+    fn into_response(self) -> Response {
+        let headers = [
+            (header::CONTENT_TYPE, XLSX_CONTENT_TYPE.to_string()),
+            (header::CONTENT_LENGTH, self.bytes.len().to_string()),
+            (header::CONTENT_DISPOSITION, content_disposition(&self.filename)),
+        ];
+        (headers, Body::from(self.bytes)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_filename_is_used_as_is() {
+        assert_eq!(
+            content_disposition("todos-2025_01.xlsx"),
+            "attachment; filename=\"todos-2025_01.xlsx\"; filename*=UTF-8''todos-2025_01.xlsx"
+        );
+    }
+
+    #[test]
+    fn japanese_filename_is_percent_encoded() {
+        assert_eq!(encode_rfc5987("売上 2025.xlsx"), "%E5%A3%B2%E4%B8%8A%202025.xlsx");
+        assert_eq!(
+            content_disposition("売上 2025.xlsx"),
+            "attachment; filename=\"__ 2025.xlsx\"; filename*=UTF-8''%E5%A3%B2%E4%B8%8A%202025.xlsx"
+        );
+    }
+
+    #[test]
+    fn quotes_and_backslashes_cannot_break_the_header() {
+        assert_eq!(encode_rfc5987("a\"b\\c.xlsx"), "a%22b%5Cc.xlsx");
+        assert_eq!(
+            content_disposition("a\"b\\c.xlsx"),
+            "attachment; filename=\"a_b_c.xlsx\"; filename*=UTF-8''a%22b%5Cc.xlsx"
+        );
+    }
+
+    #[tokio::test]
+    async fn render_writes_the_workbook_off_the_runtime() {
+        let mut book = umya_spreadsheet::new_file();
+        book.get_sheet_mut(&0).unwrap().get_cell_mut("A1").set_value("テスト");
+        let response = Xlsx::render(book, "結果.xlsx").await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[header::CONTENT_TYPE], XLSX_CONTENT_TYPE);
+        assert_eq!(
+            headers[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"__.xlsx\"; filename*=UTF-8''%E7%B5%90%E6%9E%9C.xlsx"
+        );
+        let length: usize = headers[header::CONTENT_LENGTH].to_str().unwrap().parse().unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body.len(), length);
+        // xlsx は zip
+        assert!(body.starts_with(b"PK"));
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub mod mod_audit;
+pub mod mod_auth;
+pub mod mod_client;
+pub mod mod_completion;
+pub mod mod_config;
+pub mod mod_db;
+pub mod mod_http;
+pub mod mod_logging;
+pub mod mod_metrics;
+pub mod mod_oauth;
+pub mod mod_prompts;
+pub mod mod_purchase;
+pub mod mod_ratelimit;
+pub mod mod_resources;
+pub mod mod_rpc;
+pub mod mod_schema;
+pub mod mod_session;
+pub mod mod_stdio;
+pub mod mod_todo;
+pub mod mod_tools;
+pub mod mod_upstream;
+
+use mod_audit::AuditLog;
+use mod_auth::ApiKeyStore;
+use mod_metrics::Metrics;
+use mod_oauth::OAuthConfig;
+use mod_prompts::PromptRegistry;
+use mod_purchase::PurchaseRepository;
+use mod_ratelimit::RateLimiter;
+use mod_session::SessionStore;
+use mod_todo::TodoStore;
+use mod_tools::ToolRegistry;
+
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(30);
+pub const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+// MCP Server State
+pub struct AppState {
+    pub server_name: String,
+    pub version: String,
+    pub todos: TodoStore,
+    pub purchases: PurchaseRepository,
+    pub tools: ToolRegistry,
+    pub prompts: PromptRegistry,
+    pub sessions: SessionStore,
+    pub auth: ApiKeyStore,
+    pub oauth: Option<OAuthConfig>,
+    pub audit: AuditLog,
+    pub metrics: Metrics,
+    pub limiter: RateLimiter,
+    pub tool_timeout: Duration,
+    // ツールごとの実行時間の上限 (tool_timeout と Tool::timeout より優先)
+    pub tool_timeouts: HashMap<String, Duration>,
+    // purchase をユーザーに確認する金額 (None なら確認しない)
+    pub purchase_confirm_above: Option<i64>,
+}
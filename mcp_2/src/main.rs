@@ -1,453 +1,301 @@
-use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    response::IntoResponse,
-    routing::post,
-    Json, Router,
-};
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use clap::Parser;
 use dotenvy::dotenv;
-use libsql::Builder;
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::env;
 use std::sync::Arc;
-use tower_http::trace::TraceLayer;
-
-mod mod_purchase;
-
-// JSON-RPC 2.0 Request
-#[derive(Debug, Deserialize)]
-struct JsonRpcRequest {
-    jsonrpc: String,
-    method: String,
-    #[serde(default)]
-    params: Option<Value>,
-    id: Option<Value>,
-}
-
-// JSON-RPC 2.0 Response
-#[derive(Debug, Serialize)]
-struct JsonRpcResponse {
-    jsonrpc: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    result: Option<Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    error: Option<JsonRpcError>,
-    id: Option<Value>,
-}
-
-// JSON-RPC 2.0 Error
-#[derive(Debug, Serialize)]
-struct JsonRpcError {
-    code: i32,
-    message: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    data: Option<Value>,
-}
-
-// MCP Server State
-#[derive(Clone)]
-struct AppState {
-    server_name: String,
-    version: String,
-    db_path: String,
-}
-#[derive(Debug, Deserialize,Serialize)]
-struct PurchaseParams {
-    name: String,
-    price: i32,
-}
+use std::time::Duration;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use rust_remoto_mcp_2::mod_audit::AuditLog;
+use rust_remoto_mcp_2::mod_auth::{self, ApiKeyStore};
+use rust_remoto_mcp_2::mod_config::{Cli, Config, Transport};
+use rust_remoto_mcp_2::mod_db::{Backend, Db};
+use rust_remoto_mcp_2::mod_metrics::Metrics;
+use rust_remoto_mcp_2::mod_oauth::OAuthConfig;
+use rust_remoto_mcp_2::mod_purchase::PurchaseRepository;
+use rust_remoto_mcp_2::mod_ratelimit::RateLimiter;
+use rust_remoto_mcp_2::mod_session::SessionStore;
+use rust_remoto_mcp_2::mod_todo::TodoStore;
+use rust_remoto_mcp_2::mod_upstream::{self, UpstreamConfig};
+use rust_remoto_mcp_2::{mod_http, mod_logging, mod_prompts, mod_stdio, mod_tools, AppState};
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    // ロギング初期化
-    tracing_subscriber::fmt()
-        .with_target(false)
-        .compact()
-        .init();
+    let cli = Cli::parse();
+    // キーファイル用のハッシュ生成 (標準入力の1行目を sha256:<hex> で出力)
+    if cli.hash_key {
+        let mut secret = String::new();
+        std::io::stdin().read_line(&mut secret).ok();
+        println!("{}", mod_auth::hash_key(secret.trim_end_matches(['\r', '\n'])));
+        return;
+    }
 
-    // データベース初期化
-    let db_path = ":memory:";
+    // 設定 (ファイル → 環境変数 → 引数)
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("config error: {:#}", e);
+            std::process::exit(2);
+        }
+    };
+    if cli.check_config {
+        match check_config(&config).await {
+            Ok(()) => println!("configuration OK"),
+            Err(e) => {
+                eprintln!("config error: {:#}", e);
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
+    let stdio = config.server.transport == Transport::Stdio;
+
+    // ロギング初期化 (stdio モードでは stdout をプロトコルが使うため stderr へ)
+    // MCP クライアントへの転送 (logging/setLevel) は log_level とは別に判定する
+    let filter = EnvFilter::new(&config.server.log_level);
+    let console = tracing_subscriber::fmt::layer().with_target(false).compact();
+    let console = if stdio {
+        console.with_ansi(false).with_writer(std::io::stderr).boxed()
+    } else {
+        console.boxed()
+    };
+    tracing_subscriber::registry()
+        .with(console.with_filter(filter))
+        .with(mod_logging::layer())
+        .init();
 
-    // アプリケーションステート
-    let state = Arc::new(AppState {
-        server_name: "MCP Server Example".to_string(),
-        version: "1.0.0".to_string(),
-        db_path: db_path.to_string(),
-    });
+    let Resources {
+        auth,
+        oauth,
+        upstreams,
+        purchase_backend,
+    } = match load_resources(&config) {
+        Ok(resources) => resources,
+        Err(e) => {
+            tracing::error!("config error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
 
-    // ルーター設定
-    let app = Router::new()
-        .route("/", post(handle_jsonrpc))
-        .route("/mcp", post(handle_jsonrpc))
-        .with_state(state)
-        .layer(TraceLayer::new_for_http());
+    // データベース初期化 (ローカル libsql ファイル)
+    let db_path = &config.database.path;
+    let (todos, audit) = match open_local_stores(db_path).await {
+        Ok(stores) => stores,
+        Err(e) => {
+            tracing::error!("database open error: path={}, {}", db_path, e);
+            std::process::exit(1);
+        }
+    };
 
-    // サーバー起動
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
-        .await
-        .unwrap();
+    // 購入データ (local / remote / replica)
+    tracing::info!("purchase database: {}", purchase_backend.describe());
+    let purchases = match PurchaseRepository::open(&purchase_backend).await {
+        Ok(purchases) => purchases,
+        Err(e) => {
+            tracing::error!("purchase database open error: {:#}", e);
+            std::process::exit(1);
+        }
+    };
 
-    tracing::info!("MCP Server listening on {}", listener.local_addr().unwrap());
+    if auth.is_enabled() {
+        tracing::info!("API keys loaded: {}", auth.key_count());
+    }
+    if let Some(oauth) = &oauth {
+        tracing::info!("OAuth enabled: metadata={}, keys={}", oauth.metadata_url(), oauth.key_count());
+    }
+    if !auth.is_enabled() && oauth.is_none() {
+        tracing::warn!("no API keys configured, authentication is disabled");
+    }
+    tracing::info!("rate limits: {}", config.rate_limit.describe());
 
-    axum::serve(listener, app).await.unwrap();
-}
+    // ツール (ローカル + 上流 MCP サーバー、tools.enabled / disabled で絞り込む)
+    let mut tools = mod_tools::default_registry();
+    if let Err(e) = mod_upstream::register_tools(&mut tools, &upstreams).await {
+        tracing::error!("upstreams config error: {:#}", e);
+        std::process::exit(1);
+    }
+    tools.retain(|name| config.tools.allows(name));
+    tracing::info!("tools enabled: {}", tools.names().join(", "));
 
-// JSON-RPC ハンドラー
-async fn handle_jsonrpc(
-    State(state): State<Arc<AppState>>,
-    headers: HeaderMap,
-    Json(request): Json<JsonRpcRequest>,
-) -> impl IntoResponse 
-{
-    tracing::info!("Received request: method={}, id={:?}", request.method, request.id);
+    // アプリケーションステート
+    let state = Arc::new(AppState {
+        server_name: config.server.name.clone(),
+        version: config.server.version.clone(),
+        todos,
+        purchases,
+        tools,
+        prompts: mod_prompts::default_registry(),
+        sessions: SessionStore::new(config.session_idle_timeout(), config.server.max_sessions_per_key),
+        auth,
+        oauth,
+        audit,
+        metrics: Metrics::new(),
+        limiter: RateLimiter::new(config.rate_limit.clone()),
+        tool_timeout: config.tool_timeout(),
+        tool_timeouts: config.tool_timeouts(),
+        purchase_confirm_above: config.tools.purchase_confirm_above,
+    });
 
-    // Authorization ヘッダーの検証
-    let api_key = env::var("API_KEY").unwrap_or_else(|_| String::new());
-    
-    if !api_key.is_empty() {
-        let auth_header = headers.get("Authorization")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-        
-        tracing::info!("auth={}", auth_header);
-        
-        if auth_header != api_key {
-            tracing::info!("NG, auth-key");
-            return (
-                StatusCode::UNAUTHORIZED,
-                Json(JsonRpcResponse {
-                    jsonrpc: "2.0".to_string(),
-                    result: None,
-                    error: Some(JsonRpcError {
-                        code: -32001,
-                        message: "Unauthorized: Invalid API key".to_string(),
-                        data: None,
-                    }),
-                    id: request.id,
-                }),
-            );
+    if stdio {
+        tracing::info!("MCP Server running on stdio");
+        tokio::select! {
+            _ = mod_stdio::run(state) => {}
+            _ = shutdown_signal() => tracing::info!("shutdown signal received"),
         }
-        
-        tracing::info!("ok, auth-key");
+        return;
     }
 
-    // JSON-RPC 2.0 バージョンチェック
-    if request.jsonrpc != "2.0" {
-        return (
-            StatusCode::OK,
-            Json(JsonRpcResponse {
-                jsonrpc: "2.0".to_string(),
-                result: None,
-                error: Some(JsonRpcError {
-                    code: -32600,
-                    message: "Invalid Request".to_string(),
-                    data: None,
-                }),
-                id: request.id,
-            }),
-        );
+    // サーバー起動
+    if let Err(e) = serve(&config, state).await {
+        tracing::error!("server error: {:#}", e);
+        std::process::exit(1);
     }
+    tracing::info!("MCP Server stopped");
+}
 
-    // メソッドディスパッチ
-    let result = match request.method.as_str() {
-        "initialize" => handle_initialize(&state, request.params),
-        "tools/list" => handle_tools_list(),
-        "tools/call" => handle_tools_call(state, request.params).await,
-        "resources/list" => handle_resources_list(),
-        "resources/read" => handle_resources_read(request.params),
-        "prompts/list" => handle_prompts_list(),
-        _ => Err(JsonRpcError {
-            code: -32601,
-            message: "Method not found".to_string(),
-            data: None,
-        }),
-    };
-
-    let response = match result {
-        Ok(result) => JsonRpcResponse {
-            jsonrpc: "2.0".to_string(),
-            result: Some(result),
-            error: None,
-            id: request.id,
-        },
-        Err(error) => JsonRpcResponse {
-            jsonrpc: "2.0".to_string(),
-            result: None,
-            error: Some(error),
-            id: request.id,
-        },
-    };
-
-    (StatusCode::OK, Json(response))
+// 設定が参照するファイル (API キー・JWKS・上流設定) の読み込み
+struct Resources {
+    auth: ApiKeyStore,
+    oauth: Option<OAuthConfig>,
+    upstreams: Vec<UpstreamConfig>,
+    purchase_backend: Backend,
 }
 
-// MCP initialize メソッド
-fn handle_initialize(state: &AppState, _params: Option<Value>) -> Result<Value, JsonRpcError> {
-    Ok(json!({
-        "protocolVersion": "2024-11-05",
-        "serverInfo": {
-            "name": state.server_name,
-            "version": state.version
-        },
-        "capabilities": {
-            "tools": {},
-            "resources": {},
-            "prompts": {}
-        }
-    }))
+fn load_resources(config: &Config) -> anyhow::Result<Resources> {
+    let auth = ApiKeyStore::load(config.auth.api_keys_file.as_deref(), config.auth.api_key.as_deref())?;
+    let oauth = config.auth.oauth.as_ref().map(OAuthConfig::from_settings).transpose()?;
+    Ok(Resources {
+        auth,
+        oauth,
+        upstreams: config.load_upstreams()?,
+        purchase_backend: config.purchase_backend()?,
+    })
 }
 
-// tools/list メソッド
-fn handle_tools_list() -> Result<Value, JsonRpcError> {
-    Ok(json!({
-        "tools": [
-            {
-                "name": "echo",
-                "description": "Echo back the input message",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "message": {
-                            "type": "string",
-                            "description": "Message to echo"
-                        }
-                    },
-                    "required": ["message"]
-                }
-            },
-            {
-                "name": "add",
-                "description": "Add two numbers",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "a": { "type": "number" },
-                        "b": { "type": "number" }
-                    },
-                    "required": ["a", "b"]
-                }
-            },
-            {
-                "name": "add_todo",
-                "description": "Add a new todo item to the database",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "title": {
-                            "type": "string",
-                            "description": "The todo item title/content"
-                        }
-                    },
-                    "required": ["title"]
-                }
-            },
-            {
-                "name": "purchase",
-                "description": "品名と価格を受け取り、値をAPIに送信します。",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "name": {
-                            "type": "string",
-                            "description": "購入する品名"
-                        },
-                        "price": {
-                            "type": "number",
-                            "description": "価格"
-                        }
-                    },
-                    "required": ["name", "price"]
-                }
-            },            
-            {
-                "name": "purchase_list",
-                "description": "購入品リストを、表示します。",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {},
-                    "required": []
-                }
-            },            
+// --check-config: ファイルの読み込みまで確認する (DB と上流には接続しない)
+async fn check_config(config: &Config) -> anyhow::Result<()> {
+    let resources = load_resources(config)?;
+    if let Some(tls) = &config.server.tls {
+        RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+            .await
+            .map_err(|e| anyhow::anyhow!("invalid TLS cert/key ({}, {}): {}", tls.cert.display(), tls.key.display(), e))?;
+    }
 
-        ]
-    }))
-}
+    // 上流のツール名は接続するまで分からないため接頭辞だけ確認する
+    let local = mod_tools::default_registry();
+    let prefixes: Vec<String> = resources.upstreams.iter().map(|u| format!("{}.", u.name)).collect();
+    for pattern in config.tools.unmatched(&local.names()) {
+        if !prefixes.iter().any(|prefix| pattern.starts_with(prefix.as_str())) {
+            println!("warning: tool pattern matches no tool: {}", pattern);
+        }
+    }
 
-fn purchase(product_name: String, price: i32) -> String {
-    format!("「{}」を{}円で購入しました。", product_name, price)
+    let server = &config.server;
+    println!(
+        "server: {} {}, transport={:?}, bind={}, tls={}",
+        server.name,
+        server.version,
+        server.transport,
+        server.bind,
+        server.tls.is_some()
+    );
+    println!(
+        "database: {}, purchase={}",
+        config.database.path,
+        resources.purchase_backend.describe()
+    );
+    println!(
+        "auth: api_keys={}, oauth={}",
+        resources.auth.key_count(),
+        resources.oauth.as_ref().map(|o| o.key_count()).unwrap_or_default()
+    );
+    println!("rate limits: {}", config.rate_limit.describe());
+    let upstreams: Vec<&str> = resources.upstreams.iter().map(|u| u.name.as_str()).collect();
+    println!("upstreams: {}", if upstreams.is_empty() { "-".to_string() } else { upstreams.join(", ") });
+    Ok(())
 }
-// tools/call メソッド
-async fn handle_tools_call(state: Arc<AppState>, params: Option<Value>) -> Result<Value, JsonRpcError> {
-    let params = params.ok_or(JsonRpcError {
-        code: -32602,
-        message: "Invalid params".to_string(),
-        data: None,
-    })?;
-
-    let tool_name = params["name"].as_str().ok_or(JsonRpcError {
-        code: -32602,
-        message: "Tool name is required".to_string(),
-        data: None,
-    })?;
 
-    let arguments = &params["arguments"];
+// HTTP / HTTPS で待ち受け、SIGTERM / Ctrl-C で処理中のリクエストを待って終了する
+async fn serve(config: &Config, state: Arc<AppState>) -> anyhow::Result<()> {
+    let handle = Handle::new();
+    let app = mod_http::router(state.clone()).into_make_service();
+    let scheme = if config.server.tls.is_some() { "https" } else { "http" };
 
-    match tool_name {
-        "echo" => {
-            let message = arguments["message"].as_str().unwrap_or("No message");
-            Ok(json!({
-                "content": [
-                    {
-                        "type": "text",
-                        "text": format!("Echo: {}", message)
-                    }
-                ]
-            }))
+    let listening = handle.clone();
+    tokio::spawn(async move {
+        if let Some(addr) = listening.listening().await {
+            tracing::info!("MCP Server listening on {}://{}", scheme, addr);
         }
-        "add" => {
-            let a = arguments["a"].as_f64().unwrap_or(0.0);
-            let b = arguments["b"].as_f64().unwrap_or(0.0);
-            Ok(json!({
-                "content": [
-                    {
-                        "type": "text",
-                        "text": format!("Result: {}", a + b)
-                    }
-                ]
-            }))
-        },
-        "purchase" => {
-            let name = arguments["name"].as_str().ok_or(JsonRpcError {
-                code: -32602,
-                message: "name is required".to_string(),
-                data: None,
-            })?;    
-            let price = arguments["price"].as_i64().unwrap_or(0);        
-            let result_text = mod_purchase::purchase_handler(name.to_string(), price as i32).await;
-            let out_text = format!("{} {} 円 登録しました", name, price);
-            Ok(json!({
-                "content": [
-                    {
-                        "type": "text",
-                        "text": format!("Result: {}", out_text),
-                    }
-                ]
-            }))
-        },
-        "purchase_list" => {
-            let result_text = mod_purchase::purchase_list_handler().await;
-            if result_text == "" {
-             return Err(JsonRpcError {
-                    code: -32603,
-                    message: "Internal error".to_string(),
-                    data: None,
-                })   
-            }
-            Ok(json!({
-                "content": [
-                    {
-                        "type": "text",
-                        "text": format!("{}", result_text),
-                    }
-                ]
-            }))
-        },                
-        "add_todo" => {
-            let title = arguments["title"].as_str().ok_or(JsonRpcError {
-                code: -32602,
-                message: "Title is required".to_string(),
-                data: None,
-            })?;
-
-            let db = Builder::new_local(&state.db_path).build().await.map_err(|e| JsonRpcError {
-                code: -32603,
-                message: format!("Database connection error: {}", e),
-                data: None,
-            })?;
-
-            let conn = db.connect().map_err(|e| JsonRpcError {
-                code: -32603,
-                message: format!("Database connection error: {}", e),
-                data: None,
-            })?;
+    });
+    tokio::spawn(sweep_sessions(state.clone()));
+    tokio::spawn(graceful_shutdown(handle.clone(), state, config.shutdown_timeout()));
 
-            conn.execute("INSERT INTO todo (title) VALUES (?)", [title])
+    match &config.server.tls {
+        Some(tls) => {
+            let rustls = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
                 .await
-                .map_err(|e| JsonRpcError {
-                    code: -32603,
-                    message: format!("Database insert error: {}", e),
-                    data: None,
-                })?;
-
-            let last_id = conn.last_insert_rowid();
-
-            Ok(json!({
-                "content": [
-                    {
-                        "type": "text",
-                        "text": format!("Todo added successfully with ID: {}", last_id)
-                    }
-                ]
-            }))
+                .map_err(|e| anyhow::anyhow!("invalid TLS cert/key: {}", e))?;
+            axum_server::bind_rustls(config.server.bind, rustls)
+                .handle(handle)
+                .serve(app)
+                .await?;
+        }
+        None => {
+            axum_server::bind(config.server.bind).handle(handle).serve(app).await?;
         }
-        _ => Err(JsonRpcError {
-            code: -32602,
-            message: format!("Unknown tool: {}", tool_name),
-            data: None,
-        }),
     }
+    Ok(())
 }
 
-// resources/list メソッド
-fn handle_resources_list() -> Result<Value, JsonRpcError> {
-    Ok(json!({
-        "resources": [
-            {
-                "uri": "file:///example.txt",
-                "name": "Example Resource",
-                "description": "An example resource",
-                "mimeType": "text/plain"
-            }
-        ]
-    }))
+// 使われなくなったセッションを定期的に破棄する
+async fn sweep_sessions(state: Arc<AppState>) {
+    let mut interval = tokio::time::interval(state.sessions.sweep_interval());
+    loop {
+        interval.tick().await;
+        state.sessions.sweep();
+    }
 }
 
-// resources/read メソッド
-fn handle_resources_read(params: Option<Value>) -> Result<Value, JsonRpcError> {
-    let params = params.ok_or(JsonRpcError {
-        code: -32602,
-        message: "Invalid params".to_string(),
-        data: None,
-    })?;
-
-    let uri = params["uri"].as_str().ok_or(JsonRpcError {
-        code: -32602,
-        message: "URI is required".to_string(),
-        data: None,
-    })?;
+async fn graceful_shutdown(handle: Handle, state: Arc<AppState>, timeout: Duration) {
+    shutdown_signal().await;
+    tracing::info!("shutdown signal received, waiting up to {}s for in-flight requests", timeout.as_secs());
+    // GET /mcp の SSE は終わらないため先に閉じる
+    state.sessions.close_streams();
+    handle.graceful_shutdown(Some(timeout));
+}
 
-    Ok(json!({
-        "contents": [
-            {
-                "uri": uri,
-                "mimeType": "text/plain",
-                "text": "This is the content of the resource"
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
             }
-        ]
-    }))
+            Err(e) => {
+                tracing::warn!("cannot listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
 
-// prompts/list メソッド
-fn handle_prompts_list() -> Result<Value, JsonRpcError> {
-    Ok(json!({
-        "prompts": [
-            {
-                "name": "example_prompt",
-                "description": "An example prompt",
-                "arguments": []
-            }
-        ]
-    }))
+// todo と監査ログ (同じローカルファイルを共有)
+async fn open_local_stores(path: &str) -> Result<(TodoStore, AuditLog), libsql::Error> {
+    let db = Db::open_local(path).await?;
+    let todos = TodoStore::open(db.clone()).await?;
+    let audit = AuditLog::open(db).await?;
+    Ok((todos, audit))
 }
@@ -0,0 +1,165 @@
+use libsql::{params, params_from_iter, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::mod_db::Db;
+
+// tools/call の監査ログ
+//
+// 引数そのものは保存せず、SHA-256 ダイジェストのみを記録する。
+const MIGRATIONS: &[(&str, &str)] = &[(
+    "audit_001_create",
+    "CREATE TABLE IF NOT EXISTS tool_call_audit (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        called_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        tool TEXT NOT NULL,
+        arguments_digest TEXT NOT NULL,
+        caller TEXT NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        outcome TEXT NOT NULL,
+        error_code INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS idx_tool_call_audit_called_at ON tool_call_audit (called_at);
+    CREATE INDEX IF NOT EXISTS idx_tool_call_audit_tool ON tool_call_audit (tool);",
+)];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub called_at: String,
+    pub tool: String,
+    pub arguments_digest: String,
+    pub caller: String,
+    pub duration_ms: i64,
+    pub outcome: String,
+    pub error_code: Option<i64>,
+}
+
+impl AuditEntry {
+    fn from_row(row: &Row) -> Result<Self, libsql::Error> {
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            called_at: row.get(1)?,
+            tool: row.get(2)?,
+            arguments_digest: row.get(3)?,
+            caller: row.get(4)?,
+            duration_ms: row.get(5)?,
+            outcome: row.get(6)?,
+            error_code: row.get(7)?,
+        })
+    }
+}
+
+// 記録する1件分
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    pub tool: String,
+    pub arguments_digest: String,
+    pub caller: String,
+    pub duration_ms: i64,
+    pub error_code: Option<i64>,
+}
+
+// GET /admin/audit の検索条件
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditFilter {
+    pub tool: Option<String>,
+    pub caller: Option<String>,
+    pub outcome: Option<String>,
+    // called_at >= since (YYYY-MM-DD または YYYY-MM-DD HH:MM:SS)
+    pub since: Option<String>,
+}
+
+impl AuditFilter {
+    fn where_clause(&self) -> (String, Vec<libsql::Value>) {
+        let mut conditions = vec!["1 = 1".to_string()];
+        let mut values = Vec::new();
+        for (column, value) in [
+            ("tool =", &self.tool),
+            ("caller =", &self.caller),
+            ("outcome =", &self.outcome),
+            ("called_at >=", &self.since),
+        ] {
+            if let Some(value) = value {
+                values.push(libsql::Value::Text(value.clone()));
+                conditions.push(format!("{} ?{}", column, values.len()));
+            }
+        }
+        (conditions.join(" AND "), values)
+    }
+}
+
+// 引数のダイジェスト (serde_json の Map はキー順にシリアライズされる)
+pub fn arguments_digest(arguments: &Value) -> String {
+    hex::encode(Sha256::digest(arguments.to_string().as_bytes()))
+}
+
+#[derive(Clone)]
+pub struct AuditLog {
+    db: Db,
+}
+
+impl AuditLog {
+    pub async fn open(db: Db) -> Result<Self, libsql::Error> {
+        db.migrate(MIGRATIONS).await?;
+        Ok(AuditLog { db })
+    }
+
+    pub async fn record(&self, entry: &NewAuditEntry) -> Result<(), libsql::Error> {
+        let outcome = if entry.error_code.is_some() { "error" } else { "ok" };
+        self.db
+            .conn()
+            .execute(
+                "INSERT INTO tool_call_audit (tool, arguments_digest, caller, duration_ms, outcome, error_code)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    entry.tool.as_str(),
+                    entry.arguments_digest.as_str(),
+                    entry.caller.as_str(),
+                    entry.duration_ms,
+                    outcome,
+                    entry.error_code
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    // 新しい順に取得 (戻り値は該当件数の合計と一緒に返す)
+    pub async fn query(
+        &self,
+        filter: &AuditFilter,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<AuditEntry>, i64), libsql::Error> {
+        let (condition, mut values) = filter.where_clause();
+
+        let count_sql = format!("SELECT COUNT(*) FROM tool_call_audit WHERE {}", condition);
+        let mut rows = self
+            .db
+            .conn()
+            .query(&count_sql, params_from_iter(values.clone()))
+            .await?;
+        let total: i64 = match rows.next().await? {
+            Some(row) => row.get(0)?,
+            None => 0,
+        };
+
+        values.push(libsql::Value::Integer(limit));
+        values.push(libsql::Value::Integer(offset));
+        let sql = format!(
+            "SELECT id, called_at, tool, arguments_digest, caller, duration_ms, outcome, error_code
+            FROM tool_call_audit WHERE {} ORDER BY id DESC LIMIT ?{} OFFSET ?{}",
+            condition,
+            values.len() - 1,
+            values.len()
+        );
+        let mut rows = self.db.conn().query(&sql, params_from_iter(values)).await?;
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next().await? {
+            entries.push(AuditEntry::from_row(&row)?);
+        }
+        Ok((entries, total))
+    }
+}
@@ -0,0 +1,157 @@
+use axum::http::{header, HeaderMap};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::mod_ratelimit::Rate;
+use crate::mod_tools::Tool;
+
+// API キー認証
+//
+// キーは名前・ハッシュ (sha256:<hex>)・スコープで管理し、平文は保持しない。
+// スコープ:
+//   *            すべて許可
+//   tools:*      すべてのツールを呼び出せる
+//   tools:read   読み取り専用ツール (readOnlyHint) のみ呼び出せる
+//   tools:<name> 指定ツールを呼び出せる
+//   resources:read リソースの一覧・読み取り・購読・補完
+//   prompts:read   プロンプトの一覧・取得・補完 (購入・todo のデータを含む)
+//   admin        監査ログ (GET /admin/audit)
+//   metrics      メトリクス (GET /metrics)
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiKey {
+    pub name: String,
+    pub hash: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // キーごとのレート制限 (例: "120/min", 未指定時は RATE_LIMIT_PER_KEY)
+    #[serde(default)]
+    pub rate_limit: Option<Rate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiKeysFile {
+    #[serde(default)]
+    keys: Vec<ApiKey>,
+}
+
+pub const RESOURCES_READ: &str = "resources:read";
+pub const PROMPTS_READ: &str = "prompts:read";
+pub const ADMIN: &str = "admin";
+pub const METRICS: &str = "metrics";
+
+// OAuth の呼び出し元の名前 (oauth:<sub>)。API キーの名前には使えない
+pub const OAUTH_PREFIX: &str = "oauth:";
+
+// 認証済みの呼び出し元
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub rate_limit: Option<Rate>,
+}
+
+impl Caller {
+    // 認証なし (stdio / キー未設定)
+    pub fn local() -> Self {
+        Caller {
+            name: "local".to_string(),
+            scopes: vec!["*".to_string()],
+            rate_limit: None,
+        }
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == "*" || s == scope)
+    }
+
+    pub fn can_call_tool(&self, tool: &dyn Tool) -> bool {
+        self.has_scope("tools:*")
+            || self.has_scope(&format!("tools:{}", tool.name()))
+            || (tool.read_only() && self.has_scope("tools:read"))
+    }
+}
+
+pub fn hash_key(secret: &str) -> String {
+    format!("sha256:{}", hex::encode(Sha256::digest(secret.as_bytes())))
+}
+
+// 長さ以外で早期に抜けない比較
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeyStore {
+    keys: Vec<ApiKey>,
+}
+
+impl ApiKeyStore {
+    // キーファイル (TOML) と旧来の API_KEY 環境変数から読み込む
+    pub fn load(path: Option<&str>, legacy_key: Option<&str>) -> anyhow::Result<Self> {
+        let mut keys = Vec::new();
+        if let Some(path) = path {
+            let text = std::fs::read_to_string(Path::new(path))
+                .map_err(|e| anyhow::anyhow!("cannot read API keys file {}: {}", path, e))?;
+            let file: ApiKeysFile = toml::from_str(&text)?;
+            for key in &file.keys {
+                if !key.hash.starts_with("sha256:") {
+                    anyhow::bail!("API key '{}': hash must be sha256:<hex>", key.name);
+                }
+            }
+            keys.extend(file.keys);
+        }
+        if let Some(secret) = legacy_key.filter(|k| !k.is_empty()) {
+            keys.push(ApiKey {
+                name: "default".to_string(),
+                hash: hash_key(secret),
+                scopes: vec!["*".to_string()],
+                rate_limit: None,
+            });
+        }
+        // 名前はセッションの所有者・レート制限・監査ログで呼び出し元を区別するため一意にする
+        let mut names = HashSet::new();
+        for key in &keys {
+            if key.name.starts_with(OAUTH_PREFIX) {
+                anyhow::bail!("API key '{}': names starting with '{}' are reserved", key.name, OAUTH_PREFIX);
+            }
+            if !names.insert(key.name.as_str()) {
+                anyhow::bail!("duplicate API key name: {}", key.name);
+            }
+        }
+        Ok(ApiKeyStore { keys })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn authenticate(&self, token: &str) -> Option<Caller> {
+        let hash = hash_key(token);
+        self.keys
+            .iter()
+            .find(|k| constant_time_eq(k.hash.as_bytes(), hash.as_bytes()))
+            .map(|k| Caller {
+                name: k.name.clone(),
+                scopes: k.scopes.clone(),
+                rate_limit: k.rate_limit,
+            })
+    }
+}
+
+// Authorization ヘッダーからトークンを取り出す ("Bearer <token>" または旧形式の生の値)
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?.trim();
+    if value.is_empty() || value.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+    match value.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => Some(token.trim()),
+        _ => Some(value),
+    }
+}
@@ -0,0 +1,113 @@
+use serde_json::{json, Value};
+
+use crate::mod_rpc::JsonRpcError;
+use crate::mod_session::RequestContext;
+
+// サーバー → クライアントのリクエストが失敗した (未対応・未接続・エラー応答)
+pub const CLIENT_REQUEST_FAILED: i32 = -32006;
+
+// クライアントへのリクエスト (elicitation/create, sampling/createMessage)
+//
+// 通知と同じストリーム (POST の SSE 応答 / GET /mcp / stdio) で送り、
+// クライアントが POST で返す応答を待つ。待ち時間はツールの実行時間の上限に含まれる。
+pub struct Client {
+    ctx: Option<RequestContext>,
+}
+
+// elicitation/create の結果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Elicitation {
+    Accept(Value),
+    Decline,
+    Cancel,
+}
+
+impl Elicitation {
+    pub fn action(&self) -> &'static str {
+        match self {
+            Elicitation::Accept(_) => "accept",
+            Elicitation::Decline => "decline",
+            Elicitation::Cancel => "cancel",
+        }
+    }
+}
+
+fn failed(method: &str, reason: impl Into<String>) -> JsonRpcError {
+    JsonRpcError {
+        code: CLIENT_REQUEST_FAILED,
+        message: "Client request failed".to_string(),
+        data: Some(json!({ "method": method, "reason": reason.into() })),
+    }
+}
+
+impl Client {
+    pub fn new(ctx: RequestContext) -> Self {
+        Client { ctx: Some(ctx) }
+    }
+
+    // クライアントに問い合わせない (直接呼び出し)
+    pub fn none() -> Self {
+        Client { ctx: None }
+    }
+
+    // initialize で宣言された capabilities (elicitation / sampling)
+    pub fn supports(&self, capability: &str) -> bool {
+        self.ctx
+            .as_ref()
+            .and_then(|ctx| ctx.session.as_ref())
+            .and_then(|session| session.client())
+            .is_some_and(|client| client.capabilities.get(capability).is_some_and(Value::is_object))
+    }
+
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, JsonRpcError> {
+        let ctx = self.ctx.as_ref().ok_or_else(|| failed(method, "no client"))?;
+        let session = ctx.session.as_ref().ok_or_else(|| failed(method, "no session"))?;
+        let mut pending = session.pending_request();
+        let sent = ctx.notify(json!({
+            "jsonrpc": "2.0",
+            "id": pending.id,
+            "method": method,
+            "params": params
+        }));
+        if !sent {
+            return Err(failed(method, "no stream to the client (open GET /mcp or use an SSE response)"));
+        }
+        tracing::info!("client request sent: session={}, method={}, id={}", session.id, method, pending.id);
+
+        match pending.response().await {
+            Some(Ok(result)) => Ok(result),
+            Some(Err(error)) => Err(JsonRpcError {
+                code: CLIENT_REQUEST_FAILED,
+                message: "Client request failed".to_string(),
+                data: Some(json!({ "method": method, "error": error })),
+            }),
+            None => Err(failed(method, "session closed")),
+        }
+    }
+
+    // elicitation/create: ユーザーに入力 (確認) を求める
+    pub async fn elicit(&self, message: &str, requested_schema: Value) -> Result<Elicitation, JsonRpcError> {
+        const METHOD: &str = "elicitation/create";
+        if !self.supports("elicitation") {
+            return Err(failed(METHOD, "client does not support elicitation"));
+        }
+        let result = self
+            .request(METHOD, json!({ "message": message, "requestedSchema": requested_schema }))
+            .await?;
+        match result["action"].as_str() {
+            Some("accept") => Ok(Elicitation::Accept(result["content"].clone())),
+            Some("decline") => Ok(Elicitation::Decline),
+            Some("cancel") => Ok(Elicitation::Cancel),
+            _ => Err(failed(METHOD, format!("invalid response: {}", result))),
+        }
+    }
+
+    // sampling/createMessage: クライアント側の LLM で応答を生成する
+    pub async fn create_message(&self, params: Value) -> Result<Value, JsonRpcError> {
+        const METHOD: &str = "sampling/createMessage";
+        if !self.supports("sampling") {
+            return Err(failed(METHOD, "client does not support sampling"));
+        }
+        self.request(METHOD, params).await
+    }
+}
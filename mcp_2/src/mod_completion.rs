@@ -0,0 +1,69 @@
+use serde_json::{json, Value};
+
+use crate::mod_auth::{PROMPTS_READ, RESOURCES_READ};
+use crate::mod_resources;
+use crate::mod_rpc::{forbidden_scope, JsonRpcError};
+use crate::mod_session::RequestContext;
+use crate::AppState;
+
+// 1回の応答で返す候補の上限 (MCP の仕様上限)
+const MAX_VALUES: usize = 100;
+
+// completion/complete メソッド
+//
+// ref/prompt はプロンプト引数 (prompts:read)、ref/resource はリソーステンプレートの引数 (resources:read) を補完する。
+// 候補が上限を超える場合は hasMore を立てる。
+pub async fn handle_completion_complete(
+    state: &AppState,
+    ctx: &RequestContext,
+    params: Option<Value>,
+) -> Result<Value, JsonRpcError> {
+    let params = params.ok_or_else(|| JsonRpcError::invalid_params("Invalid params"))?;
+    let argument = params["argument"]["name"]
+        .as_str()
+        .ok_or_else(|| JsonRpcError::invalid_params("argument.name is required"))?;
+    let value = params["argument"]["value"].as_str().unwrap_or("");
+
+    // 上限 + 1 件取得して続きの有無を判定する
+    let limit = MAX_VALUES + 1;
+    let reference = &params["ref"];
+    let mut values = match reference["type"].as_str() {
+        Some("ref/prompt") => {
+            if !ctx.caller.has_scope(PROMPTS_READ) {
+                return Err(forbidden_scope(ctx, PROMPTS_READ, "completion/complete"));
+            }
+            let name = reference["name"]
+                .as_str()
+                .ok_or_else(|| JsonRpcError::invalid_params("ref.name is required"))?;
+            let prompt = state
+                .prompts
+                .get(name)
+                .ok_or_else(|| JsonRpcError::invalid_params(format!("Unknown prompt: {}", name)))?;
+            prompt.complete(state, argument, value, limit).await?
+        }
+        Some("ref/resource") => {
+            if !ctx.caller.has_scope(RESOURCES_READ) {
+                return Err(forbidden_scope(ctx, RESOURCES_READ, "completion/complete"));
+            }
+            let uri = reference["uri"]
+                .as_str()
+                .ok_or_else(|| JsonRpcError::invalid_params("ref.uri is required"))?;
+            mod_resources::complete(state, uri, argument, value, limit).await?
+        }
+        other => {
+            return Err(JsonRpcError::invalid_params(format!(
+                "Unknown ref type: {}",
+                other.unwrap_or("-")
+            )));
+        }
+    };
+
+    let has_more = values.len() > MAX_VALUES;
+    values.truncate(MAX_VALUES);
+    Ok(json!({
+        "completion": {
+            "values": values,
+            "hasMore": has_more
+        }
+    }))
+}
@@ -0,0 +1,463 @@
+use clap::Parser;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::mod_db::Backend;
+use crate::mod_oauth::OAuthSettings;
+use crate::mod_ratelimit::{self, Rate, RateLimitConfig};
+use crate::mod_upstream::{self, UpstreamConfig};
+use crate::{DEFAULT_SESSION_IDLE_TIMEOUT, DEFAULT_TOOL_TIMEOUT};
+
+// 設定
+//
+// 優先順位: 既定値 < 設定ファイル (TOML, --config) < 環境変数 < コマンドライン引数
+// 環境変数は従来の名前 (DB_PATH, API_KEY, OAUTH_* など) をそのまま受け付ける。
+#[derive(Debug, Parser, Default)]
+#[command(version, about = "axum + Rust remote MCP server")]
+pub struct Cli {
+    /// 設定ファイル (TOML)
+    #[arg(short, long, env = "MCP_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// 待ち受けアドレス (例: 0.0.0.0:3000)
+    #[arg(long)]
+    pub bind: Option<SocketAddr>,
+    /// stdio トランスポートで起動する
+    #[arg(long)]
+    pub stdio: bool,
+    /// TLS 証明書 (PEM)
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// TLS 秘密鍵 (PEM)
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+    /// todo・監査ログのローカル libsql ファイル
+    #[arg(long)]
+    pub db_path: Option<String>,
+    /// ログレベル (tracing の EnvFilter 形式, 例: info,rust_remoto_mcp_2=debug)
+    #[arg(long)]
+    pub log_level: Option<String>,
+    /// 公開するツール (カンマ区切り, 末尾 * で前方一致)
+    #[arg(long, value_delimiter = ',')]
+    pub tools: Option<Vec<String>>,
+    /// 設定を検証して終了する
+    #[arg(long)]
+    pub check_config: bool,
+    /// キーファイル用のハッシュを出力して終了する (標準入力の1行目)
+    #[arg(long)]
+    pub hash_key: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub tools: ToolsConfig,
+    pub auth: AuthConfig,
+    pub rate_limit: RateLimitConfig,
+    // 上流 MCP サーバー (UPSTREAMS_FILE の内容と合わせて使う)
+    pub upstreams_file: Option<String>,
+    pub upstreams: Vec<UpstreamConfig>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Http,
+    Stdio,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub name: String,
+    pub version: String,
+    pub bind: SocketAddr,
+    pub transport: Transport,
+    pub log_level: String,
+    // ツールの実行時間の上限 (秒)
+    pub tool_timeout_secs: u64,
+    // SIGTERM 後に処理中のリクエストを待つ時間 (秒)
+    pub shutdown_timeout_secs: u64,
+    // 使われなくなった Streamable HTTP セッションを破棄するまでの時間 (秒)
+    pub session_idle_timeout_secs: u64,
+    // キーごとに同時に持てるセッション数の上限 (未指定なら無制限)
+    pub max_sessions_per_key: Option<usize>,
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    // todo・監査ログ (ローカル libsql ファイル)
+    pub path: String,
+    pub turso_url: Option<String>,
+    pub turso_auth_token: Option<String>,
+    pub purchase: PurchaseDbConfig,
+}
+
+// 購入データの保存先 (未指定の項目は database.* から決める)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PurchaseDbConfig {
+    // local | remote | replica
+    pub backend: Option<String>,
+    pub path: Option<String>,
+    pub sync_interval_secs: Option<u64>,
+}
+
+// 公開するツール (enabled 未指定なら全て、disabled は enabled より優先)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    pub enabled: Option<Vec<String>>,
+    pub disabled: Vec<String>,
+    // この金額 (単価 × 数量) を超える purchase は elicitation でユーザーに確認する
+    pub purchase_confirm_above: Option<i64>,
+    // ツールごとの実行時間の上限 (秒, 例: { purchase_insights = 300 })
+    pub timeouts: HashMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    // 旧来の単一キー (キー名 default、全権限)
+    pub api_key: Option<String>,
+    pub api_keys_file: Option<String>,
+    pub oauth: Option<OAuthSettings>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            name: "MCP Server Example".to_string(),
+            version: "1.0.0".to_string(),
+            bind: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            transport: Transport::Http,
+            log_level: "info".to_string(),
+            tool_timeout_secs: DEFAULT_TOOL_TIMEOUT.as_secs(),
+            shutdown_timeout_secs: 10,
+            session_idle_timeout_secs: DEFAULT_SESSION_IDLE_TIMEOUT.as_secs(),
+            max_sessions_per_key: None,
+            tls: None,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            path: "data.db".to_string(),
+            turso_url: None,
+            turso_auth_token: None,
+            purchase: PurchaseDbConfig::default(),
+        }
+    }
+}
+
+// TOOL_TIMEOUTS (例: purchase=300,purchase_insights=300)
+pub fn parse_tool_timeouts(spec: &str) -> anyhow::Result<HashMap<String, u64>> {
+    let mut timeouts = HashMap::new();
+    for entry in spec.split(',').filter(|e| !e.trim().is_empty()) {
+        let (tool, secs) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("TOOL_TIMEOUTS entry must be <tool>=<seconds>: {}", entry))?;
+        let secs = secs
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("TOOL_TIMEOUTS entry must be <tool>=<seconds>: {}: {}", entry, e))?;
+        timeouts.insert(tool.trim().to_string(), secs);
+    }
+    Ok(timeouts)
+}
+
+// "*" で終わるパターンは前方一致
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => pattern == name,
+    }
+}
+
+impl ToolsConfig {
+    pub fn allows(&self, name: &str) -> bool {
+        let enabled = match &self.enabled {
+            Some(patterns) => patterns.iter().any(|p| matches(p, name)),
+            None => true,
+        };
+        enabled && !self.disabled.iter().any(|p| matches(p, name))
+    }
+
+    // どのツールにも一致しないパターン
+    pub fn unmatched<'a>(&'a self, names: &[&str]) -> Vec<&'a str> {
+        self.enabled
+            .iter()
+            .flatten()
+            .chain(&self.disabled)
+            .filter(|p| !names.iter().any(|name| matches(p, name)))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+impl Config {
+    // 設定ファイル → 環境変数 → コマンドライン引数の順に読み込んで検証する
+    pub fn load(cli: &Cli) -> anyhow::Result<Self> {
+        let mut config = match &cli.config {
+            Some(path) => Self::from_file(path)?,
+            None => Config::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))?;
+        config.apply_cli(cli);
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &std::path::Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("cannot read config file {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| anyhow::anyhow!("invalid config file {}: {}", path.display(), e))
+    }
+
+    // 環境変数で上書き (var は未設定・空なら None を返す)
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        let secs = |name: &str| -> anyhow::Result<Option<u64>> {
+            var(name)
+                .map(|v| v.parse().map_err(|e| anyhow::anyhow!("{} must be seconds: {}", name, e)))
+                .transpose()
+        };
+        let list = |value: String| -> Vec<String> {
+            value.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect()
+        };
+
+        // server
+        if let Some(bind) = var("MCP_BIND") {
+            self.server.bind = bind
+                .parse()
+                .map_err(|e| anyhow::anyhow!("MCP_BIND must be <ip>:<port>: {}", e))?;
+        }
+        if let Some(transport) = var("MCP_TRANSPORT") {
+            self.server.transport = match transport.as_str() {
+                "http" => Transport::Http,
+                "stdio" => Transport::Stdio,
+                other => anyhow::bail!("unknown MCP_TRANSPORT: {}", other),
+            };
+        }
+        if let Some(level) = var("MCP_LOG_LEVEL") {
+            self.server.log_level = level;
+        }
+        match (var("MCP_TLS_CERT"), var("MCP_TLS_KEY")) {
+            (Some(cert), Some(key)) => {
+                self.server.tls = Some(TlsConfig {
+                    cert: cert.into(),
+                    key: key.into(),
+                })
+            }
+            (None, None) => {}
+            _ => anyhow::bail!("MCP_TLS_CERT and MCP_TLS_KEY must be set together"),
+        }
+        if let Some(timeout) = secs("TOOL_TIMEOUT_SECS")? {
+            self.server.tool_timeout_secs = timeout;
+        }
+        if let Some(timeout) = secs("SHUTDOWN_TIMEOUT_SECS")? {
+            self.server.shutdown_timeout_secs = timeout;
+        }
+        if let Some(timeout) = secs("SESSION_IDLE_TIMEOUT_SECS")? {
+            self.server.session_idle_timeout_secs = timeout;
+        }
+        if let Some(max) = var("MAX_SESSIONS_PER_KEY") {
+            self.server.max_sessions_per_key = Some(max.parse()?);
+        }
+
+        // database
+        if let Some(path) = var("DB_PATH") {
+            self.database.path = path;
+        }
+        if let Some(url) = var("TURSO_DATABASE_URL") {
+            self.database.turso_url = Some(url);
+        }
+        if let Some(token) = var("TURSO_AUTH_TOKEN") {
+            self.database.turso_auth_token = Some(token);
+        }
+        if let Some(backend) = var("PURCHASE_DB_BACKEND") {
+            self.database.purchase.backend = Some(backend);
+        }
+        if let Some(path) = var("PURCHASE_DB_PATH") {
+            self.database.purchase.path = Some(path);
+        }
+        if let Some(interval) = secs("PURCHASE_DB_SYNC_INTERVAL")? {
+            self.database.purchase.sync_interval_secs = Some(interval);
+        }
+
+        // tools
+        if let Some(tools) = var("MCP_TOOLS") {
+            self.tools.enabled = Some(list(tools));
+        }
+        if let Some(tools) = var("MCP_TOOLS_DISABLED") {
+            self.tools.disabled = list(tools);
+        }
+        if let Some(amount) = var("PURCHASE_CONFIRM_ABOVE") {
+            let amount = amount
+                .parse()
+                .map_err(|e| anyhow::anyhow!("PURCHASE_CONFIRM_ABOVE must be an integer: {}", e))?;
+            self.tools.purchase_confirm_above = Some(amount);
+        }
+        if let Some(timeouts) = var("TOOL_TIMEOUTS") {
+            self.tools.timeouts.extend(parse_tool_timeouts(&timeouts)?);
+        }
+
+        // auth
+        if let Some(key) = var("API_KEY") {
+            self.auth.api_key = Some(key);
+        }
+        if let Some(path) = var("API_KEYS_FILE") {
+            self.auth.api_keys_file = Some(path);
+        }
+        if let Some(jwks_file) = var("OAUTH_JWKS_FILE") {
+            self.auth.oauth.get_or_insert_with(Default::default).jwks_file = jwks_file;
+        }
+        if let Some(oauth) = self.auth.oauth.as_mut() {
+            if let Some(resource) = var("OAUTH_RESOURCE") {
+                oauth.resource = Some(resource);
+            }
+            if let Some(servers) = var("OAUTH_AUTHORIZATION_SERVERS") {
+                oauth.authorization_servers = list(servers);
+            }
+            if let Some(issuers) = var("OAUTH_ISSUER") {
+                oauth.issuers = list(issuers);
+            }
+            if let Some(audience) = var("OAUTH_AUDIENCE") {
+                oauth.audience = Some(audience);
+            }
+        }
+
+        // rate limit
+        if let Some(rate) = var("RATE_LIMIT_PER_KEY") {
+            self.rate_limit.per_key = Some(Rate::parse(&rate)?);
+        }
+        if let Some(tools) = var("RATE_LIMIT_TOOLS") {
+            self.rate_limit.per_tool.extend(mod_ratelimit::parse_tool_rates(&tools)?);
+        }
+        if let Some(max) = var("MAX_IN_FLIGHT") {
+            self.rate_limit.max_in_flight = Some(max.parse()?);
+        }
+
+        // upstreams
+        if let Some(path) = var("UPSTREAMS_FILE") {
+            self.upstreams_file = Some(path);
+        }
+        Ok(())
+    }
+
+    pub fn apply_cli(&mut self, cli: &Cli) {
+        if let Some(bind) = cli.bind {
+            self.server.bind = bind;
+        }
+        if cli.stdio {
+            self.server.transport = Transport::Stdio;
+        }
+        if let (Some(cert), Some(key)) = (&cli.tls_cert, &cli.tls_key) {
+            self.server.tls = Some(TlsConfig {
+                cert: cert.clone(),
+                key: key.clone(),
+            });
+        }
+        if let Some(path) = &cli.db_path {
+            self.database.path = path.clone();
+        }
+        if let Some(level) = &cli.log_level {
+            self.server.log_level = level.clone();
+        }
+        if let Some(tools) = &cli.tools {
+            self.tools.enabled = Some(tools.clone());
+        }
+    }
+
+    // ファイルを読まずに確認できる範囲の検証
+    pub fn validate(&self) -> anyhow::Result<()> {
+        tracing_subscriber::EnvFilter::try_new(&self.server.log_level)
+            .map_err(|e| anyhow::anyhow!("invalid log_level {:?}: {}", self.server.log_level, e))?;
+        if self.server.tool_timeout_secs == 0 {
+            anyhow::bail!("tool_timeout_secs must be positive");
+        }
+        if self.server.session_idle_timeout_secs == 0 {
+            anyhow::bail!("session_idle_timeout_secs must be positive");
+        }
+        if self.server.max_sessions_per_key == Some(0) {
+            anyhow::bail!("max_sessions_per_key must be positive");
+        }
+        if self.database.path.is_empty() {
+            anyhow::bail!("database.path must not be empty");
+        }
+        self.purchase_backend()?;
+        if let Some(oauth) = &self.auth.oauth
+            && oauth.jwks_file.is_empty()
+        {
+            anyhow::bail!("auth.oauth.jwks_file must be set");
+        }
+        if self
+            .tools
+            .enabled
+            .iter()
+            .flatten()
+            .chain(&self.tools.disabled)
+            .any(|p| p.is_empty())
+        {
+            anyhow::bail!("tool patterns must not be empty");
+        }
+        if let Some((tool, _)) = self.tools.timeouts.iter().find(|(_, secs)| **secs == 0) {
+            anyhow::bail!("tools.timeouts.{} must be positive", tool);
+        }
+        if self.tools.purchase_confirm_above.is_some_and(|amount| amount < 0) {
+            anyhow::bail!("tools.purchase_confirm_above must not be negative");
+        }
+        mod_upstream::validate(&self.upstreams)?;
+        Ok(())
+    }
+
+    pub fn tool_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.tool_timeout_secs)
+    }
+
+    // tools.timeouts (未指定のツールは tool_timeout か上流ごとの timeout_secs)
+    pub fn tool_timeouts(&self) -> HashMap<String, Duration> {
+        self.tools
+            .timeouts
+            .iter()
+            .map(|(tool, secs)| (tool.clone(), Duration::from_secs(*secs)))
+            .collect()
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.shutdown_timeout_secs)
+    }
+
+    pub fn session_idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.session_idle_timeout_secs)
+    }
+
+    // 購入データの接続先
+    pub fn purchase_backend(&self) -> anyhow::Result<Backend> {
+        Backend::from_config(&self.database)
+    }
+
+    // 上流 MCP サーバー (設定ファイル内 + upstreams_file)
+    pub fn load_upstreams(&self) -> anyhow::Result<Vec<UpstreamConfig>> {
+        let mut upstreams = self.upstreams.clone();
+        if let Some(path) = &self.upstreams_file {
+            upstreams.extend(mod_upstream::load(path)?);
+        }
+        mod_upstream::validate(&upstreams)?;
+        Ok(upstreams)
+    }
+}
@@ -0,0 +1,164 @@
+use libsql::{params, Builder, Connection, Database};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::mod_config::DatabaseConfig;
+
+// 接続先
+//
+// Local: ローカルファイル (オフライン / CI 用)
+// Remote: Turso へ直接接続
+// Replica: ローカルファイルに複製しつつ書き込みは Turso へ (embedded replica)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Backend {
+    Local {
+        path: String,
+    },
+    Remote {
+        url: String,
+        token: String,
+    },
+    Replica {
+        path: String,
+        url: String,
+        token: String,
+        sync_interval: Option<Duration>,
+    },
+}
+
+impl Backend {
+    // 設定から決定
+    //
+    // purchase.backend = local | remote | replica (未指定時は turso_url があれば remote)
+    // purchase.path = ローカルファイル (未指定時は database.path)
+    // purchase.sync_interval_secs = replica の同期間隔 (秒)
+    pub fn from_config(database: &DatabaseConfig) -> anyhow::Result<Self> {
+        let purchase = &database.purchase;
+        let url = database.turso_url.clone();
+        let token = database.turso_auth_token.clone().unwrap_or_default();
+        let path = purchase.path.clone().unwrap_or_else(|| database.path.clone());
+        let backend = purchase
+            .backend
+            .clone()
+            .unwrap_or_else(|| if url.is_some() { "remote" } else { "local" }.to_string());
+
+        let require_url = || {
+            url.clone()
+                .ok_or_else(|| anyhow::anyhow!("TURSO_DATABASE_URL must be set for {} backend", backend))
+        };
+        match backend.as_str() {
+            "local" => Ok(Backend::Local { path }),
+            "remote" => Ok(Backend::Remote {
+                url: require_url()?,
+                token,
+            }),
+            "replica" => Ok(Backend::Replica {
+                path,
+                url: require_url()?,
+                token,
+                sync_interval: purchase.sync_interval_secs.map(Duration::from_secs),
+            }),
+            other => anyhow::bail!("unknown purchase database backend: {}", other),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Backend::Local { path } => format!("local ({})", path),
+            Backend::Remote { url, .. } => format!("remote ({})", url),
+            Backend::Replica { path, url, .. } => format!("replica ({} <- {})", path, url),
+        }
+    }
+}
+
+// libsql データベース (起動時に1度だけ接続し、全リクエストで共有する)
+#[derive(Clone)]
+pub struct Db {
+    _db: Arc<Database>,
+    conn: Connection,
+}
+
+impl Db {
+    pub async fn open(backend: &Backend) -> Result<Self, libsql::Error> {
+        let db = match backend {
+            Backend::Local { path } => Builder::new_local(path).build().await?,
+            Backend::Remote { url, token } => Builder::new_remote(url.clone(), token.clone()).build().await?,
+            Backend::Replica {
+                path,
+                url,
+                token,
+                sync_interval,
+            } => {
+                let mut builder = Builder::new_remote_replica(path, url.clone(), token.clone());
+                if let Some(interval) = sync_interval {
+                    builder = builder.sync_interval(*interval);
+                }
+                let db = builder.build().await?;
+                // 起動時に最新状態へ同期
+                db.sync().await?;
+                db
+            }
+        };
+        let conn = db.connect()?;
+        Ok(Db {
+            _db: Arc::new(db),
+            conn,
+        })
+    }
+
+    pub async fn open_local(path: &str) -> Result<Self, libsql::Error> {
+        Self::open(&Backend::Local {
+            path: path.to_string(),
+        })
+        .await
+    }
+
+    pub fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    // マイグレーション実行
+    //
+    // 適用済みの名前は _migrations テーブルに記録するため、
+    // 複数のストアが同じデータベースファイルを共有しても衝突しない。
+    // 各マイグレーションと記録は1トランザクションで行い、途中で失敗したら何も残さない。
+    pub async fn migrate(&self, migrations: &[(&str, &str)]) -> Result<(), libsql::Error> {
+        self.conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS _migrations (
+                    name TEXT PRIMARY KEY,
+                    applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+                )",
+                (),
+            )
+            .await?;
+
+        for (name, sql) in migrations {
+            let mut rows = self
+                .conn
+                .query("SELECT 1 FROM _migrations WHERE name = ?1", params![*name])
+                .await?;
+            if rows.next().await?.is_some() {
+                continue;
+            }
+            tracing::info!("apply migration: {}", name);
+            let tx = self.conn.transaction().await?;
+            let applied = async {
+                tx.execute_batch(sql).await?;
+                tx.execute("INSERT INTO _migrations (name) VALUES (?1)", params![*name])
+                    .await?;
+                Ok::<_, libsql::Error>(())
+            }
+            .await;
+            match applied {
+                Ok(()) => tx.commit().await?,
+                Err(e) => {
+                    tracing::error!("migration failed, rolled back: {}: {}", name, e);
+                    tx.rollback().await?;
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequestParts, Query, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::UnboundedReceiverStream, StreamExt};
+use tower_http::trace::TraceLayer;
+
+use crate::mod_audit::AuditFilter;
+use crate::mod_auth::{self, Caller};
+use crate::mod_oauth;
+use crate::mod_ratelimit::RateLimitLayer;
+use crate::mod_rpc::{error_response, handle_message, parse_error, JsonRpcError, SUPPORTED_PROTOCOL_VERSIONS};
+use crate::mod_session::{RequestContext, Session};
+use crate::AppState;
+
+const MCP_SESSION_ID: &str = "mcp-session-id";
+const MCP_PROTOCOL_VERSION: &str = "mcp-protocol-version";
+
+// ルーター設定
+//
+// 認証とレート制限は route_layer で掛けるため、後から追加する
+// OAuth メタデータは認証なしで取得できる。
+pub fn router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", post(handle_jsonrpc))
+        .route(
+            "/mcp",
+            post(handle_jsonrpc)
+                .get(handle_sse)
+                .delete(handle_delete_session),
+        )
+        .route("/admin/audit", get(handle_audit))
+        .route("/metrics", get(handle_metrics))
+        .route_layer(RateLimitLayer::new(state.clone()))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .route(mod_oauth::METADATA_PATH, get(handle_resource_metadata))
+        .route(&format!("{}/mcp", mod_oauth::METADATA_PATH), get(handle_resource_metadata))
+        .with_state(state)
+        .layer(TraceLayer::new_for_http())
+}
+
+fn rejection(status: StatusCode, id: Option<Value>, error: JsonRpcError) -> Response {
+    (status, Json(error_response(id, error))).into_response()
+}
+
+// 認証失敗の理由
+#[derive(Debug, Clone, Copy)]
+enum AuthError {
+    // トークンなし
+    Missing,
+    // トークンが不正 (期限切れ・署名不一致・未登録のキー)
+    InvalidToken,
+}
+
+// Authorization ヘッダーの検証
+//
+// API キーと OAuth が共に未設定なら認証なし (全ツール許可)。
+// 監査ログにはキー名のみを記録し、トークンは出力しない。
+fn authorize(state: &AppState, headers: &HeaderMap) -> Result<Caller, AuthError> {
+    if !state.auth.is_enabled() && state.oauth.is_none() {
+        return Ok(Caller::local());
+    }
+
+    let Some(token) = mod_auth::bearer_token(headers) else {
+        tracing::info!(target: "audit", "authentication failed: no token");
+        return Err(AuthError::Missing);
+    };
+    if let Some(caller) = state.auth.authenticate(token) {
+        tracing::debug!(target: "audit", "authenticated: key={}", caller.name);
+        return Ok(caller);
+    }
+    if let Some(oauth) = &state.oauth {
+        match oauth.authenticate(token) {
+            Ok(caller) => {
+                tracing::debug!(target: "audit", "authenticated: key={}", caller.name);
+                return Ok(caller);
+            }
+            Err(e) => tracing::info!(target: "audit", "authentication failed: invalid access token: {}", e),
+        }
+    } else {
+        tracing::info!(target: "audit", "authentication failed: unknown API key");
+    }
+    Err(AuthError::InvalidToken)
+}
+
+// 401 応答 (WWW-Authenticate でメタデータの場所を通知)
+fn unauthorized(state: &AppState, error: AuthError) -> Response {
+    let challenge = match &state.oauth {
+        Some(oauth) => oauth.challenge(matches!(error, AuthError::InvalidToken)),
+        None => "Bearer".to_string(),
+    };
+    let mut response = rejection(
+        StatusCode::UNAUTHORIZED,
+        None,
+        JsonRpcError {
+            code: -32001,
+            message: "Unauthorized: Invalid API key".to_string(),
+            data: None,
+        },
+    );
+    if let Ok(value) = HeaderValue::from_str(&challenge) {
+        response.headers_mut().insert(header::WWW_AUTHENTICATE, value);
+    }
+    response
+}
+
+// 認証 (middleware)
+//
+// 結果をリクエストの extensions に入れ、レート制限とハンドラーで使う。
+async fn authenticate(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Response {
+    match authorize(&state, request.headers()) {
+        Ok(caller) => {
+            request.extensions_mut().insert(caller);
+        }
+        Err(error) => {
+            request.extensions_mut().insert(error);
+        }
+    }
+    next.run(request).await
+}
+
+// 認証済みの呼び出し元 (未認証なら 401)
+struct Authenticated(Caller);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for Authenticated {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self, Self::Rejection> {
+        if let Some(caller) = parts.extensions.get::<Caller>() {
+            return Ok(Authenticated(caller.clone()));
+        }
+        let error = parts.extensions.get::<AuthError>().copied().unwrap_or(AuthError::Missing);
+        Err(unauthorized(state, error))
+    }
+}
+
+// OAuth Protected Resource Metadata (GET)
+async fn handle_resource_metadata(State(state): State<Arc<AppState>>) -> Response {
+    match &state.oauth {
+        Some(oauth) => Json(oauth.metadata()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+// Mcp-Session-Id ヘッダーからセッションを取得
+//
+// ヘッダーが無い場合は Ok(None) (initialize 以外は呼び出し側で 400 にする)。
+// 期限切れのセッションと、別のキーが作成したセッションは存在しないものとして扱う。
+fn lookup_session(
+    state: &AppState,
+    headers: &HeaderMap,
+    caller: &Caller,
+) -> Result<Option<Arc<Session>>, JsonRpcError> {
+    let Some(session_id) = headers.get(MCP_SESSION_ID).and_then(|v| v.to_str().ok()) else {
+        return Ok(None);
+    };
+    state
+        .sessions
+        .get(session_id)
+        .filter(|session| session.owner == caller.name)
+        .map(Some)
+        .ok_or(JsonRpcError {
+            code: -32000,
+            message: "Session not found".to_string(),
+            data: None,
+        })
+}
+
+// MCP-Protocol-Version ヘッダーの検証 (ヘッダーが無い場合は旧クライアントとして許可)
+fn check_protocol_version(headers: &HeaderMap, session: Option<&Session>) -> Result<(), JsonRpcError> {
+    let Some(version) = headers.get(MCP_PROTOCOL_VERSION).and_then(|v| v.to_str().ok()) else {
+        return Ok(());
+    };
+    let negotiated = session.and_then(|s| s.client()).map(|c| c.protocol_version);
+    let valid = match &negotiated {
+        Some(negotiated) => negotiated == version,
+        None => SUPPORTED_PROTOCOL_VERSIONS.contains(&version),
+    };
+    if valid {
+        return Ok(());
+    }
+    Err(JsonRpcError {
+        code: -32600,
+        message: format!("Unsupported protocol version: {}", version),
+        data: Some(json!({
+            "supported": SUPPORTED_PROTOCOL_VERSIONS,
+            "negotiated": negotiated
+        })),
+    })
+}
+
+fn accepts_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"))
+}
+
+// SSE レスポンス (チャネルが閉じるとストリームも終了)
+fn sse_response(rx: mpsc::UnboundedReceiver<Value>) -> Response {
+    let stream = UnboundedReceiverStream::new(rx)
+        .map(|message| Ok::<_, Infallible>(Event::default().data(message.to_string())));
+    Sse::new(stream).keep_alive(KeepAlive::default()).into_response()
+}
+
+// メッセージ (単体またはバッチ) に指定メソッドのリクエストが含まれるか
+fn contains_method(message: &Value, method: &str) -> bool {
+    match message {
+        Value::Array(items) => items.iter().any(|item| contains_method(item, method)),
+        _ => message.get("method").and_then(Value::as_str) == Some(method),
+    }
+}
+
+// JSON-RPC ハンドラー (POST)
+async fn handle_jsonrpc(
+    State(state): State<Arc<AppState>>,
+    Authenticated(caller): Authenticated,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response
+{
+    let session = match lookup_session(&state, &headers, &caller) {
+        Ok(session) => session,
+        Err(error) => return rejection(StatusCode::NOT_FOUND, None, error),
+    };
+
+    if let Err(error) = check_protocol_version(&headers, session.as_deref()) {
+        return rejection(StatusCode::BAD_REQUEST, None, error);
+    }
+
+    let message: Value = match serde_json::from_slice(&body) {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::info!("parse error: {}", e);
+            return (StatusCode::BAD_REQUEST, Json(parse_error(e))).into_response();
+        }
+    };
+
+    // initialize 以外は Mcp-Session-Id が必要
+    let is_initialize = message.get("method").and_then(Value::as_str) == Some("initialize");
+    if session.is_none() && !is_initialize {
+        return rejection(
+            StatusCode::BAD_REQUEST,
+            None,
+            JsonRpcError {
+                code: -32000,
+                message: "Bad Request: Mcp-Session-Id header is required".to_string(),
+                data: None,
+            },
+        );
+    }
+
+    // tools/call は SSE で応答できる (クライアントが text/event-stream を受け付ける場合)
+    if contains_method(&message, "tools/call") && accepts_event_stream(&headers) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            // 進捗通知も同じストリームで返す
+            let ctx = RequestContext::new(session, caller).with_outbound(tx.clone());
+            if let Some(response) = handle_message(&state, &ctx, message).await {
+                let _ = tx.send(response);
+            }
+        });
+        return sse_response(rx);
+    }
+
+    // initialize (セッション未指定) ではセッションを払い出し、失敗したら破棄する
+    let new_session = if is_initialize && session.is_none() {
+        match state.sessions.create(&caller.name) {
+            Ok(session) => Some(session),
+            Err(error) => return rejection(StatusCode::TOO_MANY_REQUESTS, message.get("id").cloned(), error),
+        }
+    } else {
+        None
+    };
+
+    let ctx = RequestContext::new(session.or_else(|| new_session.clone()), caller);
+    let Some(response) = handle_message(&state, &ctx, message).await else {
+        // 通知のみ
+        return StatusCode::ACCEPTED.into_response();
+    };
+
+    let mut http_response = (StatusCode::OK, Json(&response)).into_response();
+    if let Some(session) = new_session {
+        if response.get("result").is_none() {
+            state.sessions.remove(&session.id);
+        } else if let Ok(value) = HeaderValue::from_str(&session.id) {
+            tracing::info!("session created: {}", session.id);
+            http_response.headers_mut().insert(MCP_SESSION_ID, value);
+        }
+    }
+    http_response
+}
+
+// SSE ストリーム (GET): サーバー → クライアント方向のメッセージ
+async fn handle_sse(
+    State(state): State<Arc<AppState>>,
+    Authenticated(caller): Authenticated,
+    headers: HeaderMap,
+) -> Response {
+    if !accepts_event_stream(&headers) {
+        return StatusCode::NOT_ACCEPTABLE.into_response();
+    }
+    let session = match lookup_session(&state, &headers, &caller) {
+        Ok(Some(session)) => session,
+        Ok(None) => return (StatusCode::BAD_REQUEST, "Mcp-Session-Id header is required").into_response(),
+        Err(error) => return rejection(StatusCode::NOT_FOUND, None, error),
+    };
+
+    tracing::info!("sse stream opened: {}", session.id);
+    sse_response(session.open_stream())
+}
+
+// セッション終了 (DELETE)
+async fn handle_delete_session(
+    State(state): State<Arc<AppState>>,
+    Authenticated(caller): Authenticated,
+    headers: HeaderMap,
+) -> Response {
+    let session = match lookup_session(&state, &headers, &caller) {
+        Ok(Some(session)) => session,
+        Ok(None) => return (StatusCode::BAD_REQUEST, "Mcp-Session-Id header is required").into_response(),
+        Err(error) => return rejection(StatusCode::NOT_FOUND, None, error),
+    };
+
+    state.sessions.remove(&session.id);
+    tracing::info!("session deleted: {}", session.id);
+    StatusCode::NO_CONTENT.into_response()
+}
+
+
+// 監査ログの検索 (GET /admin/audit?tool=&caller=&outcome=&since=&limit=&offset=)
+//
+// admin スコープのキーのみ。
+async fn handle_audit(
+    State(state): State<Arc<AppState>>,
+    Authenticated(caller): Authenticated,
+    Query(query): Query<AuditQuery>,
+) -> Response {
+    if !caller.has_scope(mod_auth::ADMIN) {
+        tracing::info!(target: "audit", "admin access denied: key={}", caller.name);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let offset = query.offset.unwrap_or(0).max(0);
+    match state.audit.query(&query.filter, limit, offset).await {
+        Ok((items, total)) => Json(json!({
+            "items": items,
+            "total": total,
+            "limit": limit,
+            "offset": offset
+        }))
+        .into_response(),
+        Err(e) => {
+            tracing::error!("audit query error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    #[serde(flatten)]
+    filter: AuditFilter,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+// Prometheus メトリクス (GET /metrics)
+//
+// 認証が有効な場合は metrics スコープのキーのみ。
+async fn handle_metrics(State(state): State<Arc<AppState>>, Authenticated(caller): Authenticated) -> Response {
+    if !caller.has_scope(mod_auth::METRICS) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        state.metrics.render(),
+    )
+        .into_response()
+}
@@ -0,0 +1,151 @@
+use serde_json::{json, Map, Value};
+use std::future::Future;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::mod_rpc::JsonRpcError;
+use crate::mod_session::RequestContext;
+
+// MCP logging (logging/setLevel, notifications/message)
+//
+// リクエスト処理中に出た tracing イベントを、そのリクエストのセッションへ
+// notifications/message として転送する。logging/setLevel で指定されたレベル以上のみ送り、
+// 指定していないセッションや他のセッションのリクエストのログは送らない。
+
+// RFC 5424 のレベル (低い順)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogLevel {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "debug" => Some(LogLevel::Debug),
+            "info" => Some(LogLevel::Info),
+            "notice" => Some(LogLevel::Notice),
+            "warning" => Some(LogLevel::Warning),
+            "error" => Some(LogLevel::Error),
+            "critical" => Some(LogLevel::Critical),
+            "alert" => Some(LogLevel::Alert),
+            "emergency" => Some(LogLevel::Emergency),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "debug",
+            LogLevel::Info => "info",
+            LogLevel::Notice => "notice",
+            LogLevel::Warning => "warning",
+            LogLevel::Error => "error",
+            LogLevel::Critical => "critical",
+            LogLevel::Alert => "alert",
+            LogLevel::Emergency => "emergency",
+        }
+    }
+
+    fn from_tracing(level: &Level) -> Self {
+        match *level {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warning,
+            Level::INFO => LogLevel::Info,
+            _ => LogLevel::Debug,
+        }
+    }
+}
+
+tokio::task_local! {
+    static REQUEST: RequestContext;
+}
+
+// future の実行中に出たログを ctx のクライアントへ転送する
+pub async fn scope<F: Future>(ctx: RequestContext, future: F) -> F::Output {
+    REQUEST.scope(ctx, future).await
+}
+
+// logging/setLevel メソッド
+pub fn handle_set_level(ctx: &RequestContext, params: Option<Value>) -> Result<Value, JsonRpcError> {
+    let session = ctx.require_session()?;
+    let level = params
+        .as_ref()
+        .and_then(|p| p["level"].as_str())
+        .ok_or_else(|| JsonRpcError::invalid_params("level is required"))?;
+    let level = LogLevel::parse(level)
+        .ok_or_else(|| JsonRpcError::invalid_params(format!("Unknown log level: {}", level)))?;
+    session.set_log_level(level);
+    tracing::info!("log level set: session={}, level={}", session.id, level.as_str());
+    Ok(json!({}))
+}
+
+// tracing イベントのフィールド (message を含む) を JSON に変換
+struct JsonVisitor(Map<String, Value>);
+
+impl Visit for JsonVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), json!(format!("{:?}", value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+}
+
+// notifications/message を送る tracing layer
+pub struct McpLogLayer;
+
+impl<S: Subscriber> Layer<S> for McpLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let level = LogLevel::from_tracing(event.metadata().level());
+        let _ = REQUEST.try_with(|request| {
+            let Some(session) = &request.session else {
+                return;
+            };
+            if session.log_level().is_none_or(|min| level < min) {
+                return;
+            }
+            let mut fields = JsonVisitor(Map::new());
+            event.record(&mut fields);
+            request.notify(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/message",
+                "params": {
+                    "level": level.as_str(),
+                    "logger": event.metadata().target(),
+                    "data": fields.0
+                }
+            }));
+        });
+    }
+}
+
+// このクレートのイベントのみ転送する (依存ライブラリ・監査ログは送らない)
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    McpLogLayer.with_filter(Targets::new().with_target(env!("CARGO_CRATE_NAME"), Level::DEBUG))
+}
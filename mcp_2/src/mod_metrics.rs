@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Prometheus 形式のメトリクス
+//
+// mcp_requests_total{method,outcome}            JSON-RPC リクエスト数
+// mcp_request_duration_seconds{method}          JSON-RPC 処理時間
+// mcp_tool_calls_total{tool,outcome}            tools/call の呼び出し数
+// mcp_tool_call_duration_seconds{tool}          tools/call の処理時間
+// mcp_rate_limited_total{limit}                 レート制限で拒否した数
+const BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+#[derive(Default)]
+struct Histogram {
+    counts: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (count, bound) in self.counts.iter_mut().zip(BUCKETS) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, label: &str, value: &str) {
+        for (count, bound) in self.counts.iter().zip(BUCKETS) {
+            let _ = writeln!(out, "{}_bucket{{{}=\"{}\",le=\"{}\"}} {}", name, label, value, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{{}=\"{}\",le=\"+Inf\"}} {}", name, label, value, self.count);
+        let _ = writeln!(out, "{}_sum{{{}=\"{}\"}} {}", name, label, value, self.sum);
+        let _ = writeln!(out, "{}_count{{{}=\"{}\"}} {}", name, label, value, self.count);
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    requests: BTreeMap<(String, &'static str), u64>,
+    request_duration: BTreeMap<String, Histogram>,
+    tool_calls: BTreeMap<(String, &'static str), u64>,
+    tool_duration: BTreeMap<String, Histogram>,
+    rate_limited: BTreeMap<&'static str, u64>,
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    inner: Mutex<Inner>,
+}
+
+fn outcome(ok: bool) -> &'static str {
+    if ok { "ok" } else { "error" }
+}
+
+// ラベル値のエスケープ
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe_request(&self, method: &str, ok: bool, elapsed: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.requests.entry((method.to_string(), outcome(ok))).or_default() += 1;
+        inner
+            .request_duration
+            .entry(method.to_string())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_tool_call(&self, tool: &str, ok: bool, elapsed: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.tool_calls.entry((tool.to_string(), outcome(ok))).or_default() += 1;
+        inner
+            .tool_duration
+            .entry(tool.to_string())
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_rate_limited(&self, limit: &'static str) {
+        *self.inner.lock().unwrap().rate_limited.entry(limit).or_default() += 1;
+    }
+
+    // text/plain; version=0.0.4
+    pub fn render(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# HELP mcp_requests_total JSON-RPC requests by method and outcome.\n");
+        out.push_str("# TYPE mcp_requests_total counter\n");
+        for ((method, outcome), count) in &inner.requests {
+            let _ = writeln!(
+                out,
+                "mcp_requests_total{{method=\"{}\",outcome=\"{}\"}} {}",
+                escape(method),
+                outcome,
+                count
+            );
+        }
+        out.push_str("# HELP mcp_request_duration_seconds JSON-RPC request duration.\n");
+        out.push_str("# TYPE mcp_request_duration_seconds histogram\n");
+        for (method, histogram) in &inner.request_duration {
+            histogram.render(&mut out, "mcp_request_duration_seconds", "method", &escape(method));
+        }
+
+        out.push_str("# HELP mcp_tool_calls_total tools/call invocations by tool and outcome.\n");
+        out.push_str("# TYPE mcp_tool_calls_total counter\n");
+        for ((tool, outcome), count) in &inner.tool_calls {
+            let _ = writeln!(
+                out,
+                "mcp_tool_calls_total{{tool=\"{}\",outcome=\"{}\"}} {}",
+                escape(tool),
+                outcome,
+                count
+            );
+        }
+        out.push_str("# HELP mcp_tool_call_duration_seconds tools/call duration.\n");
+        out.push_str("# TYPE mcp_tool_call_duration_seconds histogram\n");
+        for (tool, histogram) in &inner.tool_duration {
+            histogram.render(&mut out, "mcp_tool_call_duration_seconds", "tool", &escape(tool));
+        }
+
+        out.push_str("# HELP mcp_rate_limited_total Requests rejected by rate limits.\n");
+        out.push_str("# TYPE mcp_rate_limited_total counter\n");
+        for (limit, count) in &inner.rate_limited {
+            let _ = writeln!(out, "mcp_rate_limited_total{{limit=\"{}\"}} {}", limit, count);
+        }
+        out
+    }
+}
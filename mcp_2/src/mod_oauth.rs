@@ -0,0 +1,180 @@
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::mod_auth::{self, Caller};
+
+// OAuth 2.1 protected resource
+//
+// 認可サーバーが発行した JWT アクセストークンを、ローカルの JWKS ファイルの
+// 公開鍵で検証する。クライアントは /.well-known/oauth-protected-resource
+// (RFC 9728) で認可サーバーを発見する。
+pub const METADATA_PATH: &str = "/.well-known/oauth-protected-resource";
+
+const SCOPES_SUPPORTED: &[&str] = &[
+    "tools:read",
+    "tools:*",
+    mod_auth::RESOURCES_READ,
+    mod_auth::PROMPTS_READ,
+    mod_auth::ADMIN,
+    mod_auth::METRICS,
+];
+
+// 設定 ([auth.oauth])
+//
+// jwks_file = 公開鍵 (JWKS, JSON)
+// resource = このサーバーのリソース URL (default: http://localhost:3000/mcp)
+// authorization_servers = 認可サーバー
+// issuers = 許可する iss (default: authorization_servers)
+// audience = 要求する aud (default: resource)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OAuthSettings {
+    pub jwks_file: String,
+    pub resource: Option<String>,
+    pub authorization_servers: Vec<String>,
+    pub issuers: Vec<String>,
+    pub audience: Option<String>,
+}
+
+pub struct OAuthConfig {
+    resource: String,
+    authorization_servers: Vec<String>,
+    issuers: Vec<String>,
+    audience: String,
+    jwks: JwkSet,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: Option<String>,
+    client_id: Option<String>,
+    #[serde(default)]
+    scope: String,
+}
+
+impl OAuthConfig {
+    // 設定から JWKS ファイルを読み込む
+    pub fn from_settings(settings: &OAuthSettings) -> anyhow::Result<Self> {
+        let jwks_file = &settings.jwks_file;
+        let text = std::fs::read_to_string(jwks_file)
+            .map_err(|e| anyhow::anyhow!("cannot read JWKS file {}: {}", jwks_file, e))?;
+        let jwks: JwkSet = serde_json::from_str(&text)
+            .map_err(|e| anyhow::anyhow!("invalid JWKS file {}: {}", jwks_file, e))?;
+        if jwks.keys.is_empty() {
+            anyhow::bail!("JWKS file {} has no keys", jwks_file);
+        }
+        if let Some(jwk) = jwks.keys.iter().find(|jwk| key_algorithm(jwk).is_none()) {
+            anyhow::bail!(
+                "JWKS file {}: key {} is not RS256 / ES256",
+                jwks_file,
+                jwk.common.key_id.as_deref().unwrap_or("(no kid)")
+            );
+        }
+
+        let resource = settings
+            .resource
+            .clone()
+            .unwrap_or_else(|| "http://localhost:3000/mcp".to_string());
+        let issuers = if settings.issuers.is_empty() {
+            settings.authorization_servers.clone()
+        } else {
+            settings.issuers.clone()
+        };
+        let audience = settings.audience.clone().unwrap_or_else(|| resource.clone());
+
+        Ok(OAuthConfig {
+            resource,
+            authorization_servers: settings.authorization_servers.clone(),
+            issuers,
+            audience,
+            jwks,
+        })
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.jwks.keys.len()
+    }
+
+    // Protected Resource Metadata (RFC 9728)
+    pub fn metadata(&self) -> Value {
+        json!({
+            "resource": self.resource,
+            "authorization_servers": self.authorization_servers,
+            "scopes_supported": SCOPES_SUPPORTED,
+            "bearer_methods_supported": ["header"]
+        })
+    }
+
+    // メタデータの URL (ホストとパスの間に well-known を挟む)
+    pub fn metadata_url(&self) -> String {
+        let (origin, path) = match self.resource.split_once("://") {
+            Some((scheme, rest)) => match rest.find('/') {
+                Some(i) => (format!("{}://{}", scheme, &rest[..i]), &rest[i..]),
+                None => (self.resource.clone(), ""),
+            },
+            None => (String::new(), self.resource.as_str()),
+        };
+        format!("{}{}{}", origin, METADATA_PATH, path.trim_end_matches('/'))
+    }
+
+    // WWW-Authenticate ヘッダー (トークンが不正な場合は error="invalid_token")
+    pub fn challenge(&self, invalid_token: bool) -> String {
+        let mut challenge = format!(
+            "Bearer resource_metadata=\"{}\", scope=\"{}\"",
+            self.metadata_url(),
+            SCOPES_SUPPORTED.join(" ")
+        );
+        if invalid_token {
+            challenge.push_str(", error=\"invalid_token\"");
+        }
+        challenge
+    }
+
+    // アクセストークンの検証 (署名・アルゴリズム・有効期限・iss・aud)
+    pub fn authenticate(&self, token: &str) -> Result<Caller, String> {
+        let header = decode_header(token).map_err(|e| e.to_string())?;
+        let jwk = match &header.kid {
+            Some(kid) => self.jwks.find(kid),
+            None if self.jwks.keys.len() == 1 => self.jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| "no matching key in JWKS".to_string())?;
+        // アルゴリズムはトークンのヘッダーではなく鍵から決める
+        let algorithm = key_algorithm(jwk).ok_or_else(|| "unsupported key algorithm".to_string())?;
+        if header.alg != algorithm {
+            return Err(format!("token alg {:?} does not match key alg {:?}", header.alg, algorithm));
+        }
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| e.to_string())?;
+
+        let mut validation = Validation::new(algorithm);
+        validation.set_audience(&[&self.audience]);
+        if !self.issuers.is_empty() {
+            validation.set_issuer(&self.issuers);
+        }
+        let claims = decode::<Claims>(token, &key, &validation)
+            .map_err(|e| e.to_string())?
+            .claims;
+
+        let subject = claims.sub.or(claims.client_id).unwrap_or_else(|| "unknown".to_string());
+        Ok(Caller {
+            name: format!("{}{}", mod_auth::OAUTH_PREFIX, subject),
+            scopes: claims.scope.split_whitespace().map(str::to_string).collect(),
+            rate_limit: None,
+        })
+    }
+}
+
+// 鍵の署名アルゴリズム (RS256 / ES256 のみ、alg が無ければ鍵の種類から決める)
+fn key_algorithm(jwk: &Jwk) -> Option<Algorithm> {
+    match (&jwk.common.key_algorithm, &jwk.algorithm) {
+        (Some(KeyAlgorithm::RS256) | None, AlgorithmParameters::RSA(_)) => Some(Algorithm::RS256),
+        (Some(KeyAlgorithm::ES256) | None, AlgorithmParameters::EllipticCurve(params))
+            if params.curve == EllipticCurve::P256 =>
+        {
+            Some(Algorithm::ES256)
+        }
+        _ => None,
+    }
+}
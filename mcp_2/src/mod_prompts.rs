@@ -0,0 +1,302 @@
+use async_trait::async_trait;
+use chrono::{Datelike, Local, NaiveDate};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+use crate::mod_purchase::{format_amount, PurchaseFilter, SummaryGroup};
+use crate::mod_rpc::JsonRpcError;
+use crate::mod_todo::TodoFilter;
+use crate::AppState;
+
+// MCP プロンプト定義
+//
+// prompts/get ではストアの最新データを埋め込んだメッセージを生成する。
+#[async_trait]
+pub trait Prompt: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn arguments(&self) -> Vec<PromptArgument>;
+    async fn messages(&self, state: &AppState, arguments: &Map<String, Value>) -> Result<Vec<Value>, JsonRpcError>;
+
+    // 引数の補完候補 (completion/complete、value は入力途中の値、最大 limit 件)
+    async fn complete(
+        &self,
+        _state: &AppState,
+        _argument: &str,
+        _value: &str,
+        _limit: usize,
+    ) -> Result<Vec<String>, JsonRpcError> {
+        Ok(Vec::new())
+    }
+
+    // prompts/list 用の定義
+    fn definition(&self) -> Value {
+        json!({
+            "name": self.name(),
+            "description": self.description(),
+            "arguments": self.arguments()
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PromptArgument {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub required: bool,
+}
+
+// プロンプトレジストリ (登録順を保持)
+#[derive(Clone, Default)]
+pub struct PromptRegistry {
+    prompts: Vec<Arc<dyn Prompt>>,
+}
+
+impl PromptRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<P: Prompt + 'static>(&mut self, prompt: P) {
+        let name = prompt.name();
+        self.prompts.retain(|p| p.name() != name);
+        self.prompts.push(Arc::new(prompt));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Prompt>> {
+        self.prompts.iter().find(|p| p.name() == name).cloned()
+    }
+
+    pub fn definitions(&self) -> Vec<Value> {
+        self.prompts.iter().map(|p| p.definition()).collect()
+    }
+}
+
+// 標準プロンプト一式
+pub fn default_registry() -> PromptRegistry {
+    let mut registry = PromptRegistry::new();
+    registry.register(SummarizePurchasesPrompt);
+    registry.register(PlanTodosPrompt);
+    registry
+}
+
+fn user_message(text: String) -> Value {
+    json!({
+        "role": "user",
+        "content": {
+            "type": "text",
+            "text": text
+        }
+    })
+}
+
+// prompts/list メソッド
+pub fn handle_prompts_list(state: &AppState) -> Result<Value, JsonRpcError> {
+    Ok(json!({
+        "prompts": state.prompts.definitions()
+    }))
+}
+
+// prompts/get メソッド
+pub async fn handle_prompts_get(state: &AppState, params: Option<Value>) -> Result<Value, JsonRpcError> {
+    let params = params.ok_or_else(|| JsonRpcError::invalid_params("Invalid params"))?;
+    let name = params["name"]
+        .as_str()
+        .ok_or_else(|| JsonRpcError::invalid_params("Prompt name is required"))?;
+    let prompt = state
+        .prompts
+        .get(name)
+        .ok_or_else(|| JsonRpcError::invalid_params(format!("Unknown prompt: {}", name)))?;
+
+    let arguments = match &params["arguments"] {
+        Value::Object(arguments) => arguments.clone(),
+        Value::Null => Map::new(),
+        _ => return Err(JsonRpcError::invalid_params("arguments must be an object")),
+    };
+    let missing: Vec<&str> = prompt
+        .arguments()
+        .iter()
+        .filter(|a| a.required && !arguments.contains_key(a.name))
+        .map(|a| a.name)
+        .collect();
+    if !missing.is_empty() {
+        return Err(JsonRpcError::invalid_params("Missing required arguments").with_data(json!({
+            "prompt": name,
+            "missing": missing
+        })));
+    }
+
+    let messages = prompt.messages(state, &arguments).await?;
+    Ok(json!({
+        "description": prompt.description(),
+        "messages": messages
+    }))
+}
+
+// "YYYY-MM" を月初・月末に変換
+fn month_range(month: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let first = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").ok()?;
+    let next = if first.month() == 12 {
+        NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)?
+    };
+    Some((first, next.pred_opt()?))
+}
+
+pub struct SummarizePurchasesPrompt;
+
+#[async_trait]
+impl Prompt for SummarizePurchasesPrompt {
+    fn name(&self) -> &'static str {
+        "summarize_purchases"
+    }
+
+    fn description(&self) -> &'static str {
+        "指定した月の購入データを要約します。"
+    }
+
+    fn arguments(&self) -> Vec<PromptArgument> {
+        vec![PromptArgument {
+            name: "month",
+            description: "対象月 YYYY-MM (default: 今月)",
+            required: false,
+        }]
+    }
+
+    async fn messages(&self, state: &AppState, arguments: &Map<String, Value>) -> Result<Vec<Value>, JsonRpcError> {
+        let month = match arguments.get("month").and_then(Value::as_str) {
+            Some(month) => month.to_string(),
+            None => Local::now().format("%Y-%m").to_string(),
+        };
+        let (from, to) = month_range(&month)
+            .ok_or_else(|| JsonRpcError::invalid_params("month must be YYYY-MM"))?;
+        let filter = PurchaseFilter {
+            from: Some(from),
+            to: Some(to),
+            ..Default::default()
+        };
+
+        let (items, total) = state
+            .purchases
+            .list(&filter, 100, 0)
+            .await
+            .map_err(JsonRpcError::internal)?;
+        let by_item = state
+            .purchases
+            .summary(SummaryGroup::Item, &filter)
+            .await
+            .map_err(JsonRpcError::internal)?;
+
+        let mut text = format!(
+            "{} の購入データ ({}件) を要約してください。合計金額、よく買っている品目、気になる傾向や節約のヒントを簡潔にまとめてください。\n",
+            month, total
+        );
+        text.push_str("\n## 購入一覧\n");
+        if items.is_empty() {
+            text.push_str("(購入データはありません)\n");
+        }
+        for item in &items {
+            text.push_str(&format!("- {}\n", item.line()));
+        }
+        if total > items.len() as i64 {
+            text.push_str(&format!("- ...他 {}件\n", total - items.len() as i64));
+        }
+        text.push_str("\n## 品名別集計\n");
+        for row in &by_item {
+            text.push_str(&format!(
+                "- {}: {} ({}件, 数量 {})\n",
+                row.key,
+                format_amount(row.total, &row.currency),
+                row.count,
+                row.quantity
+            ));
+        }
+
+        Ok(vec![user_message(text)])
+    }
+
+    async fn complete(&self, state: &AppState, argument: &str, value: &str, limit: usize) -> Result<Vec<String>, JsonRpcError> {
+        if argument != "month" {
+            return Ok(Vec::new());
+        }
+        state
+            .purchases
+            .months(value, limit as i64)
+            .await
+            .map_err(JsonRpcError::internal)
+    }
+}
+
+pub struct PlanTodosPrompt;
+
+#[async_trait]
+impl Prompt for PlanTodosPrompt {
+    fn name(&self) -> &'static str {
+        "plan_todos"
+    }
+
+    fn description(&self) -> &'static str {
+        "未完了の todo から今日の計画を立てます。"
+    }
+
+    fn arguments(&self) -> Vec<PromptArgument> {
+        vec![
+            PromptArgument {
+                name: "date",
+                description: "対象日 YYYY-MM-DD (default: 今日)",
+                required: false,
+            },
+            PromptArgument {
+                name: "hours",
+                description: "使える時間 (例: 4)",
+                required: false,
+            },
+        ]
+    }
+
+    async fn messages(&self, state: &AppState, arguments: &Map<String, Value>) -> Result<Vec<Value>, JsonRpcError> {
+        let date = match arguments.get("date").and_then(Value::as_str) {
+            Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| JsonRpcError::invalid_params("date must be YYYY-MM-DD"))?,
+            None => Local::now().date_naive(),
+        };
+        let todos = state
+            .todos
+            .list(TodoFilter::Open, 100)
+            .await
+            .map_err(JsonRpcError::internal)?;
+
+        let mut text = format!("{} の作業計画を立ててください。", date);
+        if let Some(hours) = arguments.get("hours").and_then(Value::as_str) {
+            text.push_str(&format!("使える時間は {} 時間です。", hours));
+        }
+        text.push_str("優先度の高い順に並べ、各 todo の所要時間の目安と進め方を短く添えてください。\n\n## 未完了の todo\n");
+        if todos.is_empty() {
+            text.push_str("(未完了の todo はありません)\n");
+        }
+        for todo in &todos {
+            text.push_str(&format!("- #{} {} (登録: {})\n", todo.id, todo.title, todo.created_at));
+        }
+
+        Ok(vec![user_message(text)])
+    }
+
+    async fn complete(&self, _state: &AppState, argument: &str, value: &str, limit: usize) -> Result<Vec<String>, JsonRpcError> {
+        let candidates: Vec<String> = match argument {
+            // 今日から1週間
+            "date" => {
+                let today = Local::now().date_naive();
+                today.iter_days().take(7).map(|d| d.to_string()).collect()
+            }
+            "hours" => ["1", "2", "4", "8"].iter().map(|h| h.to_string()).collect(),
+            _ => Vec::new(),
+        };
+        Ok(candidates
+            .into_iter()
+            .filter(|c| c.starts_with(value))
+            .take(limit)
+            .collect())
+    }
+}
@@ -1,120 +1,935 @@
-use axum::{
-    extract::State,
-    http::StatusCode,
-    http::HeaderMap,
-    response::IntoResponse,
-    routing::post,
-    Json, Router,
-};
-use dotenvy::dotenv;
-use std::env;
-use libsql::Database;
-use libsql::Builder;
-use libsql::Connection;
-use libsql::params;
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::sync::Arc;
-use tower_http::trace::TraceLayer;
-use tracing::{info, warn};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-
-#[derive(Debug, Deserialize)]
-pub struct JsonRpcRequest {
-    jsonrpc: String,
-    id: Option<Value>,
-    method: String,
-    params: Option<Value>,
-}
-
-#[derive(Debug, Deserialize)]
-struct AddTenParams {
-    value: i32,
-}
-
-#[derive(Debug, Deserialize,Serialize)]
-pub struct PurchaseParams {
-    name: String,
-    price: i32,
-}
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Item {
-    id: i64,
-    data: String,
-    created_at: String,
-    updated_at: String,
-}
-
-
-pub fn purchase(product_name: String, price: i32) -> String {
-    format!("「{}」を{}円で購入しました。", product_name, price)
-}
-//Result<String, String>
-pub async fn purchase_handler(
-    name: String, price: i32
-) -> String
-{
-    let url = env::var("TURSO_DATABASE_URL").expect("TURSO_DATABASE_URL must be set");
-    let token = env::var("TURSO_AUTH_TOKEN").expect("TURSO_AUTH_TOKEN must be set");
-    tracing::info!("TURSO_DATABASE_URL={}", url);
-    let db = Builder::new_remote(url, token).build().await.unwrap();
-    let conn = db.connect().unwrap();    
-
-    let post_data = PurchaseParams {
-        name: name.clone(),
-        price: price
-    };    
-    let json_string_variable = serde_json::to_string(&post_data).expect("JSON convert error");
-    println!("変換されたJSON文字列: {}", json_string_variable); 
-    let sql = format!("INSERT INTO item_price (data) VALUES ('{}')", &json_string_variable);
-    let mut result = conn
-        .execute(&sql, ())
-        .await
-        .unwrap();
-
-    let result_str = purchase(name, price);
-
-    result_str.to_string()
-}
-
-/**
-*
-* @param
-*
-* @return
-*/
-pub async fn purchase_list_handler() -> String 
-{
-    let url = env::var("TURSO_DATABASE_URL").expect("TURSO_DATABASE_URL must be set");
-    let token = env::var("TURSO_AUTH_TOKEN").expect("TURSO_AUTH_TOKEN must be set");
-    tracing::info!("TURSO_DATABASE_URL={}", url);
-    let db = Builder::new_remote(url, token).build().await.unwrap();
-    let conn = db.connect().unwrap();  
-
-    let order_sql = "ORDER BY created_at DESC LIMIT 5;";
-    let sql = format!("SELECT id, data ,created_at, updated_at 
-    FROM item_price
-    {}
-    "
-    , order_sql
-    );
-    println!("sql={}", sql);
-    let mut rows = conn.query(&sql,
-        (),  // 引数なし
-    ).await.unwrap();
-    let mut todos: Vec<Item> = Vec::new();
-    while let Some(row) = rows.next().await.unwrap() {
-        let id: i64 = row.get(0).unwrap();
-        let data: String = row.get(1).unwrap();
-        todos.push(Item {
-            id: id,
-            data: data,
-            created_at: row.get(2).unwrap(),
-            updated_at: row.get(3).unwrap(),        
-        });        
-    }
-    let json_string_variable = serde_json::to_string(&todos).expect("JSON convert error");
-    //println!("変換されたJSON文字列: {}", json_string_variable);
-    return json_string_variable.to_string();    
-}
-
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use libsql::{params, params_from_iter, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::mod_client::{Client, Elicitation};
+use crate::mod_db::{Backend, Db};
+use crate::mod_prompts::{Prompt, SummarizePurchasesPrompt};
+use crate::mod_resources;
+use crate::mod_tools::{arg_i64, arg_i64_or, arg_str, structured_result, Progress, Tool};
+use crate::mod_rpc::JsonRpcError;
+use crate::AppState;
+
+const MIGRATIONS: &[(&str, &str)] = &[
+    (
+        "item_price_001_create",
+        "CREATE TABLE IF NOT EXISTS item_price (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            data TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );",
+    ),
+    // data (JSON 文字列) をカラムへ展開
+    (
+        "item_price_002_columns",
+        "ALTER TABLE item_price ADD COLUMN name TEXT;
+        ALTER TABLE item_price ADD COLUMN price INTEGER;
+        ALTER TABLE item_price ADD COLUMN quantity INTEGER NOT NULL DEFAULT 1;
+        ALTER TABLE item_price ADD COLUMN currency TEXT NOT NULL DEFAULT 'JPY';
+        ALTER TABLE item_price ADD COLUMN purchased_at TEXT;
+        UPDATE item_price
+        SET name = json_extract(data, '$.name'),
+            price = json_extract(data, '$.price')
+        WHERE name IS NULL AND json_valid(data);
+        UPDATE item_price SET purchased_at = created_at WHERE purchased_at IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_item_price_purchased_at ON item_price (purchased_at);
+        CREATE INDEX IF NOT EXISTS idx_item_price_name ON item_price (name);",
+    ),
+];
+
+const DEFAULT_CURRENCY: &str = "JPY";
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// ユーザーが purchase を承認しなかった (elicitation で decline / cancel)
+pub const PURCHASE_NOT_CONFIRMED: i32 = -32007;
+
+// item_price.data に保存する旧形式 (既存の読み手との互換用)
+#[derive(Debug, Deserialize,Serialize)]
+pub struct PurchaseParams {
+    name: String,
+    price: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Purchase {
+    pub id: i64,
+    pub name: String,
+    pub price: i64,
+    pub quantity: i64,
+    pub currency: String,
+    pub purchased_at: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Purchase {
+    fn from_row(row: &Row) -> Result<Self, libsql::Error> {
+        Ok(Purchase {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            price: row.get(2)?,
+            quantity: row.get(3)?,
+            currency: row.get(4)?,
+            purchased_at: row.get(5)?,
+            created_at: row.get(6)?,
+            updated_at: row.get(7)?,
+        })
+    }
+
+    pub fn total(&self) -> i64 {
+        self.price * self.quantity
+    }
+
+    pub fn line(&self) -> String {
+        format!(
+            "#{} {} {} {} x {} = {}",
+            self.id,
+            // 日付部分 (YYYY-MM-DD)。想定外の形式はそのまま表示する
+            self.purchased_at.get(..10).unwrap_or(&self.purchased_at),
+            self.name,
+            format_amount(self.price, &self.currency),
+            self.quantity,
+            format_amount(self.total(), &self.currency)
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewPurchase {
+    pub name: String,
+    pub price: i64,
+    pub quantity: i64,
+    pub currency: String,
+    pub purchased_at: String,
+}
+
+// 一覧・集計の絞り込み条件
+#[derive(Debug, Clone, Default)]
+pub struct PurchaseFilter {
+    // 品名の部分一致
+    pub name: Option<String>,
+    // 品名の完全一致 (purchase://item/{name})
+    pub item: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl PurchaseFilter {
+    fn where_clause(&self) -> (String, Vec<libsql::Value>) {
+        let mut conditions = vec!["1 = 1".to_string()];
+        let mut values = Vec::new();
+        if let Some(name) = &self.name {
+            let escaped = name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            values.push(libsql::Value::Text(format!("%{}%", escaped)));
+            conditions.push(format!("name LIKE ?{} ESCAPE '\\'", values.len()));
+        }
+        if let Some(item) = &self.item {
+            values.push(libsql::Value::Text(item.clone()));
+            conditions.push(format!("name = ?{}", values.len()));
+        }
+        if let Some(from) = self.from {
+            values.push(libsql::Value::Text(from.to_string()));
+            conditions.push(format!("purchased_at >= ?{}", values.len()));
+        }
+        if let Some(to) = self.to {
+            values.push(libsql::Value::Text(to.to_string()));
+            conditions.push(format!("purchased_at < date(?{}, '+1 day')", values.len()));
+        }
+        (conditions.join(" AND "), values)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SummaryGroup {
+    Day,
+    Month,
+    Item,
+}
+
+impl SummaryGroup {
+    fn parse(value: Option<&str>) -> Self {
+        match value {
+            Some("day") => SummaryGroup::Day,
+            Some("item") => SummaryGroup::Item,
+            _ => SummaryGroup::Month,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            SummaryGroup::Day => "day",
+            SummaryGroup::Month => "month",
+            SummaryGroup::Item => "item",
+        }
+    }
+
+    fn key_expr(self) -> &'static str {
+        match self {
+            SummaryGroup::Day => "substr(purchased_at, 1, 10)",
+            SummaryGroup::Month => "substr(purchased_at, 1, 7)",
+            SummaryGroup::Item => "name",
+        }
+    }
+
+    fn order_by(self) -> &'static str {
+        match self {
+            SummaryGroup::Item => "total DESC, key ASC",
+            _ => "key ASC",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryRow {
+    pub key: String,
+    pub currency: String,
+    pub count: i64,
+    pub quantity: i64,
+    pub total: i64,
+}
+
+pub fn format_amount(amount: i64, currency: &str) -> String {
+    if currency == DEFAULT_CURRENCY {
+        format!("{}円", amount)
+    } else {
+        format!("{} {}", amount, currency)
+    }
+}
+
+pub fn purchase(product_name: &str, price: i64, quantity: i64, currency: &str) -> String {
+    if quantity == 1 {
+        format!("「{}」を{}で購入しました。", product_name, format_amount(price, currency))
+    } else {
+        format!(
+            "「{}」を{}個、合計{}で購入しました。",
+            product_name,
+            quantity,
+            format_amount(price * quantity, currency)
+        )
+    }
+}
+
+// 購入日時の正規化 (YYYY-MM-DD / YYYY-MM-DD HH:MM:SS / RFC3339 を受け付ける)
+pub fn parse_purchased_at(value: &str) -> Option<String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc).format(DATETIME_FORMAT).to_string());
+    }
+    for format in [DATETIME_FORMAT, "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(dt.format(DATETIME_FORMAT).to_string());
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(|d| format!("{} 00:00:00", d))
+}
+
+const SELECT_COLUMNS: &str = "SELECT id, COALESCE(name, ''), COALESCE(price, 0), quantity, currency,
+    purchased_at, created_at, updated_at
+    FROM item_price";
+
+// 購入データのリポジトリ (接続は起動時に1度だけ作成して共有する)
+#[derive(Clone)]
+pub struct PurchaseRepository {
+    db: Db,
+}
+
+impl PurchaseRepository {
+    pub async fn open(backend: &Backend) -> anyhow::Result<Self> {
+        let db = Db::open(backend).await?;
+        db.migrate(MIGRATIONS).await?;
+        Ok(PurchaseRepository { db })
+    }
+
+    async fn collect(&self, sql: &str, values: Vec<libsql::Value>) -> anyhow::Result<Vec<Purchase>> {
+        let mut rows = self.db.conn().query(sql, params_from_iter(values)).await?;
+        let mut items = Vec::new();
+        while let Some(row) = rows.next().await? {
+            items.push(Purchase::from_row(&row)?);
+        }
+        Ok(items)
+    }
+
+    pub async fn insert(&self, purchase: &NewPurchase) -> anyhow::Result<Purchase> {
+        let data = serde_json::to_string(&PurchaseParams {
+            name: purchase.name.clone(),
+            price: purchase.price,
+        })?;
+        tracing::debug!("insert item_price: {}", data);
+        let mut rows = self
+            .db
+            .conn()
+            .query(
+                "INSERT INTO item_price (data, name, price, quantity, currency, purchased_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                RETURNING id",
+                params![
+                    data,
+                    purchase.name.clone(),
+                    purchase.price,
+                    purchase.quantity,
+                    purchase.currency.clone(),
+                    purchase.purchased_at.clone()
+                ],
+            )
+            .await?;
+        let id: i64 = match rows.next().await? {
+            Some(row) => row.get(0)?,
+            None => anyhow::bail!("insert returned no id"),
+        };
+        self.get(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("inserted purchase not found: {}", id))
+    }
+
+    pub async fn get(&self, id: i64) -> anyhow::Result<Option<Purchase>> {
+        let sql = format!("{} WHERE id = ?1", SELECT_COLUMNS);
+        Ok(self.collect(&sql, vec![libsql::Value::Integer(id)]).await?.into_iter().next())
+    }
+
+    // 新しい順に取得 (戻り値は該当件数の合計と一緒に返す)
+    pub async fn list(&self, filter: &PurchaseFilter, limit: i64, offset: i64) -> anyhow::Result<(Vec<Purchase>, i64)> {
+        let (condition, mut values) = filter.where_clause();
+
+        let count_sql = format!("SELECT COUNT(*) FROM item_price WHERE {}", condition);
+        let mut rows = self
+            .db
+            .conn()
+            .query(&count_sql, params_from_iter(values.clone()))
+            .await?;
+        let total: i64 = match rows.next().await? {
+            Some(row) => row.get(0)?,
+            None => 0,
+        };
+
+        values.push(libsql::Value::Integer(limit));
+        values.push(libsql::Value::Integer(offset));
+        let sql = format!(
+            "{} WHERE {} ORDER BY purchased_at DESC, id DESC LIMIT ?{} OFFSET ?{}",
+            SELECT_COLUMNS,
+            condition,
+            values.len() - 1,
+            values.len()
+        );
+        Ok((self.collect(&sql, values).await?, total))
+    }
+
+    pub async fn summary(&self, group: SummaryGroup, filter: &PurchaseFilter) -> anyhow::Result<Vec<SummaryRow>> {
+        let (condition, values) = filter.where_clause();
+        let sql = format!(
+            "SELECT COALESCE({}, '') AS key, currency, COUNT(*), SUM(quantity),
+                SUM(COALESCE(price, 0) * quantity) AS total
+            FROM item_price
+            WHERE {}
+            GROUP BY key, currency
+            ORDER BY {}",
+            group.key_expr(),
+            condition,
+            group.order_by()
+        );
+        let mut rows = self.db.conn().query(&sql, params_from_iter(values)).await?;
+        let mut summary = Vec::new();
+        while let Some(row) = rows.next().await? {
+            summary.push(SummaryRow {
+                key: row.get(0)?,
+                currency: row.get(1)?,
+                count: row.get(2)?,
+                quantity: row.get(3)?,
+                total: row.get(4)?,
+            });
+        }
+        Ok(summary)
+    }
+
+    // 補完候補 (completion/complete)
+    async fn strings(&self, sql: &str, prefix: &str, limit: i64) -> anyhow::Result<Vec<String>> {
+        let pattern = format!("{}%", prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+        let mut rows = self.db.conn().query(sql, params![pattern, limit]).await?;
+        let mut values = Vec::new();
+        while let Some(row) = rows.next().await? {
+            values.push(row.get(0)?);
+        }
+        Ok(values)
+    }
+
+    // 購入したことのある品名 (購入回数の多い順)
+    pub async fn names(&self, prefix: &str, limit: i64) -> anyhow::Result<Vec<String>> {
+        self.strings(
+            "SELECT name FROM item_price
+            WHERE name IS NOT NULL AND name LIKE ?1 ESCAPE '\\'
+            GROUP BY name
+            ORDER BY COUNT(*) DESC, name
+            LIMIT ?2",
+            prefix,
+            limit,
+        )
+        .await
+    }
+
+    // 購入データのある月 YYYY-MM (新しい順)
+    pub async fn months(&self, prefix: &str, limit: i64) -> anyhow::Result<Vec<String>> {
+        self.strings(
+            "SELECT DISTINCT substr(purchased_at, 1, 7) AS month FROM item_price
+            WHERE substr(purchased_at, 1, 7) LIKE ?1 ESCAPE '\\'
+            ORDER BY month DESC
+            LIMIT ?2",
+            prefix,
+            limit,
+        )
+        .await
+    }
+
+    // id (新しい順)
+    pub async fn ids(&self, prefix: &str, limit: i64) -> anyhow::Result<Vec<String>> {
+        self.strings(
+            "SELECT CAST(id AS TEXT) FROM item_price
+            WHERE CAST(id AS TEXT) LIKE ?1 ESCAPE '\\'
+            ORDER BY id DESC
+            LIMIT ?2",
+            prefix,
+            limit,
+        )
+        .await
+    }
+
+    pub async fn delete(&self, id: i64) -> anyhow::Result<Option<Purchase>> {
+        let Some(purchase) = self.get(id).await? else {
+            return Ok(None);
+        };
+        self.db
+            .conn()
+            .execute("DELETE FROM item_price WHERE id = ?1", params![id])
+            .await?;
+        Ok(Some(purchase))
+    }
+}
+
+// 引数から絞り込み条件を作成
+fn filter_from_arguments(arguments: &Value) -> Result<PurchaseFilter, JsonRpcError> {
+    let date = |key: &str| -> Result<Option<NaiveDate>, JsonRpcError> {
+        match arguments[key].as_str() {
+            None => Ok(None),
+            Some(value) => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(Some)
+                .map_err(|_| JsonRpcError::invalid_params(format!("{} must be YYYY-MM-DD", key))),
+        }
+    };
+    Ok(PurchaseFilter {
+        name: arguments["name"].as_str().map(str::to_string),
+        item: None,
+        from: date("from")?,
+        to: date("to")?,
+    })
+}
+
+fn notify_purchase_updated(state: &AppState, purchase: &Purchase) {
+    mod_resources::notify_updated(
+        state,
+        &[
+            mod_resources::PURCHASE_RECENT.to_string(),
+            mod_resources::purchase_uri(purchase.id),
+            mod_resources::purchase_item_uri(&purchase.name),
+        ],
+    );
+}
+
+fn date_schema(description: &str) -> Value {
+    json!({ "type": "string", "format": "date", "description": description })
+}
+
+fn purchase_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "id": { "type": "integer" },
+            "name": { "type": "string" },
+            "price": { "type": "integer" },
+            "quantity": { "type": "integer" },
+            "currency": { "type": "string" },
+            "purchased_at": { "type": "string" },
+            "created_at": { "type": "string" },
+            "updated_at": { "type": "string" }
+        },
+        "required": ["id", "name", "price", "quantity", "currency", "purchased_at"]
+    })
+}
+
+pub struct PurchaseTool;
+
+#[async_trait]
+impl Tool for PurchaseTool {
+    fn name(&self) -> &'static str {
+        "purchase"
+    }
+
+    fn description(&self) -> &'static str {
+        "品名と価格を受け取り、購入データとして登録します。"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "minLength": 1,
+                    "description": "購入する品名"
+                },
+                "price": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "maximum": i32::MAX,
+                    "description": "単価"
+                },
+                "quantity": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": 10000,
+                    "description": "数量 (default: 1)"
+                },
+                "currency": {
+                    "type": "string",
+                    "minLength": 3,
+                    "maxLength": 3,
+                    "description": "通貨コード ISO 4217 (default: JPY)"
+                },
+                "purchased_at": {
+                    "type": "string",
+                    "description": "購入日時 YYYY-MM-DD / YYYY-MM-DD HH:MM:SS / RFC3339 (default: 現在時刻)"
+                }
+            },
+            "required": ["name", "price"]
+        })
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(purchase_schema())
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        self.call_with_client(state, arguments, &Progress::none(), &Client::none()).await
+    }
+
+    async fn call_with_client(
+        &self,
+        state: &AppState,
+        arguments: &Value,
+        _progress: &Progress,
+        client: &Client,
+    ) -> Result<Value, JsonRpcError> {
+        let purchased_at = match arguments["purchased_at"].as_str() {
+            Some(value) => parse_purchased_at(value)
+                .ok_or_else(|| JsonRpcError::invalid_params("purchased_at must be a date or datetime"))?,
+            None => Utc::now().format(DATETIME_FORMAT).to_string(),
+        };
+        let new_purchase = NewPurchase {
+            name: arg_str(arguments, "name")?.to_string(),
+            price: arg_i64(arguments, "price")?,
+            quantity: arg_i64_or(arguments, "quantity", 1)?,
+            currency: arguments["currency"]
+                .as_str()
+                .unwrap_or(DEFAULT_CURRENCY)
+                .to_uppercase(),
+            purchased_at,
+        };
+        if let Some(limit) = state.purchase_confirm_above
+            && new_purchase.price * new_purchase.quantity > limit
+        {
+            confirm_purchase(&new_purchase, client).await?;
+        }
+
+        let record = state
+            .purchases
+            .insert(&new_purchase)
+            .await
+            .map_err(JsonRpcError::internal)?;
+        notify_purchase_updated(state, &record);
+        let text = purchase(&record.name, record.price, record.quantity, &record.currency);
+        Ok(structured_result(format!("Result: {} (ID: {})", text, record.id), json!(record)))
+    }
+}
+
+// 高額な purchase をユーザーに確認する (承認されなければエラー)
+//
+// 確認の待ち時間も purchase の実行時間の上限 (tools.timeouts) に含まれる。
+async fn confirm_purchase(purchase: &NewPurchase, client: &Client) -> Result<(), JsonRpcError> {
+    let total = format_amount(purchase.price * purchase.quantity, &purchase.currency);
+    let message = format!(
+        "{} を {}個 購入します (合計 {})。登録してよろしいですか?",
+        purchase.name, purchase.quantity, total
+    );
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "confirm": {
+                "type": "boolean",
+                "title": "購入する",
+                "description": format!("合計 {}", total)
+            }
+        },
+        "required": ["confirm"]
+    });
+    let answer = client.elicit(&message, schema).await?;
+    let confirmed = matches!(&answer, Elicitation::Accept(content) if content["confirm"] == true);
+    tracing::info!(
+        "purchase confirmation: name={}, total={}, action={}, confirmed={}",
+        purchase.name,
+        total,
+        answer.action(),
+        confirmed
+    );
+    if confirmed {
+        return Ok(());
+    }
+    Err(JsonRpcError {
+        code: PURCHASE_NOT_CONFIRMED,
+        message: "Purchase was not confirmed by the user".to_string(),
+        data: Some(json!({ "action": answer.action(), "total": total })),
+    })
+}
+
+pub struct PurchaseListTool;
+
+#[async_trait]
+impl Tool for PurchaseListTool {
+    fn name(&self) -> &'static str {
+        "purchase_list"
+    }
+
+    fn description(&self) -> &'static str {
+        "購入品リストを、新しい順に表示します。期間・品名で絞り込めます。"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "limit": { "type": "integer", "minimum": 1, "maximum": 100, "description": "件数 (default: 20)" },
+                "offset": { "type": "integer", "minimum": 0, "description": "開始位置 (default: 0)" },
+                "name": { "type": "string", "minLength": 1, "description": "品名 (部分一致)" },
+                "from": date_schema("開始日 YYYY-MM-DD"),
+                "to": date_schema("終了日 YYYY-MM-DD (当日を含む)")
+            },
+            "required": []
+        })
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(json!({
+            "type": "object",
+            "properties": {
+                "items": { "type": "array", "items": purchase_schema() },
+                "total": { "type": "integer" },
+                "limit": { "type": "integer" },
+                "offset": { "type": "integer" }
+            },
+            "required": ["items", "total", "limit", "offset"]
+        }))
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let filter = filter_from_arguments(arguments)?;
+        let limit = arg_i64_or(arguments, "limit", 20)?;
+        let offset = arg_i64_or(arguments, "offset", 0)?;
+        let (items, total) = state
+            .purchases
+            .list(&filter, limit, offset)
+            .await
+            .map_err(JsonRpcError::internal)?;
+
+        let mut lines: Vec<String> = items.iter().map(Purchase::line).collect();
+        if lines.is_empty() {
+            lines.push(format!("購入データはありません。({}件)", total));
+        } else {
+            lines.push(format!("({} - {} / {}件)", offset + 1, offset + items.len() as i64, total));
+        }
+
+        Ok(structured_result(
+            lines.join("\n"),
+            json!({ "items": items, "total": total, "limit": limit, "offset": offset }),
+        ))
+    }
+}
+
+pub struct PurchaseSummaryTool;
+
+#[async_trait]
+impl Tool for PurchaseSummaryTool {
+    fn name(&self) -> &'static str {
+        "purchase_summary"
+    }
+
+    fn description(&self) -> &'static str {
+        "購入金額を日別・月別・品名別に集計します。"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "group_by": { "type": "string", "enum": ["day", "month", "item"], "description": "集計単位 (default: month)" },
+                "name": { "type": "string", "minLength": 1, "description": "品名 (部分一致)" },
+                "from": date_schema("開始日 YYYY-MM-DD"),
+                "to": date_schema("終了日 YYYY-MM-DD (当日を含む)")
+            },
+            "required": []
+        })
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(json!({
+            "type": "object",
+            "properties": {
+                "group_by": { "type": "string" },
+                "rows": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "key": { "type": "string" },
+                            "currency": { "type": "string" },
+                            "count": { "type": "integer" },
+                            "quantity": { "type": "integer" },
+                            "total": { "type": "integer" }
+                        },
+                        "required": ["key", "currency", "count", "quantity", "total"]
+                    }
+                }
+            },
+            "required": ["group_by", "rows"]
+        }))
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let group = SummaryGroup::parse(arguments["group_by"].as_str());
+        let filter = filter_from_arguments(arguments)?;
+        let rows = state
+            .purchases
+            .summary(group, &filter)
+            .await
+            .map_err(JsonRpcError::internal)?;
+
+        let mut lines: Vec<String> = rows
+            .iter()
+            .map(|r| format!("{}: {} ({}件, 数量 {})", r.key, format_amount(r.total, &r.currency), r.count, r.quantity))
+            .collect();
+        if lines.is_empty() {
+            lines.push("購入データはありません。".to_string());
+        }
+
+        Ok(structured_result(
+            lines.join("\n"),
+            json!({ "group_by": group.as_str(), "rows": rows }),
+        ))
+    }
+}
+
+pub struct PurchaseInsightsTool;
+
+#[async_trait]
+impl Tool for PurchaseInsightsTool {
+    fn name(&self) -> &'static str {
+        "purchase_insights"
+    }
+
+    fn description(&self) -> &'static str {
+        "月の購入データの傾向を、クライアントの LLM (sampling) で要約します。"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                // mod_schema は pattern に対応していないため、形式はプロンプト側で検証する
+                "month": {
+                    "type": "string",
+                    "description": "対象月 YYYY-MM (default: 今月)"
+                },
+                "max_tokens": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": 4096,
+                    "description": "生成する最大トークン数 (default: 800)"
+                }
+            },
+            "required": []
+        })
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        self.call_with_client(state, arguments, &Progress::none(), &Client::none()).await
+    }
+
+    async fn call_with_client(
+        &self,
+        state: &AppState,
+        arguments: &Value,
+        _progress: &Progress,
+        client: &Client,
+    ) -> Result<Value, JsonRpcError> {
+        // プロンプト summarize_purchases と同じメッセージを使う
+        let mut prompt_arguments = serde_json::Map::new();
+        if let Some(month) = arguments["month"].as_str() {
+            prompt_arguments.insert("month".to_string(), json!(month));
+        }
+        let messages = SummarizePurchasesPrompt.messages(state, &prompt_arguments).await?;
+        let result = client
+            .create_message(json!({
+                "messages": messages,
+                "systemPrompt": "あなたは家計のアドバイザーです。日本語で簡潔に答えてください。",
+                "includeContext": "none",
+                "maxTokens": arg_i64_or(arguments, "max_tokens", 800)?
+            }))
+            .await?;
+
+        let text = result["content"]["text"].as_str().unwrap_or_default();
+        let model = result["model"].as_str().unwrap_or("unknown");
+        tracing::info!("purchase insights generated: model={}, chars={}", model, text.chars().count());
+        Ok(structured_result(text, json!({ "model": model, "text": text })))
+    }
+}
+
+pub struct PurchaseDeleteTool;
+
+#[async_trait]
+impl Tool for PurchaseDeleteTool {
+    fn name(&self) -> &'static str {
+        "purchase_delete"
+    }
+
+    fn description(&self) -> &'static str {
+        "購入データを ID で削除します。"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "minimum": 1, "description": "購入データ ID" }
+            },
+            "required": ["id"]
+        })
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(purchase_schema())
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let id = arg_i64(arguments, "id")?;
+        let record = state
+            .purchases
+            .delete(id)
+            .await
+            .map_err(JsonRpcError::internal)?
+            .ok_or_else(|| JsonRpcError::invalid_params(format!("Purchase not found: {}", id)))?;
+        notify_purchase_updated(state, &record);
+        Ok(structured_result(format!("削除しました: {}", record.line()), json!(record)))
+    }
+}
+
+// CSV のフィールド (カンマ・引用符・改行を含む場合は引用符で囲む)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+const EXPORT_PAGE_SIZE: i64 = 100;
+
+pub struct PurchaseExportTool;
+
+#[async_trait]
+impl Tool for PurchaseExportTool {
+    fn name(&self) -> &'static str {
+        "purchase_export"
+    }
+
+    fn description(&self) -> &'static str {
+        "購入データを CSV で出力します。件数が多い場合は進捗を通知します。"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 1, "description": "品名 (部分一致)" },
+                "from": date_schema("開始日 YYYY-MM-DD"),
+                "to": date_schema("終了日 YYYY-MM-DD (当日を含む)")
+            },
+            "required": []
+        })
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        Some(json!({
+            "type": "object",
+            "properties": {
+                "count": { "type": "integer" }
+            },
+            "required": ["count"]
+        }))
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        self.call_with_progress(state, arguments, &Progress::none()).await
+    }
+
+    async fn call_with_progress(
+        &self,
+        state: &AppState,
+        arguments: &Value,
+        progress: &Progress,
+    ) -> Result<Value, JsonRpcError> {
+        let filter = filter_from_arguments(arguments)?;
+        let mut lines = vec!["id,name,price,quantity,currency,purchased_at".to_string()];
+        let mut offset = 0;
+        loop {
+            let (items, total) = state
+                .purchases
+                .list(&filter, EXPORT_PAGE_SIZE, offset)
+                .await
+                .map_err(JsonRpcError::internal)?;
+            for item in &items {
+                lines.push(format!(
+                    "{},{},{},{},{},{}",
+                    item.id,
+                    csv_field(&item.name),
+                    item.price,
+                    item.quantity,
+                    csv_field(&item.currency),
+                    item.purchased_at
+                ));
+            }
+            offset += items.len() as i64;
+            progress.report(offset as f64, Some(total as f64), Some(&format!("{} / {}件", offset, total)));
+            if items.is_empty() || offset >= total {
+                break;
+            }
+        }
+
+        let count = lines.len() - 1;
+        Ok(structured_result(lines.join("\n"), json!({ "count": count })))
+    }
+}
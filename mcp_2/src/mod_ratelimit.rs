@@ -0,0 +1,446 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower::{Layer, Service};
+
+use crate::mod_auth::Caller;
+use crate::mod_metrics::Metrics;
+use crate::mod_rpc::{error_response, JsonRpcError};
+use crate::AppState;
+
+// レート制限 (tower layer)
+//
+// JSON-RPC の POST に対して次の順で判定する。
+//   1. 同時実行数 (全体)
+//   2. API キーごとのトークンバケット
+//   3. API キー × ツールごとのトークンバケット (tools/call)
+// 2 と 3 はすべて通る場合のみトークンを消費する。
+// バッチ内の同じツールの呼び出しがバケットの容量を超える場合は待っても通らないため -32600 で拒否する。
+// クライアントからの応答 (result / error のみのメッセージ) は対象外。
+// 制限に掛かった場合は JSON-RPC エラー (data.retryAfter 秒) と Retry-After ヘッダーを返す。
+const RATE_LIMITED: i32 = -32029;
+
+// axum の Bytes 抽出と同じ上限
+const BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+// "60/min" 形式のレート (バースト = 回数)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rate {
+    pub count: u32,
+    pub per: Duration,
+}
+
+impl Rate {
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let (count, unit) = spec
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("rate must be <count>/<unit>: {}", spec))?;
+        let count: u32 = count.trim().parse()?;
+        if count == 0 {
+            anyhow::bail!("rate count must be positive: {}", spec);
+        }
+        let per = match unit.trim() {
+            "s" | "sec" | "second" => Duration::from_secs(1),
+            "m" | "min" | "minute" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(3600),
+            other => anyhow::bail!("unknown rate unit: {}", other),
+        };
+        Ok(Rate { count, per })
+    }
+
+    fn per_second(&self) -> f64 {
+        self.count as f64 / self.per.as_secs_f64()
+    }
+}
+
+impl TryFrom<String> for Rate {
+    type Error = anyhow::Error;
+
+    fn try_from(spec: String) -> Result<Self, Self::Error> {
+        Rate::parse(&spec)
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Rate) -> Self {
+        Bucket {
+            tokens: rate.count as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    // 経過時間分を補充し、count トークンあるか調べる (消費はしない)
+    fn check(&mut self, rate: Rate, count: f64) -> Result<(), Duration> {
+        let now = Instant::now();
+        let refill = now.duration_since(self.updated).as_secs_f64() * rate.per_second();
+        self.tokens = (self.tokens + refill).min(rate.count as f64);
+        self.updated = now;
+        if self.tokens >= count {
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((count - self.tokens) / rate.per_second()))
+    }
+}
+
+// 設定 ([rate_limit])
+//
+// per_key = キーごとの既定レート (例: "120/min", キーファイルの rate_limit で上書き)
+// per_tool = ツールごとのレート (例: { purchase = "10/min" })
+// max_in_flight = 同時に処理するリクエスト数の上限
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub per_key: Option<Rate>,
+    pub per_tool: HashMap<String, Rate>,
+    pub max_in_flight: Option<usize>,
+}
+
+impl RateLimitConfig {
+    pub fn describe(&self) -> String {
+        let mut tools: Vec<String> = self
+            .per_tool
+            .iter()
+            .map(|(tool, rate)| format!("{}={}/{}s", tool, rate.count, rate.per.as_secs()))
+            .collect();
+        tools.sort();
+        format!(
+            "per_key={}, per_tool=[{}], max_in_flight={}",
+            self.per_key
+                .map(|r| format!("{}/{}s", r.count, r.per.as_secs()))
+                .unwrap_or_else(|| "-".to_string()),
+            tools.join(", "),
+            self.max_in_flight.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string())
+        )
+    }
+}
+
+// RATE_LIMIT_TOOLS 形式 (例: purchase=10/min,purchase_delete=5/min)
+pub fn parse_tool_rates(spec: &str) -> anyhow::Result<HashMap<String, Rate>> {
+    let mut rates = HashMap::new();
+    for entry in spec.split(',').filter(|e| !e.trim().is_empty()) {
+        let (tool, rate) = entry
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("RATE_LIMIT_TOOLS entry must be <tool>=<rate>: {}", entry))?;
+        rates.insert(tool.trim().to_string(), Rate::parse(rate)?);
+    }
+    Ok(rates)
+}
+
+// 制限に掛かった理由
+struct Limited {
+    limit: &'static str,
+    tool: Option<String>,
+    retry_after: Duration,
+}
+
+// 拒否の理由 (待てば通る制限か、バッチが大きすぎて通らないか)
+enum Rejected {
+    Limited(Limited),
+    TooManyCalls { tool: String, calls: u32, capacity: u32 },
+}
+
+impl From<Limited> for Rejected {
+    fn from(limited: Limited) -> Self {
+        Rejected::Limited(limited)
+    }
+}
+
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    in_flight: Option<Arc<Semaphore>>,
+    keys: Mutex<HashMap<String, Bucket>>,
+    tools: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            in_flight: config.max_in_flight.map(|n| Arc::new(Semaphore::new(n))),
+            config,
+            keys: Mutex::new(HashMap::new()),
+            tools: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // キー・バッチ内の全ツールを先に調べ、すべて通る場合のみトークンを消費する
+    fn check(&self, caller: &Caller, tools: &[String]) -> Result<(), Rejected> {
+        let key_rate = caller.rate_limit.or(self.config.per_key);
+        let mut counts: Vec<(&String, Rate, u32)> = Vec::new();
+        for tool in tools {
+            let Some(rate) = self.config.per_tool.get(tool).copied() else {
+                continue;
+            };
+            match counts.iter_mut().find(|(name, _, _)| *name == tool) {
+                Some((_, _, count)) => *count += 1,
+                None => counts.push((tool, rate, 1)),
+            }
+        }
+        // バケットの容量を超える呼び出しは待っても通らない
+        if let Some((tool, rate, count)) = counts.iter().find(|(_, rate, count)| *count > rate.count) {
+            return Err(Rejected::TooManyCalls {
+                tool: (*tool).clone(),
+                calls: *count,
+                capacity: rate.count,
+            });
+        }
+
+        // ロックは keys → tools の順
+        let mut keys = self.keys.lock().unwrap();
+        let mut buckets = self.tools.lock().unwrap();
+        if let Some(rate) = key_rate {
+            let bucket = keys.entry(caller.name.clone()).or_insert_with(|| Bucket::new(rate));
+            bucket.check(rate, 1.0).map_err(|retry_after| Limited {
+                limit: "key",
+                tool: None,
+                retry_after,
+            })?;
+        }
+        for (tool, rate, count) in &counts {
+            let bucket = buckets
+                .entry((caller.name.clone(), (*tool).clone()))
+                .or_insert_with(|| Bucket::new(*rate));
+            bucket.check(*rate, *count as f64).map_err(|retry_after| Limited {
+                limit: "tool",
+                tool: Some((*tool).clone()),
+                retry_after,
+            })?;
+        }
+
+        if key_rate.is_some()
+            && let Some(bucket) = keys.get_mut(&caller.name)
+        {
+            bucket.tokens -= 1.0;
+        }
+        for (tool, _, count) in &counts {
+            if let Some(bucket) = buckets.get_mut(&(caller.name.clone(), (*tool).clone())) {
+                bucket.tokens -= *count as f64;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    state: Arc<AppState>,
+}
+
+impl RateLimitLayer {
+    pub fn new(state: Arc<AppState>) -> Self {
+        RateLimitLayer { state }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            state: self.state.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    state: Arc<AppState>,
+}
+
+impl<S> Service<Request> for RateLimitService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        // poll_ready 済みのサービスを使い、次の呼び出し用にクローンを残す
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let state = self.state.clone();
+
+        Box::pin(async move {
+            let limiter = &state.limiter;
+            // JSON-RPC (POST) のみ対象 (SSE ストリームは長時間つながるため除外)
+            if request.method() != Method::POST {
+                return inner.call(request).await;
+            }
+
+            // メッセージの種類・ツール名を調べるため本文を読み、同じ内容で組み立て直す
+            let (parts, body) = request.into_parts();
+            let bytes = match to_bytes(body, BODY_LIMIT).await {
+                Ok(bytes) => bytes,
+                Err(_) => return Ok(StatusCode::PAYLOAD_TOO_LARGE.into_response()),
+            };
+            let message: Option<Value> = serde_json::from_slice(&bytes).ok();
+            let request = Request::from_parts(parts, Body::from(bytes));
+
+            // クライアントの応答 (elicitation/create などへの result / error) は制限しない
+            // (応答を待っているツール呼び出しが permit を持ったままのため)
+            if message.as_ref().is_some_and(is_response_only) {
+                return inner.call(request).await;
+            }
+
+            // 同時実行数 (応答の本文を送り終えるまで保持する。SSE の tools/call はストリーム終了まで)
+            let permit = match &limiter.in_flight {
+                Some(semaphore) => match semaphore.clone().try_acquire_owned() {
+                    Ok(permit) => Some(permit),
+                    Err(_) => {
+                        let limited = Limited {
+                            limit: "in_flight",
+                            tool: None,
+                            retry_after: Duration::from_secs(1),
+                        };
+                        return Ok(limited_response(&state.metrics, "-", None, limited));
+                    }
+                },
+                None => None,
+            };
+
+            let response = check_and_call(&state, &mut inner, request, message.as_ref()).await?;
+            Ok(match permit {
+                Some(permit) => hold_until_end(response, permit),
+                None => response,
+            })
+        })
+    }
+}
+
+// キー・ツールごとの制限を調べ、通ればハンドラーを呼ぶ
+async fn check_and_call<S>(
+    state: &AppState,
+    inner: &mut S,
+    request: Request,
+    message: Option<&Value>,
+) -> Result<Response, S::Error>
+where
+    S: Service<Request, Response = Response>,
+{
+    let limiter = &state.limiter;
+    let metrics = &state.metrics;
+
+    // 未認証のリクエストはハンドラーで 401 にする
+    let Some(caller) = request.extensions().get::<Caller>().cloned() else {
+        return inner.call(request).await;
+    };
+    let tools = message.map(called_tools).unwrap_or_default();
+    let id = message.and_then(|message| message.get("id")).cloned();
+    match limiter.check(&caller, &tools) {
+        Ok(()) => {}
+        Err(Rejected::Limited(limited)) => return Ok(limited_response(metrics, &caller.name, id, limited)),
+        Err(Rejected::TooManyCalls { tool, calls, capacity }) => {
+            tracing::info!(
+                target: "audit",
+                "batch rejected: key={}, tool={}, calls={}, capacity={}",
+                caller.name,
+                tool,
+                calls,
+                capacity
+            );
+            let error = JsonRpcError {
+                code: -32600,
+                message: format!(
+                    "Batch calls {} {} times, more than its rate limit allows at once ({})",
+                    tool, calls, capacity
+                ),
+                data: Some(json!({ "tool": tool, "calls": calls, "capacity": capacity })),
+            };
+            return Ok((StatusCode::BAD_REQUEST, Json(error_response(id, error))).into_response());
+        }
+    }
+    inner.call(request).await
+}
+
+// 応答の本文が送り終わる (または破棄される) まで permit を保持する
+fn hold_until_end(response: Response, permit: OwnedSemaphorePermit) -> Response {
+    let (parts, body) = response.into_parts();
+    let stream = body.into_data_stream().map(move |chunk| {
+        let _permit = &permit;
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+// クライアントからの応答のみ (method が無く result / error を持つ。バッチは全要素)
+fn is_response_only(message: &Value) -> bool {
+    let is_response = |item: &Value| {
+        item.get("method").is_none() && (item.get("result").is_some() || item.get("error").is_some())
+    };
+    match message {
+        Value::Array(items) => !items.is_empty() && items.iter().all(is_response),
+        _ => is_response(message),
+    }
+}
+
+// メッセージ (単体またはバッチ) 内の tools/call のツール名
+fn called_tools(message: &Value) -> Vec<String> {
+    match message {
+        Value::Array(items) => items.iter().flat_map(called_tools).collect(),
+        _ if message.get("method").and_then(Value::as_str) == Some("tools/call") => message["params"]["name"]
+            .as_str()
+            .map(|name| vec![name.to_string()])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+fn limited_response(metrics: &Metrics, key: &str, id: Option<Value>, limited: Limited) -> Response {
+    let retry_after = limited.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    tracing::info!(
+        target: "audit",
+        "rate limited: key={}, limit={}, tool={}, retry_after={}s",
+        key,
+        limited.limit,
+        limited.tool.as_deref().unwrap_or("-"),
+        retry_after
+    );
+    metrics.observe_rate_limited(limited.limit);
+
+    let error = JsonRpcError {
+        code: RATE_LIMITED,
+        message: "Rate limit exceeded".to_string(),
+        data: Some(json!({
+            "limit": limited.limit,
+            "tool": limited.tool,
+            "retryAfter": retry_after
+        })),
+    };
+    let status = if limited.limit == "in_flight" {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::TOO_MANY_REQUESTS
+    };
+    let mut response = (status, Json(error_response(id, error))).into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
@@ -0,0 +1,224 @@
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+
+use crate::mod_purchase::PurchaseFilter;
+use crate::mod_rpc::JsonRpcError;
+use crate::mod_session::RequestContext;
+use crate::mod_todo::TodoFilter;
+use crate::AppState;
+
+// MCP リソース
+//
+// purchase://recent      最近の購入データ
+// purchase://{id}        購入データ1件
+// purchase://item/{name} 品名ごとの購入データ (name はパーセントエンコード)
+// todo://list            todo 一覧
+// todo://{id}            todo 1件
+pub const PURCHASE_RECENT: &str = "purchase://recent";
+pub const TODO_LIST: &str = "todo://list";
+const PURCHASE_ITEM: &str = "purchase://item/";
+
+const RECENT_LIMIT: i64 = 20;
+const LIST_LIMIT: i64 = 100;
+
+pub fn purchase_uri(id: i64) -> String {
+    format!("purchase://{}", id)
+}
+
+pub fn purchase_item_uri(name: &str) -> String {
+    format!("{}{}", PURCHASE_ITEM, utf8_percent_encode(name, NON_ALPHANUMERIC))
+}
+
+pub fn todo_uri(id: i64) -> String {
+    format!("todo://{}", id)
+}
+
+fn resource_not_found(uri: &str) -> JsonRpcError {
+    JsonRpcError {
+        code: -32002,
+        message: "Resource not found".to_string(),
+        data: Some(json!({ "uri": uri })),
+    }
+}
+
+fn uri_param(params: &Option<Value>) -> Result<String, JsonRpcError> {
+    params
+        .as_ref()
+        .and_then(|p| p["uri"].as_str())
+        .map(str::to_string)
+        .ok_or_else(|| JsonRpcError::invalid_params("URI is required"))
+}
+
+// resources/list メソッド
+pub fn handle_resources_list() -> Result<Value, JsonRpcError> {
+    Ok(json!({
+        "resources": [
+            {
+                "uri": PURCHASE_RECENT,
+                "name": "Recent purchases",
+                "description": "最近の購入データ (新しい順)",
+                "mimeType": "application/json"
+            },
+            {
+                "uri": TODO_LIST,
+                "name": "Todo list",
+                "description": "todo 一覧 (未完了が先)",
+                "mimeType": "application/json"
+            }
+        ]
+    }))
+}
+
+// resources/templates/list メソッド
+pub fn handle_resource_templates_list() -> Result<Value, JsonRpcError> {
+    Ok(json!({
+        "resourceTemplates": [
+            {
+                "uriTemplate": "purchase://{id}",
+                "name": "Purchase",
+                "description": "購入データ1件",
+                "mimeType": "application/json"
+            },
+            {
+                "uriTemplate": "purchase://item/{name}",
+                "name": "Purchases by item",
+                "description": "品名ごとの購入データ (新しい順)",
+                "mimeType": "application/json"
+            },
+            {
+                "uriTemplate": "todo://{id}",
+                "name": "Todo",
+                "description": "todo 1件",
+                "mimeType": "application/json"
+            }
+        ]
+    }))
+}
+
+// resources/read メソッド
+pub async fn handle_resources_read(state: &AppState, params: Option<Value>) -> Result<Value, JsonRpcError> {
+    let uri = uri_param(&params)?;
+    let body = read(state, &uri).await?;
+    Ok(json!({
+        "contents": [
+            {
+                "uri": uri,
+                "mimeType": "application/json",
+                "text": serde_json::to_string_pretty(&body).map_err(JsonRpcError::internal)?
+            }
+        ]
+    }))
+}
+
+async fn read(state: &AppState, uri: &str) -> Result<Value, JsonRpcError> {
+    if uri == PURCHASE_RECENT {
+        let (items, total) = state
+            .purchases
+            .list(&PurchaseFilter::default(), RECENT_LIMIT, 0)
+            .await
+            .map_err(JsonRpcError::internal)?;
+        return Ok(json!({ "items": items, "total": total }));
+    }
+    if uri == TODO_LIST {
+        let todos = state
+            .todos
+            .list(TodoFilter::All, LIST_LIMIT)
+            .await
+            .map_err(JsonRpcError::internal)?;
+        return Ok(json!({ "items": todos }));
+    }
+    if let Some(name) = uri.strip_prefix(PURCHASE_ITEM) {
+        let name = percent_decode_str(name)
+            .decode_utf8()
+            .map_err(|_| resource_not_found(uri))?;
+        let filter = PurchaseFilter {
+            item: Some(name.to_string()),
+            ..Default::default()
+        };
+        let (items, total) = state
+            .purchases
+            .list(&filter, LIST_LIMIT, 0)
+            .await
+            .map_err(JsonRpcError::internal)?;
+        return Ok(json!({ "name": name, "items": items, "total": total }));
+    }
+
+    let id = |prefix: &str| {
+        uri.strip_prefix(prefix)
+            .and_then(|id| id.parse::<i64>().ok())
+    };
+    if let Some(id) = id("purchase://") {
+        let purchase = state.purchases.get(id).await.map_err(JsonRpcError::internal)?;
+        return purchase
+            .map(|p| json!(p))
+            .ok_or_else(|| resource_not_found(uri));
+    }
+    if let Some(id) = id("todo://") {
+        let todo = state.todos.get(id).await.map_err(JsonRpcError::internal)?;
+        return todo.map(|t| json!(t)).ok_or_else(|| resource_not_found(uri));
+    }
+    Err(resource_not_found(uri))
+}
+
+// テンプレート引数の補完候補 (completion/complete、最大 limit 件)
+pub async fn complete(
+    state: &AppState,
+    uri_template: &str,
+    argument: &str,
+    value: &str,
+    limit: usize,
+) -> Result<Vec<String>, JsonRpcError> {
+    let limit = limit as i64;
+    let values = match (uri_template, argument) {
+        ("purchase://{id}", "id") => state.purchases.ids(value, limit).await.map_err(JsonRpcError::internal)?,
+        ("purchase://item/{name}", "name") => state
+            .purchases
+            .names(value, limit)
+            .await
+            .map_err(JsonRpcError::internal)?,
+        // 未完了の todo のみ
+        ("todo://{id}", "id") => state.todos.open_ids(value, limit).await.map_err(JsonRpcError::internal)?,
+        _ => Vec::new(),
+    };
+    Ok(values)
+}
+
+// resources/subscribe メソッド
+pub async fn handle_resources_subscribe(
+    state: &AppState,
+    ctx: &RequestContext,
+    params: Option<Value>,
+) -> Result<Value, JsonRpcError> {
+    let session = ctx.require_session()?;
+    let uri = uri_param(&params)?;
+    // 存在しない URI は購読できない
+    read(state, &uri).await?;
+    session.subscribe(&uri);
+    tracing::info!("resource subscribed: session={}, uri={}", session.id, uri);
+    Ok(json!({}))
+}
+
+// resources/unsubscribe メソッド
+pub fn handle_resources_unsubscribe(ctx: &RequestContext, params: Option<Value>) -> Result<Value, JsonRpcError> {
+    let session = ctx.require_session()?;
+    let uri = uri_param(&params)?;
+    session.unsubscribe(&uri);
+    Ok(json!({}))
+}
+
+// データ変更時の通知 (購読中のセッションへ notifications/resources/updated)
+pub fn notify_updated(state: &AppState, uris: &[String]) {
+    for uri in uris {
+        let sent = state.sessions.broadcast_to_subscribers(
+            uri,
+            json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/resources/updated",
+                "params": { "uri": uri }
+            }),
+        );
+        if sent > 0 {
+            tracing::debug!("resource updated: uri={}, sessions={}", uri, sent);
+        }
+    }
+}
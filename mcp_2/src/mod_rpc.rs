@@ -0,0 +1,553 @@
+use futures::future::{join_all, AbortHandle, Abortable, Aborted};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::mod_audit::{self, NewAuditEntry};
+use crate::mod_auth::{PROMPTS_READ, RESOURCES_READ};
+use crate::mod_client::Client;
+use crate::mod_completion;
+use crate::mod_logging;
+use crate::mod_prompts;
+use crate::mod_resources;
+use crate::mod_schema;
+use crate::mod_session::{ClientInfo, RequestContext};
+use crate::mod_tools::Progress;
+use crate::AppState;
+
+// 対応プロトコルバージョン (新しい順)
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+const OLDEST_PROTOCOL_VERSION: &str = "2024-11-05";
+
+// メトリクスのラベルに使うメソッド名 (これ以外は "other" にまとめる)
+const KNOWN_METHODS: &[&str] = &[
+    "initialize",
+    "ping",
+    "tools/list",
+    "tools/call",
+    "resources/list",
+    "resources/templates/list",
+    "resources/read",
+    "resources/subscribe",
+    "resources/unsubscribe",
+    "prompts/list",
+    "prompts/get",
+    "completion/complete",
+    "logging/setLevel",
+];
+
+// notifications/cancelled で中断したリクエスト (応答は返さない)
+const REQUEST_CANCELLED: i32 = -32800;
+
+// JSON-RPC 2.0 Request
+#[derive(Debug, Deserialize)]
+pub struct JsonRpcRequest {
+    pub jsonrpc: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Option<Value>,
+    pub id: Option<Value>,
+}
+
+// JSON-RPC 2.0 Response
+#[derive(Debug, Serialize)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+    pub id: Option<Value>,
+}
+
+// JSON-RPC 2.0 Error
+#[derive(Debug, Serialize)]
+pub struct JsonRpcError {
+    pub code: i32,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl JsonRpcError {
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        JsonRpcError {
+            code: -32602,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    // 内部エラー (詳細は data.detail に入れる)
+    pub fn internal(detail: impl std::fmt::Display) -> Self {
+        tracing::error!("internal error: {}", detail);
+        JsonRpcError {
+            code: -32603,
+            message: "Internal error".to_string(),
+            data: Some(json!({ "detail": detail.to_string() })),
+        }
+    }
+
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
+// JSON-RPC レスポンス生成
+pub fn error_response(id: Option<Value>, error: JsonRpcError) -> JsonRpcResponse {
+    JsonRpcResponse {
+        jsonrpc: "2.0".to_string(),
+        result: None,
+        error: Some(error),
+        id,
+    }
+}
+
+fn to_message(response: JsonRpcResponse) -> Value {
+    serde_json::to_value(response).unwrap_or_default()
+}
+
+// パースエラー応答 (id は null)
+pub fn parse_error(detail: impl std::fmt::Display) -> Value {
+    to_message(error_response(None, JsonRpcError {
+        code: -32700,
+        message: "Parse error".to_string(),
+        data: Some(json!(detail.to_string())),
+    }))
+}
+
+// 受信メッセージの処理 (単体 / バッチ)
+//
+// 応答が無い場合 (通知のみ) は None。バッチの各要素は並行に処理する。
+pub async fn handle_message(state: &Arc<AppState>, ctx: &RequestContext, message: Value) -> Option<Value> {
+    match message {
+        Value::Array(items) if items.is_empty() => Some(to_message(error_response(None, JsonRpcError {
+            code: -32600,
+            message: "Invalid Request".to_string(),
+            data: Some(json!("empty batch")),
+        }))),
+        Value::Array(items) => {
+            let responses: Vec<Value> = join_all(items.into_iter().map(|item| handle_single(state, ctx, item)))
+                .await
+                .into_iter()
+                .flatten()
+                .collect();
+            if responses.is_empty() {
+                None
+            } else {
+                Some(Value::Array(responses))
+            }
+        }
+        message => handle_single(state, ctx, message).await,
+    }
+}
+
+// 処理中のログはリクエスト元のセッションへ notifications/message として送る
+async fn handle_single(state: &Arc<AppState>, ctx: &RequestContext, message: Value) -> Option<Value> {
+    mod_logging::scope(ctx.clone(), handle_request(state, ctx, message)).await
+}
+
+async fn handle_request(state: &Arc<AppState>, ctx: &RequestContext, message: Value) -> Option<Value> {
+    // サーバー発リクエスト (elicitation/create など) への応答
+    if message.get("method").is_none() && (message.get("result").is_some() || message.get("error").is_some()) {
+        handle_client_response(ctx, message);
+        return None;
+    }
+
+    // id は文字列か数値 (null は通知ではなく不正なリクエスト)
+    if let Some(id) = message.get("id")
+        && !(id.is_string() || id.is_number())
+    {
+        return Some(to_message(error_response(None, JsonRpcError {
+            code: -32600,
+            message: "Invalid Request".to_string(),
+            data: Some(json!("id must be a string or number")),
+        })));
+    }
+
+    let request: JsonRpcRequest = match serde_json::from_value(message.clone()) {
+        Ok(request) => request,
+        Err(e) => {
+            let id = message.get("id").cloned();
+            return Some(to_message(error_response(id, JsonRpcError {
+                code: -32600,
+                message: "Invalid Request".to_string(),
+                data: Some(json!(e.to_string())),
+            })));
+        }
+    };
+    tracing::info!("Received request: method={}, id={:?}", request.method, request.id);
+
+    // id の無いリクエストは通知 (応答しない)
+    if request.id.is_none() {
+        handle_notification(state, ctx, request).await;
+        return None;
+    }
+    let response = dispatch(state, ctx, request).await;
+    // キャンセルされたリクエストには応答しない
+    if response.error.as_ref().is_some_and(|e| e.code == REQUEST_CANCELLED) {
+        return None;
+    }
+    Some(to_message(response))
+}
+
+// クライアントからの応答を待機中のリクエストへ渡す
+fn handle_client_response(ctx: &RequestContext, mut message: Value) {
+    let id = message["id"].take();
+    let response = match message.get_mut("error") {
+        Some(error) => Err(error.take()),
+        None => Ok(message["result"].take()),
+    };
+    let resolved = ctx
+        .session
+        .as_ref()
+        .is_some_and(|session| session.resolve_request(&id, response));
+    if !resolved {
+        tracing::info!("unexpected response from client: id={}", id);
+    }
+}
+
+// 通知の処理
+async fn handle_notification(state: &Arc<AppState>, ctx: &RequestContext, request: JsonRpcRequest) {
+    match request.method.as_str() {
+        "notifications/initialized" => match &ctx.session {
+            Some(session) if session.mark_initialized() => {
+                tracing::info!("client initialized: session={}", session.id);
+            }
+            Some(session) => tracing::info!("initialized before initialize ignored: session={}", session.id),
+            None => tracing::info!("client initialized: session=-"),
+        },
+        "notifications/cancelled" => {
+            let params = request.params.unwrap_or_default();
+            let cancelled = ctx
+                .session
+                .as_ref()
+                .is_some_and(|session| session.cancel_request(&params["requestId"]));
+            tracing::info!(
+                "request cancelled by client: id={}, reason={}, running={}",
+                params["requestId"],
+                params["reason"].as_str().unwrap_or("-"),
+                cancelled
+            );
+        }
+        method if method.starts_with("notifications/") => {
+            tracing::debug!("notification ignored: {}", method);
+        }
+        // 通常のメソッドも通知として送られた場合は実行のみ行う
+        _ => {
+            let response = dispatch(state, ctx, request).await;
+            if let Some(error) = response.error {
+                tracing::info!("notification failed: code={}, message={}", error.code, error.message);
+            }
+        }
+    }
+}
+
+// メソッドディスパッチ (メソッドごとの件数と処理時間を記録)
+pub async fn dispatch(state: &Arc<AppState>, ctx: &RequestContext, request: JsonRpcRequest) -> JsonRpcResponse {
+    let started = Instant::now();
+    let method = request.method.clone();
+    let response = dispatch_request(state, ctx, request).await;
+
+    // 未知のメソッドはエラーの種類によらずラベルをまとめる
+    let label = if KNOWN_METHODS.contains(&method.as_str()) { method.as_str() } else { "other" };
+    state.metrics.observe_request(label, response.error.is_none(), started.elapsed());
+    response
+}
+
+async fn dispatch_request(state: &Arc<AppState>, ctx: &RequestContext, request: JsonRpcRequest) -> JsonRpcResponse {
+    // JSON-RPC 2.0 バージョンチェック
+    if request.jsonrpc != "2.0" {
+        return error_response(request.id, JsonRpcError {
+            code: -32600,
+            message: "Invalid Request".to_string(),
+            data: None,
+        });
+    }
+
+    // notifications/initialized を受け取るまでは initialize と ping 以外を受け付けない
+    if let Some(session) = &ctx.session
+        && !session.is_initialized()
+        && !matches!(request.method.as_str(), "initialize" | "ping")
+    {
+        return error_response(request.id, JsonRpcError {
+            code: -32600,
+            message: "Server not initialized".to_string(),
+            data: Some(json!({ "method": request.method })),
+        });
+    }
+
+    // リソース・プロンプトはスコープが無ければ一覧を空にし、読み取りは拒否する
+    let can_read_resources = ctx.caller.has_scope(RESOURCES_READ);
+    let can_read_prompts = ctx.caller.has_scope(PROMPTS_READ);
+    let result = match request.method.as_str() {
+        "initialize" => handle_initialize(state, ctx, request.params),
+        "ping" => Ok(json!({})),
+        "tools/list" => handle_tools_list(state, ctx),
+        "tools/call" => handle_tools_call(state.clone(), ctx, request.id.clone(), request.params).await,
+        "resources/list" if !can_read_resources => Ok(json!({ "resources": [] })),
+        "resources/list" => mod_resources::handle_resources_list(),
+        "resources/templates/list" if !can_read_resources => Ok(json!({ "resourceTemplates": [] })),
+        "resources/templates/list" => mod_resources::handle_resource_templates_list(),
+        "resources/read" | "resources/subscribe" if !can_read_resources => {
+            Err(forbidden_scope(ctx, RESOURCES_READ, &request.method))
+        }
+        "resources/read" => mod_resources::handle_resources_read(state, request.params).await,
+        "resources/subscribe" => mod_resources::handle_resources_subscribe(state, ctx, request.params).await,
+        "resources/unsubscribe" => mod_resources::handle_resources_unsubscribe(ctx, request.params),
+        "prompts/list" if !can_read_prompts => Ok(json!({ "prompts": [] })),
+        "prompts/list" => mod_prompts::handle_prompts_list(state),
+        "prompts/get" if !can_read_prompts => Err(forbidden_scope(ctx, PROMPTS_READ, &request.method)),
+        "prompts/get" => mod_prompts::handle_prompts_get(state, request.params).await,
+        "completion/complete" => mod_completion::handle_completion_complete(state, ctx, request.params).await,
+        "logging/setLevel" => mod_logging::handle_set_level(ctx, request.params),
+        _ => Err(JsonRpcError {
+            code: -32601,
+            message: "Method not found".to_string(),
+            data: None,
+        }),
+    };
+
+    match result {
+        Ok(result) => JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            result: Some(result),
+            error: None,
+            id: request.id,
+        },
+        Err(error) => error_response(request.id, error),
+    }
+}
+
+// スコープ不足 (tools/call の Forbidden と同じコード)
+pub fn forbidden_scope(ctx: &RequestContext, scope: &str, method: &str) -> JsonRpcError {
+    tracing::info!(target: "audit", "{} denied: key={}, required scope={}", method, ctx.caller.name, scope);
+    JsonRpcError {
+        code: -32003,
+        message: format!("Forbidden: {} scope is required", scope),
+        data: Some(json!({ "method": method, "scope": scope })),
+    }
+}
+
+// MCP initialize メソッド
+fn handle_initialize(state: &AppState, ctx: &RequestContext, params: Option<Value>) -> Result<Value, JsonRpcError> {
+    let params = params.unwrap_or_default();
+
+    // プロトコルバージョンの交渉 (未対応なら最新版を提示し、クライアント側で判断させる)
+    let requested = params["protocolVersion"].as_str().unwrap_or(OLDEST_PROTOCOL_VERSION);
+    let protocol_version = negotiate_protocol_version(requested);
+    if protocol_version != requested {
+        tracing::info!("protocol version {} is not supported, offering {}", requested, protocol_version);
+    }
+
+    if let Some(session) = &ctx.session {
+        let client = ClientInfo {
+            protocol_version: protocol_version.to_string(),
+            name: params["clientInfo"]["name"].as_str().unwrap_or("unknown").to_string(),
+            version: params["clientInfo"]["version"].as_str().unwrap_or("").to_string(),
+            capabilities: params["capabilities"].clone(),
+        };
+        tracing::info!(
+            "initialize: session={}, client={} {}, protocol={}, capabilities={}",
+            session.id,
+            client.name,
+            client.version,
+            client.protocol_version,
+            client.capabilities
+        );
+        if !session.initialize(client) {
+            return Err(JsonRpcError {
+                code: -32600,
+                message: "Session already initialized".to_string(),
+                data: None,
+            });
+        }
+    }
+
+    Ok(json!({
+        "protocolVersion": protocol_version,
+        "serverInfo": {
+            "name": state.server_name,
+            "version": state.version
+        },
+        "capabilities": {
+            "tools": {
+                "listChanged": false
+            },
+            "resources": {
+                "subscribe": true,
+                "listChanged": false
+            },
+            "prompts": {
+                "listChanged": false
+            },
+            "logging": {},
+            "completions": {}
+        }
+    }))
+}
+
+pub fn negotiate_protocol_version(requested: &str) -> &'static str {
+    SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .find(|v| **v == requested)
+        .copied()
+        .unwrap_or(SUPPORTED_PROTOCOL_VERSIONS[0])
+}
+
+// tools/list メソッド
+fn handle_tools_list(state: &AppState, ctx: &RequestContext) -> Result<Value, JsonRpcError> {
+    Ok(json!({
+        "tools": state.tools.definitions_for(&ctx.caller)
+    }))
+}
+
+// tools/call メソッド
+async fn handle_tools_call(
+    state: Arc<AppState>,
+    ctx: &RequestContext,
+    id: Option<Value>,
+    params: Option<Value>,
+) -> Result<Value, JsonRpcError> {
+    let params = params.ok_or(JsonRpcError {
+        code: -32602,
+        message: "Invalid params".to_string(),
+        data: None,
+    })?;
+
+    let tool_name = params["name"].as_str().ok_or(JsonRpcError {
+        code: -32602,
+        message: "Tool name is required".to_string(),
+        data: None,
+    })?;
+
+    let arguments = match &params["arguments"] {
+        Value::Null => json!({}),
+        arguments => arguments.clone(),
+    };
+
+    // _meta.progressToken (文字列または数値) があれば進捗を通知する
+    let token = &params["_meta"]["progressToken"];
+    let token = (token.is_string() || token.is_number()).then(|| token.clone());
+    let progress = Progress::new(token, ctx.clone());
+    let started = Instant::now();
+    let result = call_tool(&state, ctx, id, tool_name, &arguments, &progress).await;
+    record_tool_call(&state, ctx, tool_name, &arguments, &result, started.elapsed());
+    result
+}
+
+async fn call_tool(
+    state: &AppState,
+    ctx: &RequestContext,
+    id: Option<Value>,
+    tool_name: &str,
+    arguments: &Value,
+    progress: &Progress,
+) -> Result<Value, JsonRpcError> {
+    let tool = state.tools.get(tool_name).ok_or_else(|| JsonRpcError {
+        code: -32602,
+        message: format!("Unknown tool: {}", tool_name),
+        data: None,
+    })?;
+
+    // キーのスコープで許可されたツールのみ
+    if !ctx.caller.can_call_tool(tool.as_ref()) {
+        tracing::info!(target: "audit", "tool denied: key={}, tool={}", ctx.caller.name, tool_name);
+        return Err(JsonRpcError {
+            code: -32003,
+            message: "Forbidden: tool is not allowed for this API key".to_string(),
+            data: Some(json!({ "tool": tool_name })),
+        });
+    }
+
+    // 引数を inputSchema で検証
+    let violations = mod_schema::validate(&tool.input_schema(), arguments);
+    if !violations.is_empty() {
+        tracing::info!("invalid arguments: tool={}, violations={}", tool_name, violations.len());
+        return Err(JsonRpcError::invalid_params("Invalid params").with_data(json!({
+            "tool": tool_name,
+            "violations": violations
+        })));
+    }
+
+    // 時間切れ・キャンセル (notifications/cancelled) で実行中の future を破棄する
+    let (handle, registration) = AbortHandle::new_pair();
+    // 同じ id で処理中のリクエストがあると、キャンセルの対象を区別できないため拒否する
+    let _in_flight = match (&ctx.session, &id) {
+        (Some(session), Some(id)) => Some(session.track_request(id, handle).ok_or_else(|| JsonRpcError {
+            code: -32600,
+            message: "Request id is already in use by a request in progress".to_string(),
+            data: Some(json!({ "id": id })),
+        })?),
+        _ => None,
+    };
+    let timeout = state
+        .tool_timeouts
+        .get(tool_name)
+        .copied()
+        .or_else(|| tool.timeout())
+        .unwrap_or(state.tool_timeout);
+    let client = Client::new(ctx.clone());
+    let call = Abortable::new(tool.call_with_client(state, arguments, progress, &client), registration);
+    match tokio::time::timeout(timeout, call).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(Aborted)) => {
+            tracing::info!("tool cancelled: tool={}, id={:?}", tool_name, id);
+            Err(JsonRpcError {
+                code: REQUEST_CANCELLED,
+                message: "Request cancelled".to_string(),
+                data: Some(json!({ "tool": tool_name })),
+            })
+        }
+        Err(_) => {
+            tracing::warn!("tool timed out: tool={}, timeout={:?}", tool_name, timeout);
+            Err(JsonRpcError {
+                code: -32004,
+                message: format!("Tool timed out after {} seconds", timeout.as_secs()),
+                data: Some(json!({ "tool": tool_name, "timeoutSecs": timeout.as_secs() })),
+            })
+        }
+    }
+}
+
+// 監査ログ (tool_call_audit) とメトリクスへ記録
+//
+// 監査ログの書き込みは応答を待たせないようバックグラウンドで行う。
+fn record_tool_call(
+    state: &AppState,
+    ctx: &RequestContext,
+    tool_name: &str,
+    arguments: &Value,
+    result: &Result<Value, JsonRpcError>,
+    elapsed: Duration,
+) {
+    let error_code = result.as_ref().err().map(|e| i64::from(e.code));
+    tracing::info!(
+        target: "audit",
+        "tool called: key={}, tool={}, duration_ms={}, error_code={:?}",
+        ctx.caller.name,
+        tool_name,
+        elapsed.as_millis(),
+        error_code
+    );
+
+    // 未登録のツール名はラベルをまとめる
+    let label = if state.tools.get(tool_name).is_some() { tool_name } else { "unknown" };
+    state.metrics.observe_tool_call(label, error_code.is_none(), elapsed);
+
+    let audit = state.audit.clone();
+    let entry = NewAuditEntry {
+        tool: tool_name.to_string(),
+        arguments_digest: mod_audit::arguments_digest(arguments),
+        caller: ctx.caller.name.clone(),
+        duration_ms: elapsed.as_millis() as i64,
+        error_code,
+    };
+    tokio::spawn(async move {
+        if let Err(e) = audit.record(&entry).await {
+            tracing::warn!("audit log write error: tool={}, {}", entry.tool, e);
+        }
+    });
+}
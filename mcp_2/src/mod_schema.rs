@@ -0,0 +1,291 @@
+use chrono::{DateTime, NaiveDate};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+// inputSchema 検証 (JSON Schema のサブセット)
+//
+// 対応キーワード: type, required, properties, additionalProperties,
+// enum, minimum, maximum, exclusiveMinimum, exclusiveMaximum,
+// minLength, maxLength, format (date, date-time), items, minItems, maxItems
+// それ以外のキーワード (pattern, oneOf など) と未知の type は無視する。
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Violation {
+    pub path: String,
+    pub message: String,
+}
+
+pub fn validate(schema: &Value, value: &Value) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    validate_at(schema, value, "", &mut violations);
+    violations
+}
+
+fn push(violations: &mut Vec<Violation>, path: &str, message: String) {
+    violations.push(Violation {
+        path: if path.is_empty() { "/".to_string() } else { path.to_string() },
+        message,
+    });
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, violations: &mut Vec<Violation>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| matches_type(t, value)) {
+            push(
+                violations,
+                path,
+                format!("expected {}, got {}", types.join(" or "), type_name(value)),
+            );
+            // 型が違う場合は以降のキーワードを評価しない
+            return;
+        }
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum")
+        && !allowed.contains(value)
+    {
+        push(violations, path, format!("must be one of {}", Value::Array(allowed.clone())));
+    }
+
+    match value {
+        Value::Number(n) => validate_number(schema, n.as_f64().unwrap_or_default(), path, violations),
+        Value::String(s) => validate_string(schema, s, path, violations),
+        Value::Array(items) => validate_array(schema, items, path, violations),
+        Value::Object(map) => validate_object(schema, map, path, violations),
+        _ => {}
+    }
+}
+
+fn validate_number(schema: &Map<String, Value>, n: f64, path: &str, violations: &mut Vec<Violation>) {
+    if let Some(min) = schema.get("minimum").and_then(Value::as_f64)
+        && n < min
+    {
+        push(violations, path, format!("must be >= {}", min));
+    }
+    if let Some(max) = schema.get("maximum").and_then(Value::as_f64)
+        && n > max
+    {
+        push(violations, path, format!("must be <= {}", max));
+    }
+    if let Some(min) = schema.get("exclusiveMinimum").and_then(Value::as_f64)
+        && n <= min
+    {
+        push(violations, path, format!("must be > {}", min));
+    }
+    if let Some(max) = schema.get("exclusiveMaximum").and_then(Value::as_f64)
+        && n >= max
+    {
+        push(violations, path, format!("must be < {}", max));
+    }
+}
+
+fn validate_string(schema: &Map<String, Value>, s: &str, path: &str, violations: &mut Vec<Violation>) {
+    let len = s.chars().count() as u64;
+    if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+        && len < min
+    {
+        push(violations, path, format!("must be at least {} characters", min));
+    }
+    if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+        && len > max
+    {
+        push(violations, path, format!("must be at most {} characters", max));
+    }
+    let valid_format = match schema.get("format").and_then(Value::as_str) {
+        Some("date") => NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok(),
+        Some("date-time") => DateTime::parse_from_rfc3339(s).is_ok(),
+        _ => true,
+    };
+    if !valid_format {
+        let format = schema.get("format").and_then(Value::as_str).unwrap_or_default();
+        push(violations, path, format!("must be a valid {}", format));
+    }
+}
+
+fn validate_array(schema: &Map<String, Value>, items: &[Value], path: &str, violations: &mut Vec<Violation>) {
+    let len = items.len() as u64;
+    if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+        && len < min
+    {
+        push(violations, path, format!("must contain at least {} items", min));
+    }
+    if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+        && len > max
+    {
+        push(violations, path, format!("must contain at most {} items", max));
+    }
+    if let Some(item_schema) = schema.get("items") {
+        for (i, item) in items.iter().enumerate() {
+            validate_at(item_schema, item, &format!("{}/{}", path, i), violations);
+        }
+    }
+}
+
+fn validate_object(
+    schema: &Map<String, Value>,
+    map: &Map<String, Value>,
+    path: &str,
+    violations: &mut Vec<Violation>,
+) {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for key in required.iter().filter_map(Value::as_str) {
+            if !map.contains_key(key) {
+                push(violations, &format!("{}/{}", path, key), "is required".to_string());
+            }
+        }
+    }
+
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (key, child) in map {
+        let child_path = format!("{}/{}", path, key);
+        match properties.and_then(|p| p.get(key)) {
+            Some(child_schema) => validate_at(child_schema, child, &child_path, violations),
+            None => {
+                if schema.get("additionalProperties") == Some(&Value::Bool(false)) {
+                    push(violations, &child_path, "is not an allowed property".to_string());
+                }
+            }
+        }
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(violations: &[Violation]) -> Vec<&str> {
+        violations.iter().map(|v| v.path.as_str()).collect()
+    }
+
+    #[test]
+    fn type_mismatch_stops_at_the_value() {
+        let schema = json!({ "type": "integer", "minimum": 10 });
+        let violations = validate(&schema, &json!("5"));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "/");
+        assert_eq!(violations[0].message, "expected integer, got string");
+
+        // 整数値の小数は integer として扱う
+        assert!(validate(&schema, &json!(12.0)).is_empty());
+        assert_eq!(validate(&schema, &json!(12.5))[0].message, "expected integer, got number");
+        assert!(validate(&json!({ "type": ["string", "null"] }), &json!(null)).is_empty());
+    }
+
+    #[test]
+    fn required_properties_are_reported_by_path() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "name": { "type": "string" }, "price": { "type": "integer" } },
+            "required": ["name", "price"]
+        });
+        let violations = validate(&schema, &json!({ "name": "coffee" }));
+        assert_eq!(paths(&violations), ["/price"]);
+        assert_eq!(violations[0].message, "is required");
+    }
+
+    #[test]
+    fn enum_limits_values() {
+        let schema = json!({ "type": "string", "enum": ["open", "done"] });
+        assert!(validate(&schema, &json!("open")).is_empty());
+        let violations = validate(&schema, &json!("all"));
+        assert_eq!(violations[0].message, r#"must be one of ["open","done"]"#);
+    }
+
+    #[test]
+    fn minimum_and_maximum_are_inclusive() {
+        let schema = json!({ "type": "number", "minimum": 1, "maximum": 100 });
+        assert!(validate(&schema, &json!(1)).is_empty());
+        assert!(validate(&schema, &json!(100)).is_empty());
+        assert_eq!(validate(&schema, &json!(0))[0].message, "must be >= 1");
+        assert_eq!(validate(&schema, &json!(100.5))[0].message, "must be <= 100");
+
+        let schema = json!({ "type": "number", "exclusiveMinimum": 0, "exclusiveMaximum": 1 });
+        assert_eq!(validate(&schema, &json!(0))[0].message, "must be > 0");
+        assert_eq!(validate(&schema, &json!(1))[0].message, "must be < 1");
+    }
+
+    #[test]
+    fn additional_properties_false_rejects_unknown_keys() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "name": { "type": "string" } },
+            "additionalProperties": false
+        });
+        let violations = validate(&schema, &json!({ "name": "coffee", "extra": true }));
+        assert_eq!(paths(&violations), ["/extra"]);
+        assert_eq!(violations[0].message, "is not an allowed property");
+
+        // 指定が無ければ許可する
+        let schema = json!({ "type": "object", "properties": {} });
+        assert!(validate(&schema, &json!({ "extra": true })).is_empty());
+    }
+
+    #[test]
+    fn nested_values_report_full_path() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "items": { "type": "array", "items": { "type": "string", "minLength": 1 }, "maxItems": 2 }
+            }
+        });
+        let violations = validate(&schema, &json!({ "items": ["a", "", "c"] }));
+        assert_eq!(paths(&violations), ["/items", "/items/1"]);
+    }
+
+    #[test]
+    fn unsupported_keywords_are_ignored() {
+        let schema = json!({
+            "type": "string",
+            "pattern": "^[0-9]+$",
+            "format": "email",
+            "contentMediaType": "text/plain",
+            "oneOf": [{ "const": "x" }]
+        });
+        assert!(validate(&schema, &json!("not digits")).is_empty());
+        assert!(validate(&json!({ "type": "uuid" }), &json!(1)).is_empty());
+        assert!(validate(&json!(true), &json!(1)).is_empty());
+    }
+
+    #[test]
+    fn date_formats_are_checked() {
+        let schema = json!({ "type": "string", "format": "date" });
+        assert!(validate(&schema, &json!("2025-01-31")).is_empty());
+        assert_eq!(validate(&schema, &json!("2025-02-30"))[0].message, "must be a valid date");
+        let schema = json!({ "type": "string", "format": "date-time" });
+        assert!(validate(&schema, &json!("2025-01-31T09:00:00+09:00")).is_empty());
+        assert!(!validate(&schema, &json!("2025-01-31 09:00")).is_empty());
+    }
+}
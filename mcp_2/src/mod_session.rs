@@ -0,0 +1,383 @@
+use serde_json::{json, Value};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use futures::future::AbortHandle;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::mod_auth::Caller;
+use crate::mod_logging::LogLevel;
+use crate::mod_rpc::JsonRpcError;
+
+// initialize で受け取ったクライアント情報
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub protocol_version: String,
+    pub name: String,
+    pub version: String,
+    pub capabilities: Value,
+}
+
+// Streamable HTTP セッション
+//
+// initialize 時に Mcp-Session-Id を払い出し、GET /mcp の SSE ストリーム
+// (サーバー → クライアント方向) の送信先を保持する。
+pub struct Session {
+    pub id: String,
+    // セッションを作成したキー名 (他のキーからは使えない)
+    pub owner: String,
+    // 最後にリクエストを受けた時刻 (アイドル時間の判定に使う)
+    last_seen: Mutex<Instant>,
+    stream: Mutex<Option<mpsc::UnboundedSender<Value>>>,
+    subscriptions: Mutex<HashSet<String>>,
+    client: RwLock<Option<ClientInfo>>,
+    // notifications/initialized を受け取った
+    initialized: AtomicBool,
+    // 処理中のリクエスト (notifications/cancelled で中断する)
+    in_flight: Mutex<HashMap<String, AbortHandle>>,
+    // logging/setLevel で指定された通知レベル (未指定なら送らない)
+    log_level: Mutex<Option<LogLevel>>,
+    // サーバー → クライアントのリクエスト (elicitation/create など) の応答待ち
+    pending: Mutex<HashMap<String, oneshot::Sender<ClientResponse>>>,
+    next_request_id: AtomicI64,
+}
+
+// クライアントからの応答 (result または error)
+pub type ClientResponse = Result<Value, Value>;
+
+impl Session {
+    fn new(id: String, owner: String) -> Self {
+        Session {
+            id,
+            owner,
+            last_seen: Mutex::new(Instant::now()),
+            stream: Mutex::new(None),
+            subscriptions: Mutex::new(HashSet::new()),
+            client: RwLock::new(None),
+            initialized: AtomicBool::new(false),
+            in_flight: Mutex::new(HashMap::new()),
+            log_level: Mutex::new(None),
+            pending: Mutex::new(HashMap::new()),
+            next_request_id: AtomicI64::new(1),
+        }
+    }
+
+    // initialize 成功時に記録 (initialize 済みなら false)
+    pub fn initialize(&self, client: ClientInfo) -> bool {
+        let mut current = self.client.write().unwrap();
+        if current.is_some() {
+            return false;
+        }
+        *current = Some(client);
+        true
+    }
+
+    // notifications/initialized で初期化完了とする (initialize 前なら false)
+    pub fn mark_initialized(&self) -> bool {
+        if self.client.read().unwrap().is_none() {
+            return false;
+        }
+        self.initialized.store(true, Ordering::Relaxed);
+        true
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Relaxed)
+    }
+
+    fn touch(&self) {
+        *self.last_seen.lock().unwrap() = Instant::now();
+    }
+
+    // idle_timeout の間リクエストが無く、GET /mcp のストリームも処理中のリクエストも無い
+    fn is_expired(&self, idle_timeout: Duration) -> bool {
+        let streaming = self.stream.lock().unwrap().as_ref().is_some_and(|tx| !tx.is_closed());
+        !streaming
+            && self.in_flight.lock().unwrap().is_empty()
+            && self.last_seen.lock().unwrap().elapsed() >= idle_timeout
+    }
+
+    pub fn client(&self) -> Option<ClientInfo> {
+        self.client.read().unwrap().clone()
+    }
+
+    // GET /mcp のストリームを開く (既存のストリームは置き換える)
+    pub fn open_stream(&self) -> mpsc::UnboundedReceiver<Value> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.stream.lock().unwrap() = Some(tx);
+        rx
+    }
+
+    pub fn close_stream(&self) {
+        self.stream.lock().unwrap().take();
+    }
+
+    // ストリームへ送信 (未接続なら false)
+    pub fn send(&self, message: Value) -> bool {
+        let mut stream = self.stream.lock().unwrap();
+        match stream.as_ref() {
+            Some(tx) if tx.send(message).is_ok() => true,
+            Some(_) => {
+                // クライアントが切断済み
+                *stream = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn subscribe(&self, uri: &str) {
+        self.subscriptions.lock().unwrap().insert(uri.to_string());
+    }
+
+    pub fn unsubscribe(&self, uri: &str) {
+        self.subscriptions.lock().unwrap().remove(uri);
+    }
+
+    pub fn is_subscribed(&self, uri: &str) -> bool {
+        self.subscriptions.lock().unwrap().contains(uri)
+    }
+
+    pub fn set_log_level(&self, level: LogLevel) {
+        *self.log_level.lock().unwrap() = Some(level);
+    }
+
+    pub fn log_level(&self) -> Option<LogLevel> {
+        *self.log_level.lock().unwrap()
+    }
+
+    // 処理中のリクエストを登録 (戻り値を破棄すると登録も外れる。同じ id が処理中なら None)
+    pub fn track_request(self: &Arc<Self>, id: &Value, handle: AbortHandle) -> Option<InFlightGuard> {
+        let key = id.to_string();
+        match self.in_flight.lock().unwrap().entry(key.clone()) {
+            Entry::Occupied(_) => return None,
+            Entry::Vacant(entry) => {
+                entry.insert(handle);
+            }
+        }
+        Some(InFlightGuard {
+            session: self.clone(),
+            key,
+        })
+    }
+
+    // リクエストを中断 (処理中でなければ false)
+    pub fn cancel_request(&self, id: &Value) -> bool {
+        match self.in_flight.lock().unwrap().remove(&id.to_string()) {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // サーバー発リクエストの id を払い出し、応答待ちに登録する
+    //
+    // 戻り値の PendingRequest を破棄すると登録も外れる (応答前なら notifications/cancelled を送る)。
+    pub fn pending_request(self: &Arc<Self>) -> PendingRequest {
+        let id = Value::from(self.next_request_id.fetch_add(1, Ordering::Relaxed));
+        let (tx, rx) = oneshot::channel();
+        let key = id.to_string();
+        self.pending.lock().unwrap().insert(key.clone(), tx);
+        PendingRequest {
+            session: self.clone(),
+            id,
+            key,
+            response: rx,
+        }
+    }
+
+    // クライアントの応答を待っているリクエストへ渡す (該当が無ければ false)
+    pub fn resolve_request(&self, id: &Value, response: ClientResponse) -> bool {
+        match self.pending.lock().unwrap().remove(&id.to_string()) {
+            Some(tx) => tx.send(response).is_ok(),
+            None => false,
+        }
+    }
+}
+
+pub struct PendingRequest {
+    session: Arc<Session>,
+    pub id: Value,
+    key: String,
+    response: oneshot::Receiver<ClientResponse>,
+}
+
+impl PendingRequest {
+    // 応答を待つ (セッションが閉じられた場合は None)
+    pub async fn response(&mut self) -> Option<ClientResponse> {
+        (&mut self.response).await.ok()
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        // 応答前に破棄された (ツールの時間切れ・キャンセル)
+        if self.session.pending.lock().unwrap().remove(&self.key).is_some() {
+            self.session.send(json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/cancelled",
+                "params": { "requestId": self.id, "reason": "request no longer needed" }
+            }));
+        }
+    }
+}
+
+pub struct InFlightGuard {
+    session: Arc<Session>,
+    key: String,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.session.in_flight.lock().unwrap().remove(&self.key);
+    }
+}
+
+// 期限切れセッションの掃除間隔の上限
+const MAX_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct SessionStore {
+    sessions: RwLock<HashMap<String, Arc<Session>>>,
+    // この時間使われなかったセッションは破棄する
+    idle_timeout: Duration,
+    // キー (owner) ごとのセッション数の上限
+    max_per_owner: Option<usize>,
+}
+
+impl SessionStore {
+    pub fn new(idle_timeout: Duration, max_per_owner: Option<usize>) -> Self {
+        SessionStore {
+            sessions: RwLock::new(HashMap::new()),
+            idle_timeout,
+            max_per_owner,
+        }
+    }
+
+    // owner は作成したキー名 (Caller::name)
+    //
+    // owner のセッション数が上限に達していればエラー (期限切れのセッションは数えない)。
+    pub fn create(&self, owner: &str) -> Result<Arc<Session>, JsonRpcError> {
+        let mut sessions = self.sessions.write().unwrap();
+        if let Some(max) = self.max_per_owner {
+            let active = sessions
+                .values()
+                .filter(|s| s.owner == owner && !s.is_expired(self.idle_timeout))
+                .count();
+            if active >= max {
+                tracing::info!(target: "audit", "session limit reached: key={}, max={}", owner, max);
+                return Err(JsonRpcError {
+                    code: -32000,
+                    message: "Too many sessions for this key".to_string(),
+                    data: Some(json!({ "maxSessions": max })),
+                });
+            }
+        }
+        let id = uuid::Uuid::new_v4().to_string();
+        let session = Arc::new(Session::new(id.clone(), owner.to_string()));
+        sessions.insert(id, session.clone());
+        Ok(session)
+    }
+
+    // セッションを取得して最終利用時刻を更新する (期限切れなら破棄して None)
+    pub fn get(&self, id: &str) -> Option<Arc<Session>> {
+        let session = self.sessions.read().unwrap().get(id).cloned()?;
+        if session.is_expired(self.idle_timeout) {
+            self.remove(id);
+            return None;
+        }
+        session.touch();
+        Some(session)
+    }
+
+    // 期限切れのセッションを破棄する (破棄した数を返す)
+    pub fn sweep(&self) -> usize {
+        let expired: Vec<String> = self
+            .sessions
+            .read()
+            .unwrap()
+            .values()
+            .filter(|s| s.is_expired(self.idle_timeout))
+            .map(|s| s.id.clone())
+            .collect();
+        for id in &expired {
+            self.remove(id);
+            tracing::info!("session expired: {}", id);
+        }
+        expired.len()
+    }
+
+    pub fn sweep_interval(&self) -> Duration {
+        self.idle_timeout.min(MAX_SWEEP_INTERVAL)
+    }
+
+    // uri を購読中のセッションへ送信 (送信できた数を返す)
+    pub fn broadcast_to_subscribers(&self, uri: &str, message: Value) -> usize {
+        let sessions: Vec<Arc<Session>> = self.sessions.read().unwrap().values().cloned().collect();
+        sessions
+            .iter()
+            .filter(|s| s.is_subscribed(uri))
+            .filter(|s| s.send(message.clone()))
+            .count()
+    }
+
+    pub fn remove(&self, id: &str) -> Option<Arc<Session>> {
+        let session = self.sessions.write().unwrap().remove(id);
+        if let Some(session) = &session {
+            session.close_stream();
+        }
+        session
+    }
+
+    // 全セッションの GET /mcp ストリームを閉じる (シャットダウン時)
+    pub fn close_streams(&self) {
+        for session in self.sessions.read().unwrap().values() {
+            session.close_stream();
+        }
+    }
+}
+
+// リクエスト単位のコンテキスト
+#[derive(Clone)]
+pub struct RequestContext {
+    pub session: Option<Arc<Session>>,
+    pub caller: Caller,
+    // 応答と同じストリームへ送る通知 (POST の SSE 応答)
+    outbound: Option<mpsc::UnboundedSender<Value>>,
+}
+
+impl RequestContext {
+    pub fn new(session: Option<Arc<Session>>, caller: Caller) -> Self {
+        RequestContext {
+            session,
+            caller,
+            outbound: None,
+        }
+    }
+
+    pub fn with_outbound(mut self, tx: mpsc::UnboundedSender<Value>) -> Self {
+        self.outbound = Some(tx);
+        self
+    }
+
+    // クライアントへ通知を送る
+    //
+    // POST の SSE 応答中はそのストリームへ、それ以外はセッションのストリーム (GET / stdio) へ。
+    pub fn notify(&self, message: Value) -> bool {
+        if let Some(tx) = &self.outbound {
+            return tx.send(message).is_ok();
+        }
+        self.session.as_ref().is_some_and(|s| s.send(message))
+    }
+
+    pub fn require_session(&self) -> Result<&Arc<Session>, JsonRpcError> {
+        self.session.as_ref().ok_or(JsonRpcError {
+            code: -32600,
+            message: "This method requires an MCP session (Mcp-Session-Id)".to_string(),
+            data: None,
+        })
+    }
+}
@@ -0,0 +1,87 @@
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+use crate::mod_auth::Caller;
+use crate::mod_rpc::{handle_message, parse_error};
+use crate::mod_session::RequestContext;
+use crate::AppState;
+
+// stdio トランスポート
+//
+// stdin から改行区切りの JSON-RPC を読み、応答を stdout へ1行ずつ書き出す。
+// リクエストは並行に処理し、stdout への書き込みは1タスクに集約する。
+pub async fn run(state: Arc<AppState>) {
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = rx.recv().await {
+            let mut line = message.to_string();
+            line.push('\n');
+            if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err() {
+                break;
+            }
+        }
+    });
+
+    // stdio 接続全体を1セッションとして扱い、サーバー発の通知も stdout へ流す
+    let caller = Caller::local();
+    let session = match state.sessions.create(&caller.name) {
+        Ok(session) => session,
+        Err(error) => {
+            tracing::error!("cannot create stdio session: {}", error.message);
+            return;
+        }
+    };
+    let mut notifications = session.open_stream();
+    let forward_tx = tx.clone();
+    tokio::spawn(async move {
+        while let Some(message) = notifications.recv().await {
+            if forward_tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    let ctx = RequestContext::new(Some(session.clone()), caller).with_outbound(tx.clone());
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::error!("stdin read error: {}", e);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                let _ = tx.send(parse_error(e));
+                continue;
+            }
+        };
+
+        let state = state.clone();
+        let ctx = ctx.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            if let Some(response) = handle_message(&state, &ctx, message).await {
+                let _ = tx.send(response);
+            }
+        });
+    }
+
+    // stdin が閉じたら処理中の応答を書き切って終了
+    drop(ctx);
+    state.sessions.remove(&session.id);
+    drop(tx);
+    let _ = writer.await;
+    tracing::info!("stdin closed, shutting down");
+}
@@ -0,0 +1,399 @@
+use async_trait::async_trait;
+use libsql::{params, Row};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::mod_db::Db;
+use crate::mod_resources;
+use crate::mod_rpc::JsonRpcError;
+use crate::mod_tools::{arg_i64, arg_i64_or, arg_str, text_result, Tool};
+use crate::AppState;
+
+const MIGRATIONS: &[(&str, &str)] = &[(
+    "todo_001_create",
+    "CREATE TABLE IF NOT EXISTS todo (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        title TEXT NOT NULL,
+        completed INTEGER NOT NULL DEFAULT 0,
+        created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+        completed_at TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_todo_completed ON todo (completed);",
+)];
+
+#[derive(Debug, Serialize)]
+pub struct Todo {
+    pub id: i64,
+    pub title: String,
+    pub completed: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    pub completed_at: Option<String>,
+}
+
+impl Todo {
+    fn from_row(row: &Row) -> Result<Self, libsql::Error> {
+        Ok(Todo {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            completed: row.get::<i64>(2)? != 0,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+            completed_at: row.get(5)?,
+        })
+    }
+
+    fn line(&self) -> String {
+        let mark = if self.completed { "x" } else { " " };
+        format!("[{}] {}: {}", mark, self.id, self.title)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TodoFilter {
+    All,
+    Open,
+    Done,
+}
+
+impl TodoFilter {
+    fn parse(value: Option<&str>) -> Self {
+        match value {
+            Some("all") => TodoFilter::All,
+            Some("done") => TodoFilter::Done,
+            _ => TodoFilter::Open,
+        }
+    }
+
+    fn condition(self) -> &'static str {
+        match self {
+            TodoFilter::All => "1 = 1",
+            TodoFilter::Open => "completed = 0",
+            TodoFilter::Done => "completed = 1",
+        }
+    }
+}
+
+const SELECT_COLUMNS: &str = "SELECT id, title, completed, created_at, updated_at, completed_at FROM todo";
+
+// todo ストア
+#[derive(Clone)]
+pub struct TodoStore {
+    db: Db,
+}
+
+impl TodoStore {
+    pub async fn open(db: Db) -> Result<Self, libsql::Error> {
+        db.migrate(MIGRATIONS).await?;
+        Ok(TodoStore { db })
+    }
+
+    async fn collect(&self, sql: &str, params: impl libsql::params::IntoParams) -> Result<Vec<Todo>, libsql::Error> {
+        let mut rows = self.db.conn().query(sql, params).await?;
+        let mut todos = Vec::new();
+        while let Some(row) = rows.next().await? {
+            todos.push(Todo::from_row(&row)?);
+        }
+        Ok(todos)
+    }
+
+    pub async fn add(&self, title: &str) -> Result<i64, libsql::Error> {
+        let mut rows = self
+            .db
+            .conn()
+            .query("INSERT INTO todo (title) VALUES (?1) RETURNING id", params![title])
+            .await?;
+        match rows.next().await? {
+            Some(row) => row.get(0),
+            None => Ok(self.db.conn().last_insert_rowid()),
+        }
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<Todo>, libsql::Error> {
+        let sql = format!("{} WHERE id = ?1", SELECT_COLUMNS);
+        Ok(self.collect(&sql, params![id]).await?.into_iter().next())
+    }
+
+    pub async fn list(&self, filter: TodoFilter, limit: i64) -> Result<Vec<Todo>, libsql::Error> {
+        let sql = format!(
+            "{} WHERE {} ORDER BY completed ASC, id DESC LIMIT ?1",
+            SELECT_COLUMNS,
+            filter.condition()
+        );
+        self.collect(&sql, params![limit]).await
+    }
+
+    pub async fn search(&self, query: &str, filter: TodoFilter, limit: i64) -> Result<Vec<Todo>, libsql::Error> {
+        // LIKE のワイルドカードはエスケープして部分一致検索
+        let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let sql = format!(
+            "{} WHERE title LIKE ?1 ESCAPE '\\' AND {} ORDER BY completed ASC, id DESC LIMIT ?2",
+            SELECT_COLUMNS,
+            filter.condition()
+        );
+        self.collect(&sql, params![format!("%{}%", escaped), limit]).await
+    }
+
+    // 未完了の todo の id (新しい順、prefix で前方一致)
+    pub async fn open_ids(&self, prefix: &str, limit: i64) -> Result<Vec<String>, libsql::Error> {
+        let escaped = prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let mut rows = self
+            .db
+            .conn()
+            .query(
+                "SELECT CAST(id AS TEXT) FROM todo
+                WHERE completed = 0 AND CAST(id AS TEXT) LIKE ?1 ESCAPE '\\'
+                ORDER BY id DESC
+                LIMIT ?2",
+                params![format!("{}%", escaped), limit],
+            )
+            .await?;
+        let mut ids = Vec::new();
+        while let Some(row) = rows.next().await? {
+            ids.push(row.get(0)?);
+        }
+        Ok(ids)
+    }
+
+    // 完了にする (対象が無ければ None)
+    pub async fn complete(&self, id: i64) -> Result<Option<Todo>, libsql::Error> {
+        let updated = self
+            .db
+            .conn()
+            .execute(
+                "UPDATE todo
+                SET completed = 1,
+                    completed_at = COALESCE(completed_at, CURRENT_TIMESTAMP),
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE id = ?1",
+                params![id],
+            )
+            .await?;
+        if updated == 0 {
+            return Ok(None);
+        }
+        self.get(id).await
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<bool, libsql::Error> {
+        let deleted = self
+            .db
+            .conn()
+            .execute("DELETE FROM todo WHERE id = ?1", params![id])
+            .await?;
+        Ok(deleted > 0)
+    }
+}
+
+fn todo_lines(todos: &[Todo]) -> String {
+    if todos.is_empty() {
+        return "No todos found".to_string();
+    }
+    todos.iter().map(Todo::line).collect::<Vec<_>>().join("\n")
+}
+
+fn notify_todo_updated(state: &AppState, id: i64) {
+    mod_resources::notify_updated(state, &[mod_resources::TODO_LIST.to_string(), mod_resources::todo_uri(id)]);
+}
+
+fn todo_not_found(id: i64) -> JsonRpcError {
+    JsonRpcError::invalid_params(format!("Todo not found: {}", id))
+}
+
+fn status_schema(default: &str) -> Value {
+    json!({
+        "type": "string",
+        "enum": ["open", "done", "all"],
+        "description": format!("Filter by status (default: {})", default)
+    })
+}
+
+fn limit_schema() -> Value {
+    json!({
+        "type": "integer",
+        "minimum": 1,
+        "maximum": 100,
+        "description": "Maximum number of items (default: 50)"
+    })
+}
+
+pub struct AddTodoTool;
+
+#[async_trait]
+impl Tool for AddTodoTool {
+    fn name(&self) -> &'static str {
+        "add_todo"
+    }
+
+    fn description(&self) -> &'static str {
+        "Add a new todo item to the database"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "title": {
+                    "type": "string",
+                    "minLength": 1,
+                    "description": "The todo item title/content"
+                }
+            },
+            "required": ["title"]
+        })
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let title = arg_str(arguments, "title")?;
+        let id = state.todos.add(title).await.map_err(JsonRpcError::internal)?;
+        notify_todo_updated(state, id);
+        Ok(text_result(format!("Todo added successfully with ID: {}", id)))
+    }
+}
+
+pub struct ListTodosTool;
+
+#[async_trait]
+impl Tool for ListTodosTool {
+    fn name(&self) -> &'static str {
+        "list_todos"
+    }
+
+    fn description(&self) -> &'static str {
+        "List todo items"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "status": status_schema("open"),
+                "limit": limit_schema()
+            },
+            "required": []
+        })
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let filter = TodoFilter::parse(arguments["status"].as_str());
+        let limit = arg_i64_or(arguments, "limit", 50)?;
+        let todos = state.todos.list(filter, limit).await.map_err(JsonRpcError::internal)?;
+        Ok(text_result(todo_lines(&todos)))
+    }
+}
+
+pub struct CompleteTodoTool;
+
+#[async_trait]
+impl Tool for CompleteTodoTool {
+    fn name(&self) -> &'static str {
+        "complete_todo"
+    }
+
+    fn description(&self) -> &'static str {
+        "Mark a todo item as completed"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "minimum": 1, "description": "Todo ID" }
+            },
+            "required": ["id"]
+        })
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let id = arg_i64(arguments, "id")?;
+        let todo = state
+            .todos
+            .complete(id)
+            .await
+            .map_err(JsonRpcError::internal)?
+            .ok_or_else(|| todo_not_found(id))?;
+        notify_todo_updated(state, id);
+        Ok(text_result(format!("Todo completed: {}", todo.line())))
+    }
+}
+
+pub struct DeleteTodoTool;
+
+#[async_trait]
+impl Tool for DeleteTodoTool {
+    fn name(&self) -> &'static str {
+        "delete_todo"
+    }
+
+    fn description(&self) -> &'static str {
+        "Delete a todo item"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "integer", "minimum": 1, "description": "Todo ID" }
+            },
+            "required": ["id"]
+        })
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let id = arg_i64(arguments, "id")?;
+        if !state.todos.delete(id).await.map_err(JsonRpcError::internal)? {
+            return Err(todo_not_found(id));
+        }
+        notify_todo_updated(state, id);
+        Ok(text_result(format!("Todo deleted: {}", id)))
+    }
+}
+
+pub struct SearchTodosTool;
+
+#[async_trait]
+impl Tool for SearchTodosTool {
+    fn name(&self) -> &'static str {
+        "search_todos"
+    }
+
+    fn description(&self) -> &'static str {
+        "Search todo items by title"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "minLength": 1, "description": "Text to search for" },
+                "status": status_schema("all"),
+                "limit": limit_schema()
+            },
+            "required": ["query"]
+        })
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let query = arg_str(arguments, "query")?;
+        let filter = match arguments["status"].as_str() {
+            None => TodoFilter::All,
+            status => TodoFilter::parse(status),
+        };
+        let limit = arg_i64_or(arguments, "limit", 50)?;
+        let todos = state
+            .todos
+            .search(query, filter, limit)
+            .await
+            .map_err(JsonRpcError::internal)?;
+        Ok(text_result(todo_lines(&todos)))
+    }
+}
@@ -0,0 +1,298 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::mod_auth::Caller;
+use crate::mod_client::Client;
+use crate::mod_rpc::JsonRpcError;
+use crate::mod_session::RequestContext;
+use crate::mod_todo;
+use crate::AppState;
+
+// MCP ツール定義
+//
+// tools/list の内容は登録済みツールから生成されるため、
+// スキーマと実装を同じ場所で管理できる。
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    fn input_schema(&self) -> Value;
+
+    // structuredContent のスキーマ (任意)
+    fn output_schema(&self) -> Option<Value> {
+        None
+    }
+
+    // データを変更しないツール (tools:read スコープで呼び出せる)
+    fn read_only(&self) -> bool {
+        false
+    }
+
+    // 実行時間の上限 (None なら TOOL_TIMEOUT_SECS, tools.timeouts の指定が優先)
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError>;
+
+    // 進捗を通知する長時間ツールはこちらを実装する
+    async fn call_with_progress(
+        &self,
+        state: &AppState,
+        arguments: &Value,
+        _progress: &Progress,
+    ) -> Result<Value, JsonRpcError> {
+        self.call(state, arguments).await
+    }
+
+    // クライアントへ問い合わせる (elicitation / sampling) ツールはこちらを実装する
+    async fn call_with_client(
+        &self,
+        state: &AppState,
+        arguments: &Value,
+        progress: &Progress,
+        _client: &Client,
+    ) -> Result<Value, JsonRpcError> {
+        self.call_with_progress(state, arguments, progress).await
+    }
+
+    // tools/list 用の定義
+    fn definition(&self) -> Value {
+        let mut definition = json!({
+            "name": self.name(),
+            "description": self.description(),
+            "inputSchema": self.input_schema(),
+            "annotations": {
+                "readOnlyHint": self.read_only()
+            }
+        });
+        if let Some(schema) = self.output_schema() {
+            definition["outputSchema"] = schema;
+        }
+        definition
+    }
+}
+
+// ツールレジストリ (登録順を保持)
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<T: Tool + 'static>(&mut self, tool: T) {
+        let name = tool.name().to_string();
+        self.tools.retain(|t| t.name() != name);
+        self.tools.push(Arc::new(tool));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.iter().find(|t| t.name() == name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.tools.iter().map(|t| t.name()).collect()
+    }
+
+    // 条件に合うツールだけを残す
+    pub fn retain(&mut self, f: impl Fn(&str) -> bool) {
+        self.tools.retain(|t| f(t.name()));
+    }
+
+    // 呼び出し元のスコープで呼び出せるツールの定義
+    pub fn definitions_for(&self, caller: &Caller) -> Vec<Value> {
+        self.tools
+            .iter()
+            .filter(|t| caller.can_call_tool(t.as_ref()))
+            .map(|t| t.definition())
+            .collect()
+    }
+}
+
+// notifications/progress の送信
+//
+// クライアントが _meta.progressToken を指定した場合のみ通知する。
+pub struct Progress {
+    target: Option<(Value, RequestContext)>,
+}
+
+impl Progress {
+    pub fn new(token: Option<Value>, ctx: RequestContext) -> Self {
+        Progress {
+            target: token.map(|token| (token, ctx)),
+        }
+    }
+
+    // 通知しない (progressToken なし)
+    pub fn none() -> Self {
+        Progress { target: None }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.target.is_some()
+    }
+
+    pub fn report(&self, progress: f64, total: Option<f64>, message: Option<&str>) {
+        let Some((token, ctx)) = &self.target else {
+            return;
+        };
+        let mut params = json!({ "progressToken": token, "progress": progress });
+        if let Some(total) = total {
+            params["total"] = json!(total);
+        }
+        if let Some(message) = message {
+            params["message"] = json!(message);
+        }
+        ctx.notify(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": params
+        }));
+    }
+}
+
+// 標準ツール一式
+pub fn default_registry() -> ToolRegistry {
+    let mut registry = ToolRegistry::new();
+    registry.register(EchoTool);
+    registry.register(AddTool);
+    registry.register(mod_todo::AddTodoTool);
+    registry.register(mod_todo::ListTodosTool);
+    registry.register(mod_todo::CompleteTodoTool);
+    registry.register(mod_todo::DeleteTodoTool);
+    registry.register(mod_todo::SearchTodosTool);
+    registry.register(crate::mod_purchase::PurchaseTool);
+    registry.register(crate::mod_purchase::PurchaseListTool);
+    registry.register(crate::mod_purchase::PurchaseSummaryTool);
+    registry.register(crate::mod_purchase::PurchaseInsightsTool);
+    registry.register(crate::mod_purchase::PurchaseDeleteTool);
+    registry.register(crate::mod_purchase::PurchaseExportTool);
+    registry
+}
+
+// テキスト1件の tools/call 結果
+pub fn text_result(text: impl Into<String>) -> Value {
+    json!({
+        "content": [
+            {
+                "type": "text",
+                "text": text.into()
+            }
+        ]
+    })
+}
+
+// テキストと structuredContent を併せて返す tools/call 結果
+pub fn structured_result(text: impl Into<String>, structured: Value) -> Value {
+    let mut result = text_result(text);
+    result["structuredContent"] = structured;
+    result
+}
+
+// 検証済み引数の取り出し
+pub fn arg_str<'a>(arguments: &'a Value, key: &str) -> Result<&'a str, JsonRpcError> {
+    arguments[key]
+        .as_str()
+        .ok_or_else(|| JsonRpcError::invalid_params(format!("{} is required", key)))
+}
+
+pub fn arg_f64(arguments: &Value, key: &str) -> Result<f64, JsonRpcError> {
+    arguments[key]
+        .as_f64()
+        .ok_or_else(|| JsonRpcError::invalid_params(format!("{} is required", key)))
+}
+
+pub fn arg_i64(arguments: &Value, key: &str) -> Result<i64, JsonRpcError> {
+    let value = &arguments[key];
+    value
+        .as_i64()
+        .or_else(|| value.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+        .ok_or_else(|| JsonRpcError::invalid_params(format!("{} is required", key)))
+}
+
+// 省略可能な整数 (未指定なら default、5.0 のような整数値の小数も受け付ける)
+pub fn arg_i64_or(arguments: &Value, key: &str, default: i64) -> Result<i64, JsonRpcError> {
+    let value = &arguments[key];
+    if value.is_null() {
+        return Ok(default);
+    }
+    value
+        .as_i64()
+        .or_else(|| value.as_f64().filter(|f| f.fract() == 0.0).map(|f| f as i64))
+        .ok_or_else(|| JsonRpcError::invalid_params(format!("{} must be an integer", key)))
+}
+
+pub struct EchoTool;
+
+#[async_trait]
+impl Tool for EchoTool {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn description(&self) -> &'static str {
+        "Echo back the input message"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "message": {
+                    "type": "string",
+                    "description": "Message to echo"
+                }
+            },
+            "required": ["message"]
+        })
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, _state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let message = arg_str(arguments, "message")?;
+        Ok(text_result(format!("Echo: {}", message)))
+    }
+}
+
+pub struct AddTool;
+
+#[async_trait]
+impl Tool for AddTool {
+    fn name(&self) -> &'static str {
+        "add"
+    }
+
+    fn description(&self) -> &'static str {
+        "Add two numbers"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "a": { "type": "number" },
+                "b": { "type": "number" }
+            },
+            "required": ["a", "b"]
+        })
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, _state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        let a = arg_f64(arguments, "a")?;
+        let b = arg_f64(arguments, "b")?;
+        Ok(text_result(format!("Result: {}", a + b)))
+    }
+}
@@ -0,0 +1,529 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::mod_rpc::{JsonRpcError, SUPPORTED_PROTOCOL_VERSIONS};
+use crate::mod_tools::{Progress, Tool, ToolRegistry};
+use crate::AppState;
+
+// 上流 MCP サーバー (ゲートウェイ)
+//
+// 設定の [[upstreams]] / upstreams_file (TOML) に列挙した Streamable HTTP の MCP サーバーへ接続し、
+// 起動時に tools/list を取得して `<upstream>.<tool>` の名前でローカルのツールと並べて公開する。
+// tools/call は上流ごとの資格情報で転送し、上流のエラーは JsonRpcError に変換する。
+pub const UPSTREAM_ERROR: i32 = -32005;
+
+const SESSION_HEADER: &str = "mcp-session-id";
+const PROTOCOL_VERSION_HEADER: &str = "mcp-protocol-version";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// 起動時の initialize + tools/list の上限
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UpstreamConfig {
+    // ツール名の接頭辞 (英数字, _, -)
+    pub name: String,
+    pub url: String,
+    // Authorization: Bearer に使うトークン (token_env で環境変数から読むこともできる)
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub token_env: Option<String>,
+    // 追加の HTTP ヘッダー
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    // tools/call の実行時間の上限 (秒, 未指定時は TOOL_TIMEOUT_SECS)
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    // tools:read のキーにも公開する上流のツール名 (上流の readOnlyHint は信用しない)
+    #[serde(default)]
+    pub read_only_tools: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UpstreamsFile {
+    #[serde(default)]
+    upstreams: Vec<UpstreamConfig>,
+}
+
+// 上流の設定ファイル (TOML) を読み込む
+pub fn load(path: &str) -> anyhow::Result<Vec<UpstreamConfig>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("cannot read upstreams file {}: {}", path, e))?;
+    let file: UpstreamsFile =
+        toml::from_str(&text).map_err(|e| anyhow::anyhow!("invalid upstreams file {}: {}", path, e))?;
+    validate(&file.upstreams)?;
+    Ok(file.upstreams)
+}
+
+// 名前 (ツール名の接頭辞) と URL の検証
+pub fn validate(upstreams: &[UpstreamConfig]) -> anyhow::Result<()> {
+    let mut names = HashSet::new();
+    for upstream in upstreams {
+        if upstream.name.is_empty()
+            || !upstream
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            anyhow::bail!("upstream '{}': name must be [A-Za-z0-9_-]+", upstream.name);
+        }
+        if !names.insert(upstream.name.as_str()) {
+            anyhow::bail!("upstream '{}': duplicate name", upstream.name);
+        }
+        if !upstream.url.starts_with("http://") && !upstream.url.starts_with("https://") {
+            anyhow::bail!("upstream '{}': url must be http(s)://", upstream.name);
+        }
+    }
+    Ok(())
+}
+
+// 上流ごとのツールを登録する
+//
+// 設定の誤り (ヘッダー・環境変数) はエラー、接続できない上流は警告して読み飛ばす。
+pub async fn register_tools(registry: &mut ToolRegistry, configs: &[UpstreamConfig]) -> anyhow::Result<()> {
+    for config in configs {
+        let client = Arc::new(UpstreamClient::new(config)?);
+        let tools = match tokio::time::timeout(STARTUP_TIMEOUT, client.list_tools()).await {
+            Ok(Ok(tools)) => tools,
+            Ok(Err(e)) => {
+                tracing::warn!("upstream unavailable, skipped: upstream={}, {}", config.name, e.message);
+                continue;
+            }
+            Err(_) => {
+                tracing::warn!("upstream timed out, skipped: upstream={}", config.name);
+                continue;
+            }
+        };
+        let mut count = 0;
+        for definition in &tools {
+            match UpstreamTool::from_definition(&client, definition) {
+                Some(tool) => {
+                    registry.register(tool);
+                    count += 1;
+                }
+                None => tracing::warn!("upstream tool without name ignored: upstream={}", config.name),
+            }
+        }
+        tracing::info!("upstream tools registered: upstream={}, url={}, tools={}", config.name, config.url, count);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct Connection {
+    session_id: Option<String>,
+    protocol_version: String,
+}
+
+// 上流 1台分の Streamable HTTP クライアント
+//
+// initialize は最初のリクエスト時に行い、セッション切れ (404) では1回だけ張り直す。
+pub struct UpstreamClient {
+    name: String,
+    url: String,
+    http: reqwest::Client,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    read_only_tools: HashSet<String>,
+    connection: Mutex<Option<Connection>>,
+    next_id: AtomicI64,
+}
+
+impl UpstreamClient {
+    pub fn new(config: &UpstreamConfig) -> anyhow::Result<Self> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| anyhow::anyhow!("upstream '{}': header {}: {}", config.name, name, e))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| anyhow::anyhow!("upstream '{}': header {}: {}", config.name, name, e))?;
+            headers.insert(name, value);
+        }
+        let token = match (&config.token, &config.token_env) {
+            (Some(token), _) => Some(token.clone()),
+            (None, Some(var)) => Some(env::var(var).map_err(|_| {
+                anyhow::anyhow!("upstream '{}': environment variable {} is not set", config.name, var)
+            })?),
+            (None, None) => None,
+        };
+        if let Some(token) = token {
+            let mut value = HeaderValue::from_str(&format!("Bearer {}", token))
+                .map_err(|e| anyhow::anyhow!("upstream '{}': token: {}", config.name, e))?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+
+        Ok(UpstreamClient {
+            name: config.name.clone(),
+            url: config.url.clone(),
+            http: reqwest::Client::builder().connect_timeout(CONNECT_TIMEOUT).build()?,
+            headers,
+            timeout: config.timeout_secs.map(Duration::from_secs),
+            read_only_tools: config.read_only_tools.iter().cloned().collect(),
+            connection: Mutex::new(None),
+            next_id: AtomicI64::new(1),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // 通信エラー・想定外の応答
+    fn error(&self, detail: impl std::fmt::Display) -> JsonRpcError {
+        tracing::warn!("upstream error: upstream={}, {}", self.name, detail);
+        JsonRpcError {
+            code: UPSTREAM_ERROR,
+            message: format!("Upstream error: {}", self.name),
+            data: Some(json!({ "upstream": self.name, "detail": detail.to_string() })),
+        }
+    }
+
+    // 上流の JSON-RPC エラー (コードとメッセージはそのまま、data に上流名を付ける)
+    fn remote_error(&self, error: &Value) -> JsonRpcError {
+        let mut data = json!({ "upstream": self.name });
+        if let Some(detail) = error.get("data") {
+            data["data"] = detail.clone();
+        }
+        JsonRpcError {
+            code: error["code"]
+                .as_i64()
+                .and_then(|code| i32::try_from(code).ok())
+                .unwrap_or(UPSTREAM_ERROR),
+            message: error["message"].as_str().unwrap_or("Upstream error").to_string(),
+            data: Some(data),
+        }
+    }
+
+    async fn send(&self, connection: Option<&Connection>, message: &Value) -> Result<reqwest::Response, JsonRpcError> {
+        let mut request = self
+            .http
+            .post(&self.url)
+            .headers(self.headers.clone())
+            .header(ACCEPT, "application/json, text/event-stream")
+            .json(message);
+        if let Some(connection) = connection {
+            if let Some(session_id) = &connection.session_id {
+                request = request.header(SESSION_HEADER, session_id);
+            }
+            request = request.header(PROTOCOL_VERSION_HEADER, &connection.protocol_version);
+        }
+        request.send().await.map_err(|e| self.error(e))
+    }
+
+    fn check_status(&self, response: &reqwest::Response) -> Result<(), JsonRpcError> {
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Err(self.error(format!("credentials rejected (HTTP {})", response.status().as_u16())))
+            }
+            status => Err(self.error(format!("HTTP {}", status.as_u16()))),
+        }
+    }
+
+    // 応答 (application/json または text/event-stream) から id に対応するメッセージを取り出す
+    //
+    // SSE の途中で届く notifications/progress は呼び出し元へ中継する。
+    async fn read_reply(&self, response: reqwest::Response, id: i64, progress: &Progress) -> Result<Value, JsonRpcError> {
+        let is_sse = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+        if !is_sse {
+            return response.json().await.map_err(|e| self.error(e));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut buffer = Vec::new();
+        let mut data = String::new();
+        while let Some(chunk) = stream.next().await {
+            buffer.extend_from_slice(&chunk.map_err(|e| self.error(e))?);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end_matches(['\r', '\n']);
+                if let Some(value) = line.strip_prefix("data:") {
+                    if !data.is_empty() {
+                        data.push('\n');
+                    }
+                    data.push_str(value.strip_prefix(' ').unwrap_or(value));
+                } else if line.is_empty() && !data.is_empty() {
+                    let message: Value = serde_json::from_str(&data).map_err(|e| self.error(e))?;
+                    data.clear();
+                    if message["id"] == json!(id) && (message.get("result").is_some() || message.get("error").is_some()) {
+                        return Ok(message);
+                    }
+                    self.relay(&message, progress);
+                }
+            }
+        }
+        Err(self.error("stream closed before response"))
+    }
+
+    // 上流からのメッセージ (進捗のみ中継し、それ以外は読み捨てる)
+    fn relay(&self, message: &Value, progress: &Progress) {
+        match message["method"].as_str() {
+            Some("notifications/progress") => {
+                let params = &message["params"];
+                progress.report(
+                    params["progress"].as_f64().unwrap_or_default(),
+                    params["total"].as_f64(),
+                    params["message"].as_str(),
+                );
+            }
+            method => tracing::debug!("upstream message ignored: upstream={}, method={:?}", self.name, method),
+        }
+    }
+
+    async fn initialize(&self) -> Result<Connection, JsonRpcError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let response = self
+            .send(
+                None,
+                &json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": "initialize",
+                    "params": {
+                        "protocolVersion": SUPPORTED_PROTOCOL_VERSIONS[0],
+                        "capabilities": {},
+                        "clientInfo": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") }
+                    }
+                }),
+            )
+            .await?;
+        self.check_status(&response)?;
+        let session_id = response
+            .headers()
+            .get(SESSION_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let reply = self.read_reply(response, id, &Progress::none()).await?;
+        if let Some(error) = reply.get("error") {
+            return Err(self.remote_error(error));
+        }
+
+        let protocol_version = reply["result"]["protocolVersion"].as_str().unwrap_or_default();
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&protocol_version) {
+            return Err(self.error(format!("unsupported protocol version: {:?}", protocol_version)));
+        }
+        let connection = Connection {
+            session_id,
+            protocol_version: protocol_version.to_string(),
+        };
+
+        let response = self
+            .send(Some(&connection), &json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }))
+            .await?;
+        self.check_status(&response)?;
+        tracing::info!(
+            "upstream connected: upstream={}, protocol={}, session={}",
+            self.name,
+            connection.protocol_version,
+            connection.session_id.as_deref().unwrap_or("-")
+        );
+        Ok(connection)
+    }
+
+    async fn connect(&self) -> Result<Connection, JsonRpcError> {
+        let mut connection = self.connection.lock().await;
+        if let Some(connection) = connection.as_ref() {
+            return Ok(connection.clone());
+        }
+        let established = self.initialize().await?;
+        *connection = Some(established.clone());
+        Ok(established)
+    }
+
+    // セッション切れの接続を破棄 (他のリクエストが張り直し済みなら何もしない)
+    async fn reset(&self, stale: &Connection) {
+        let mut connection = self.connection.lock().await;
+        if connection.as_ref().is_some_and(|c| c.session_id == stale.session_id) {
+            *connection = None;
+        }
+    }
+
+    // 1往復 (セッション切れなら None)
+    async fn exchange(
+        &self,
+        connection: &Connection,
+        message: &Value,
+        id: i64,
+        progress: &Progress,
+    ) -> Result<Option<Value>, JsonRpcError> {
+        let response = self.send(Some(connection), message).await?;
+        if response.status() == StatusCode::NOT_FOUND && connection.session_id.is_some() {
+            return Ok(None);
+        }
+        self.check_status(&response)?;
+        self.read_reply(response, id, progress).await.map(Some)
+    }
+
+    async fn request(self: &Arc<Self>, method: &str, mut params: Value, progress: &Progress) -> Result<Value, JsonRpcError> {
+        let mut retried = false;
+        loop {
+            let connection = self.connect().await?;
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            if progress.is_enabled() {
+                params["_meta"] = json!({ "progressToken": id });
+            }
+            let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+
+            let mut guard = CancelGuard {
+                client: Some(self.clone()),
+                connection: connection.clone(),
+                id,
+            };
+            let reply = self.exchange(&connection, &message, id, progress).await;
+            guard.client = None;
+
+            match reply? {
+                Some(reply) => {
+                    if let Some(error) = reply.get("error") {
+                        return Err(self.remote_error(error));
+                    }
+                    return Ok(reply["result"].clone());
+                }
+                None if !retried => {
+                    tracing::info!("upstream session expired, reconnecting: upstream={}", self.name);
+                    self.reset(&connection).await;
+                    retried = true;
+                }
+                None => return Err(self.error("session not found")),
+            }
+        }
+    }
+
+    pub async fn list_tools(self: &Arc<Self>) -> Result<Vec<Value>, JsonRpcError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let result = self.request("tools/list", params, &Progress::none()).await?;
+            if let Some(items) = result["tools"].as_array() {
+                tools.extend(items.iter().cloned());
+            }
+            match result["nextCursor"].as_str() {
+                Some(next) if !next.is_empty() => cursor = Some(next.to_string()),
+                _ => return Ok(tools),
+            }
+        }
+    }
+
+    pub async fn call_tool(self: &Arc<Self>, name: &str, arguments: &Value, progress: &Progress) -> Result<Value, JsonRpcError> {
+        self.request("tools/call", json!({ "name": name, "arguments": arguments }), progress)
+            .await
+    }
+}
+
+// 応答を待たずに破棄された (キャンセル・時間切れ) リクエストは上流にも notifications/cancelled を送る
+struct CancelGuard {
+    client: Option<Arc<UpstreamClient>>,
+    connection: Connection,
+    id: i64,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        let Some(client) = self.client.take() else {
+            return;
+        };
+        let connection = self.connection.clone();
+        let id = self.id;
+        tokio::spawn(async move {
+            let message = json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/cancelled",
+                "params": { "requestId": id, "reason": "cancelled by gateway" }
+            });
+            if let Err(e) = client.send(Some(&connection), &message).await {
+                tracing::debug!("upstream cancel not delivered: upstream={}, {}", client.name, e.message);
+            }
+        });
+    }
+}
+
+// 上流のツール (`<upstream>.<tool>`)
+pub struct UpstreamTool {
+    name: String,
+    remote_name: String,
+    description: String,
+    input_schema: Value,
+    output_schema: Option<Value>,
+    read_only: bool,
+    client: Arc<UpstreamClient>,
+}
+
+impl UpstreamTool {
+    pub fn from_definition(client: &Arc<UpstreamClient>, definition: &Value) -> Option<Self> {
+        let remote_name = definition["name"].as_str().filter(|n| !n.is_empty())?;
+        Some(UpstreamTool {
+            name: format!("{}.{}", client.name, remote_name),
+            remote_name: remote_name.to_string(),
+            description: definition["description"].as_str().unwrap_or_default().to_string(),
+            input_schema: match &definition["inputSchema"] {
+                Value::Object(_) => definition["inputSchema"].clone(),
+                _ => json!({ "type": "object" }),
+            },
+            output_schema: definition.get("outputSchema").filter(|s| s.is_object()).cloned(),
+            // 読み取り専用かどうかはローカルの設定だけで決める
+            read_only: client.read_only_tools.contains(remote_name),
+            client: client.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Tool for UpstreamTool {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn input_schema(&self) -> Value {
+        self.input_schema.clone()
+    }
+
+    fn output_schema(&self) -> Option<Value> {
+        self.output_schema.clone()
+    }
+
+    fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.client.timeout
+    }
+
+    async fn call(&self, state: &AppState, arguments: &Value) -> Result<Value, JsonRpcError> {
+        self.call_with_progress(state, arguments, &Progress::none()).await
+    }
+
+    async fn call_with_progress(
+        &self,
+        _state: &AppState,
+        arguments: &Value,
+        progress: &Progress,
+    ) -> Result<Value, JsonRpcError> {
+        self.client.call_tool(&self.remote_name, arguments, progress).await
+    }
+}
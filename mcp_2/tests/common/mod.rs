@@ -0,0 +1,380 @@
+// 統合テスト用のインプロセス MCP クライアント
+//
+// axum の Router を tower::ServiceExt::oneshot で直接呼び出す (ソケットは使わない)。
+// データベースはテストごとの一時 libsql ファイル (Turso の代わり)。
+#![allow(dead_code)]
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, HeaderMap, Method, Request, Response, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tower::ServiceExt;
+
+use rust_remoto_mcp_2::mod_audit::AuditLog;
+use rust_remoto_mcp_2::mod_auth::ApiKeyStore;
+use rust_remoto_mcp_2::mod_db::{Backend, Db};
+use rust_remoto_mcp_2::mod_metrics::Metrics;
+use rust_remoto_mcp_2::mod_oauth::{OAuthConfig, OAuthSettings};
+use rust_remoto_mcp_2::mod_purchase::PurchaseRepository;
+use rust_remoto_mcp_2::mod_rpc::JsonRpcError;
+use rust_remoto_mcp_2::mod_ratelimit::{RateLimitConfig, RateLimiter};
+use rust_remoto_mcp_2::mod_session::SessionStore;
+use rust_remoto_mcp_2::mod_todo::TodoStore;
+use rust_remoto_mcp_2::mod_tools::{Tool, ToolRegistry};
+use rust_remoto_mcp_2::{mod_http, mod_prompts, mod_tools, AppState, DEFAULT_SESSION_IDLE_TIMEOUT, DEFAULT_TOOL_TIMEOUT};
+
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+// TestOptions::oauth_jwks の認可サーバー (iss)
+pub const OAUTH_ISSUER: &str = "https://auth.example";
+
+pub struct TestServer {
+    pub state: Arc<AppState>,
+    router: Router,
+    dir: PathBuf,
+}
+
+// テストサーバーの設定 (既定は認証なし・制限なし・既定のツール)
+#[derive(Default)]
+pub struct TestOptions {
+    // API キーファイル (TOML)
+    pub api_keys: Option<String>,
+    pub tools: Option<ToolRegistry>,
+    pub purchase_confirm_above: Option<i64>,
+    pub rate_limit: RateLimitConfig,
+    pub tool_timeout: Option<Duration>,
+    pub tool_timeouts: HashMap<String, Duration>,
+    pub session_idle_timeout: Option<Duration>,
+    pub max_sessions_per_key: Option<usize>,
+    // OAuth の JWKS (JSON, 認可サーバーは OAUTH_ISSUER)
+    pub oauth_jwks: Option<String>,
+}
+
+impl TestServer {
+    // 認証なし
+    pub async fn new() -> Self {
+        Self::with_options(TestOptions::default()).await
+    }
+
+    // 認証なし、ツールを差し替える
+    pub async fn with_tools(tools: ToolRegistry) -> Self {
+        Self::with_options(TestOptions {
+            tools: Some(tools),
+            ..Default::default()
+        })
+        .await
+    }
+
+    // 認証なし、amount を超える purchase をユーザーに確認する
+    pub async fn with_purchase_confirmation(amount: i64) -> Self {
+        Self::with_options(TestOptions {
+            purchase_confirm_above: Some(amount),
+            ..Default::default()
+        })
+        .await
+    }
+
+    // API キーファイル (TOML) で認証
+    pub async fn with_api_keys(keys_toml: &str) -> Self {
+        Self::with_options(TestOptions {
+            api_keys: Some(keys_toml.to_string()),
+            ..Default::default()
+        })
+        .await
+    }
+
+    pub async fn with_options(options: TestOptions) -> Self {
+        let dir = temp_dir();
+        let auth = match &options.api_keys {
+            Some(keys_toml) => {
+                let path = dir.join("keys.toml");
+                std::fs::write(&path, keys_toml).unwrap();
+                ApiKeyStore::load(Some(path.to_str().unwrap()), None).unwrap()
+            }
+            None => ApiKeyStore::default(),
+        };
+        let oauth = options.oauth_jwks.as_ref().map(|jwks| {
+            let path = dir.join("jwks.json");
+            std::fs::write(&path, jwks).unwrap();
+            OAuthConfig::from_settings(&OAuthSettings {
+                jwks_file: path.to_str().unwrap().to_string(),
+                authorization_servers: vec![OAUTH_ISSUER.to_string()],
+                ..Default::default()
+            })
+            .unwrap()
+        });
+        let tools = options.tools.unwrap_or_else(mod_tools::default_registry);
+
+        let db_path = dir.join("test.db").to_str().unwrap().to_string();
+        let db = Db::open_local(&db_path).await.unwrap();
+        let todos = TodoStore::open(db.clone()).await.unwrap();
+        let audit = AuditLog::open(db).await.unwrap();
+        let purchases = PurchaseRepository::open(&Backend::Local { path: db_path })
+            .await
+            .unwrap();
+
+        let state = Arc::new(AppState {
+            server_name: "MCP Server Test".to_string(),
+            version: "0.0.0".to_string(),
+            todos,
+            purchases,
+            tools,
+            prompts: mod_prompts::default_registry(),
+            sessions: SessionStore::new(
+                options.session_idle_timeout.unwrap_or(DEFAULT_SESSION_IDLE_TIMEOUT),
+                options.max_sessions_per_key,
+            ),
+            auth,
+            oauth,
+            audit,
+            metrics: Metrics::new(),
+            limiter: RateLimiter::new(options.rate_limit),
+            tool_timeout: options.tool_timeout.unwrap_or(DEFAULT_TOOL_TIMEOUT),
+            tool_timeouts: options.tool_timeouts,
+            purchase_confirm_above: options.purchase_confirm_above,
+        });
+        let router = mod_http::router(state.clone());
+        TestServer { state, router, dir }
+    }
+
+    // ローカルのソケットで待ち受ける (上流サーバー役, 実際の HTTP が必要なテスト用)
+    pub async fn serve(&self) -> SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = self.router.clone();
+        tokio::spawn(async move {
+            axum::serve(listener, router).await.unwrap();
+        });
+        addr
+    }
+
+    // 本文を読まずに応答を返す (SSE ストリームのテスト用)
+    pub async fn send_streaming(&self, request: Request<Body>) -> Response<Body> {
+        self.router.clone().oneshot(request).await.unwrap()
+    }
+
+    pub async fn send(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        TestResponse {
+            status,
+            headers,
+            body: String::from_utf8(bytes.to_vec()).unwrap(),
+        }
+    }
+
+    // POST /mcp (本文はそのまま送る)
+    pub async fn post_raw(&self, headers: &[(&str, &str)], body: &str) -> TestResponse {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri("/mcp")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        self.send(request.body(Body::from(body.to_string())).unwrap()).await
+    }
+
+    pub async fn get(&self, uri: &str, headers: &[(&str, &str)]) -> TestResponse {
+        let mut request = Request::builder().method(Method::GET).uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        self.send(request.body(Body::empty()).unwrap()).await
+    }
+
+    pub fn client(&self) -> McpClient<'_> {
+        McpClient {
+            server: self,
+            token: None,
+            session: None,
+            next_id: 1,
+        }
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("mcp_2-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl TestResponse {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or_else(|e| panic!("invalid JSON ({}): {}", e, self.body))
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+}
+
+pub struct McpClient<'a> {
+    server: &'a TestServer,
+    token: Option<String>,
+    pub session: Option<String>,
+    next_id: i64,
+}
+
+impl McpClient<'_> {
+    pub fn with_token(mut self, token: &str) -> Self {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    fn headers(&self) -> Vec<(&str, String)> {
+        let mut headers = Vec::new();
+        if let Some(token) = &self.token {
+            headers.push(("authorization", format!("Bearer {}", token)));
+        }
+        if let Some(session) = &self.session {
+            headers.push(("mcp-session-id", session.clone()));
+        }
+        headers
+    }
+
+    pub async fn post(&self, message: &Value) -> TestResponse {
+        let headers = self.headers();
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(n, v)| (*n, v.as_str())).collect();
+        self.server.post_raw(&headers, &message.to_string()).await
+    }
+
+    // text/event-stream を受け付けて POST し、本文を読まずに応答を返す
+    pub async fn post_streaming(&self, message: &Value) -> Response<Body> {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri("/mcp")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json, text/event-stream");
+        for (name, value) in self.headers() {
+            request = request.header(name, value);
+        }
+        self.server
+            .send_streaming(request.body(Body::from(message.to_string())).unwrap())
+            .await
+    }
+
+    // initialize → notifications/initialized (セッションを保持する)
+    pub async fn initialize(&mut self) -> Value {
+        self.initialize_with(json!({})).await
+    }
+
+    // capabilities (elicitation / sampling など) を宣言して initialize
+    pub async fn initialize_with(&mut self, capabilities: Value) -> Value {
+        let response = self
+            .post(&json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "method": "initialize",
+                "params": {
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": capabilities,
+                    "clientInfo": { "name": "test-client", "version": "1.0.0" }
+                }
+            }))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        self.session = response.header("mcp-session-id").map(str::to_string);
+        assert!(self.session.is_some(), "initialize must return Mcp-Session-Id");
+
+        let initialized = self.notify("notifications/initialized", json!({})).await;
+        assert_eq!(initialized.status, StatusCode::ACCEPTED);
+        response.json()
+    }
+
+    // リクエストを送り JSON-RPC 応答を返す
+    pub async fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        let response = self
+            .post(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let body = response.json();
+        assert_eq!(body["id"], json!(id));
+        body
+    }
+
+    pub async fn notify(&self, method: &str, params: Value) -> TestResponse {
+        self.post(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+            .await
+    }
+
+    // tools/call の result (エラーなら panic)
+    pub async fn call_tool(&mut self, name: &str, arguments: Value) -> Value {
+        let response = self
+            .request("tools/call", json!({ "name": name, "arguments": arguments }))
+            .await;
+        assert!(response.get("error").is_none(), "{} failed: {}", name, response);
+        response["result"].clone()
+    }
+
+    // tools/call の error (成功なら panic)
+    pub async fn call_tool_error(&mut self, name: &str, arguments: Value) -> Value {
+        let response = self
+            .request("tools/call", json!({ "name": name, "arguments": arguments }))
+            .await;
+        assert!(response.get("result").is_none(), "{} should fail: {}", name, response);
+        response["error"].clone()
+    }
+}
+
+// tools/call 結果の先頭テキスト
+pub fn text(result: &Value) -> &str {
+    result["content"][0]["text"].as_str().unwrap_or_default()
+}
+
+// release が通知されるまで終わらないツール (同時実行数・キャンセル・時間切れのテスト用)
+pub struct WaitTool {
+    pub release: Arc<Notify>,
+}
+
+#[async_trait::async_trait]
+impl Tool for WaitTool {
+    fn name(&self) -> &str {
+        "wait"
+    }
+
+    fn description(&self) -> &str {
+        "Waits until the test releases it"
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object" })
+    }
+
+    async fn call(&self, _state: &AppState, _arguments: &Value) -> Result<Value, JsonRpcError> {
+        self.release.notified().await;
+        Ok(json!({ "content": [{ "type": "text", "text": "released" }] }))
+    }
+}
+
+// 既定のツール + wait
+pub fn registry_with_wait() -> (ToolRegistry, Arc<Notify>) {
+    let release = Arc::new(Notify::new());
+    let mut tools = mod_tools::default_registry();
+    tools.register(WaitTool {
+        release: release.clone(),
+    });
+    (tools, release)
+}
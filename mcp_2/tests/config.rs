@@ -0,0 +1,131 @@
+// 設定ファイル・環境変数・引数の優先順位と検証
+use clap::Parser;
+use rust_remoto_mcp_2::mod_config::{Cli, Config, Transport};
+use rust_remoto_mcp_2::mod_db::Backend;
+use std::collections::HashMap;
+use std::path::Path;
+
+fn parse(toml: &str) -> Config {
+    toml::from_str(toml).unwrap()
+}
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    move |name| vars.get(name).cloned()
+}
+
+#[test]
+fn example_config_is_valid() {
+    let config = Config::from_file(Path::new(env!("CARGO_MANIFEST_DIR")).join("mcp.example.toml").as_path()).unwrap();
+    config.validate().unwrap();
+    assert_eq!(config.server.bind.port(), 3000);
+    assert_eq!(config.database.purchase.backend.as_deref(), Some("local"));
+}
+
+#[test]
+fn defaults_match_previous_hard_coded_values() {
+    let config = Config::default();
+    assert_eq!(config.server.bind.to_string(), "0.0.0.0:3000");
+    assert_eq!(config.server.name, "MCP Server Example");
+    assert_eq!(config.server.transport, Transport::Http);
+    assert_eq!(config.database.path, "data.db");
+    assert_eq!(config.tool_timeout().as_secs(), 30);
+    assert_eq!(config.session_idle_timeout().as_secs(), 1800);
+    assert_eq!(config.server.max_sessions_per_key, None);
+    assert_eq!(
+        config.purchase_backend().unwrap(),
+        Backend::Local {
+            path: "data.db".to_string()
+        }
+    );
+}
+
+#[test]
+fn env_overrides_file_and_cli_overrides_env() {
+    let mut config = parse(
+        r#"
+[server]
+bind = "127.0.0.1:4000"
+log_level = "warn"
+
+[database]
+path = "file.db"
+"#,
+    );
+    config
+        .apply_env(env(&[
+            ("MCP_BIND", "127.0.0.1:5000"),
+            ("DB_PATH", "env.db"),
+            ("API_KEY", "secret"),
+            ("RATE_LIMIT_TOOLS", "purchase=10/min"),
+            ("OAUTH_JWKS_FILE", "jwks.json"),
+            ("OAUTH_ISSUER", "https://a.example, https://b.example"),
+            ("PURCHASE_CONFIRM_ABOVE", "10000"),
+            ("SESSION_IDLE_TIMEOUT_SECS", "600"),
+            ("MAX_SESSIONS_PER_KEY", "5"),
+            ("TOOL_TIMEOUTS", "purchase=300, purchase_insights=120"),
+        ]))
+        .unwrap();
+    assert_eq!(config.server.bind.port(), 5000);
+    assert_eq!(config.server.log_level, "warn");
+    assert_eq!(config.database.path, "env.db");
+    assert_eq!(config.auth.api_key.as_deref(), Some("secret"));
+    assert_eq!(config.rate_limit.per_tool["purchase"].count, 10);
+    assert_eq!(config.tools.purchase_confirm_above, Some(10000));
+    assert_eq!(config.session_idle_timeout().as_secs(), 600);
+    assert_eq!(config.server.max_sessions_per_key, Some(5));
+    assert_eq!(config.tool_timeouts()["purchase_insights"].as_secs(), 120);
+    let oauth = config.auth.oauth.as_ref().unwrap();
+    assert_eq!(oauth.jwks_file, "jwks.json");
+    assert_eq!(oauth.issuers, ["https://a.example", "https://b.example"]);
+
+    let cli = Cli::parse_from(["mcp", "--bind", "127.0.0.1:6000", "--stdio", "--tools", "echo,purchase*"]);
+    config.apply_cli(&cli);
+    assert_eq!(config.server.bind.port(), 6000);
+    assert_eq!(config.server.transport, Transport::Stdio);
+    assert!(config.tools.allows("purchase_list"));
+    assert!(!config.tools.allows("add"));
+}
+
+#[test]
+fn tool_patterns() {
+    let config = parse(
+        r#"
+[tools]
+enabled = ["echo", "purchase*", "docs.*"]
+disabled = ["purchase_delete"]
+"#,
+    );
+    assert!(config.tools.allows("echo"));
+    assert!(config.tools.allows("purchase_export"));
+    assert!(config.tools.allows("docs.search"));
+    assert!(!config.tools.allows("purchase_delete"));
+    assert!(!config.tools.allows("add_todo"));
+    assert_eq!(config.tools.unmatched(&["echo", "purchase"]), ["docs.*", "purchase_delete"]);
+}
+
+#[test]
+fn invalid_settings_are_rejected() {
+    // 未知のキー
+    assert!(toml::from_str::<Config>("[server]\nport = 3000\n").is_err());
+    assert!(toml::from_str::<Config>("[server]\nbind = \"localhost\"\n").is_err());
+    assert!(toml::from_str::<Config>("[rate_limit]\nper_key = \"10/day\"\n").is_err());
+
+    let config = parse("[server]\nlog_level = \"info,=\"\n");
+    assert!(config.validate().is_err());
+
+    let config = parse("[server]\nmax_sessions_per_key = 0\n");
+    assert!(config.validate().is_err());
+
+    let config = parse("[database.purchase]\nbackend = \"remote\"\n");
+    assert!(config.validate().is_err());
+
+    let config = parse("[[upstreams]]\nname = \"a.b\"\nurl = \"http://localhost/mcp\"\n");
+    assert!(config.validate().is_err());
+
+    let mut config = Config::default();
+    assert!(config.apply_env(env(&[("MCP_TLS_CERT", "cert.pem")])).is_err());
+    assert!(config.apply_env(env(&[("MCP_TRANSPORT", "websocket")])).is_err());
+    assert!(config.apply_env(env(&[("TOOL_TIMEOUT_SECS", "soon")])).is_err());
+    assert!(config.apply_env(env(&[("PURCHASE_CONFIRM_ABOVE", "lots")])).is_err());
+}
@@ -0,0 +1,34 @@
+// マイグレーション (_migrations への記録とロールバック)
+use rust_remoto_mcp_2::mod_db::Db;
+
+async fn temp_db() -> Db {
+    let dir = std::env::temp_dir().join(format!("mcp_2-test-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    Db::open_local(dir.join("test.db").to_str().unwrap()).await.unwrap()
+}
+
+async fn count(db: &Db, sql: &str) -> i64 {
+    let mut rows = db.conn().query(sql, ()).await.unwrap();
+    rows.next().await.unwrap().unwrap().get(0).unwrap()
+}
+
+#[tokio::test]
+async fn failed_migration_leaves_nothing_behind() {
+    let db = temp_db().await;
+    db.migrate(&[("001_table", "CREATE TABLE t (a INTEGER)")]).await.unwrap();
+
+    // 2つ目の ALTER TABLE が失敗する
+    let broken = "ALTER TABLE t ADD COLUMN b TEXT; ALTER TABLE t ADD COLUMN b TEXT;";
+    assert!(db.migrate(&[("001_table", ""), ("002_columns", broken)]).await.is_err());
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM pragma_table_info('t') WHERE name = 'b'").await, 0);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM _migrations WHERE name = '002_columns'").await, 0);
+
+    // 直したマイグレーションはそのまま適用できる
+    let fixed = "ALTER TABLE t ADD COLUMN b TEXT; ALTER TABLE t ADD COLUMN c TEXT;";
+    db.migrate(&[("001_table", ""), ("002_columns", fixed)]).await.unwrap();
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM pragma_table_info('t')").await, 3);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM _migrations").await, 2);
+
+    // 適用済みは再実行しない
+    db.migrate(&[("001_table", ""), ("002_columns", broken)]).await.unwrap();
+}
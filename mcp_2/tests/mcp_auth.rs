@@ -0,0 +1,277 @@
+// 認証とスコープ
+mod common;
+
+use axum::http::StatusCode;
+use common::{temp_dir, TestOptions, TestServer};
+use rust_remoto_mcp_2::mod_auth::{hash_key, ApiKeyStore};
+use serde_json::json;
+
+const ADMIN_KEY: &str = "admin-secret";
+const VIEWER_KEY: &str = "viewer-secret";
+
+fn keys() -> String {
+    format!(
+        r#"
+[[keys]]
+name = "admin"
+hash = "{}"
+scopes = ["*"]
+
+[[keys]]
+name = "viewer"
+hash = "{}"
+scopes = ["tools:read"]
+"#,
+        hash_key(ADMIN_KEY),
+        hash_key(VIEWER_KEY)
+    )
+}
+
+async fn server() -> TestServer {
+    TestServer::with_api_keys(&keys()).await
+}
+
+fn ping() -> &'static str {
+    r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#
+}
+
+// セッションなしで送れるのは initialize のみ
+fn initialize() -> &'static str {
+    r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18","capabilities":{}}}"#
+}
+
+#[tokio::test]
+async fn missing_token_is_unauthorized() {
+    let server = server().await;
+    let response = server.post_raw(&[], ping()).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.header("www-authenticate"), Some("Bearer"));
+    assert_eq!(response.json()["error"]["code"], -32001);
+
+    // "Bearer " だけのヘッダーもトークン無しとして扱う
+    let response = server.post_raw(&[("authorization", "Bearer ")], ping()).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn wrong_token_is_unauthorized() {
+    let server = server().await;
+    let response = server
+        .post_raw(&[("authorization", "Bearer not-a-key")], ping())
+        .await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json()["error"]["code"], -32001);
+    assert!(!response.body.contains("not-a-key"));
+}
+
+#[tokio::test]
+async fn valid_token_is_accepted() {
+    let server = server().await;
+    let response = server
+        .post_raw(&[("authorization", &format!("Bearer {}", ADMIN_KEY))], initialize())
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    // Bearer を付けない生の値も受け付ける
+    let response = server.post_raw(&[("authorization", ADMIN_KEY)], initialize()).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    let mut client = server.client().with_token(ADMIN_KEY);
+    client.initialize().await;
+    let result = client.call_tool("add", json!({ "a": 2, "b": 3 })).await;
+    assert_eq!(result["content"][0]["text"], "Result: 5");
+}
+
+#[tokio::test]
+async fn read_only_scope_limits_tools() {
+    let server = server().await;
+    let mut client = server.client().with_token(VIEWER_KEY);
+    client.initialize().await;
+
+    let response = client.request("tools/list", json!({})).await;
+    let names: Vec<&str> = response["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"list_todos"));
+    assert!(names.contains(&"purchase_list"));
+    assert!(!names.contains(&"purchase"));
+    assert!(!names.contains(&"add_todo"));
+
+    let error = client
+        .call_tool_error("purchase", json!({ "name": "coffee", "price": 450 }))
+        .await;
+    assert_eq!(error["code"], -32003);
+    assert_eq!(error["data"]["tool"], "purchase");
+
+    client.call_tool("list_todos", json!({})).await;
+}
+
+#[tokio::test]
+async fn admin_routes_require_scope() {
+    let server = server().await;
+
+    let viewer = format!("Bearer {}", VIEWER_KEY);
+    let response = server.get("/admin/audit", &[("authorization", &viewer)]).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    let response = server.get("/metrics", &[("authorization", &viewer)]).await;
+    assert_eq!(response.status, StatusCode::FORBIDDEN);
+    let response = server.get("/admin/audit", &[]).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+    let admin = format!("Bearer {}", ADMIN_KEY);
+    let response = server.get("/admin/audit?limit=10", &[("authorization", &admin)]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["limit"], 10);
+    assert!(response.json()["items"].is_array());
+
+    let response = server.get("/metrics", &[("authorization", &admin)]).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.body.contains("# TYPE mcp_requests_total counter"));
+}
+
+#[tokio::test]
+async fn resources_and_prompts_require_scope() {
+    let server = server().await;
+    let mut viewer = server.client().with_token(VIEWER_KEY);
+    viewer.initialize().await;
+
+    // tools:read のみのキーは一覧が空になり、読み取りは拒否される
+    let response = viewer.request("resources/list", json!({})).await;
+    assert_eq!(response["result"]["resources"], json!([]));
+    let response = viewer.request("prompts/list", json!({})).await;
+    assert_eq!(response["result"]["prompts"], json!([]));
+
+    for (method, params, scope) in [
+        ("resources/read", json!({ "uri": "todo://list" }), "resources:read"),
+        ("resources/subscribe", json!({ "uri": "todo://list" }), "resources:read"),
+        ("prompts/get", json!({ "name": "plan_todos", "arguments": {} }), "prompts:read"),
+        (
+            "completion/complete",
+            json!({ "ref": { "type": "ref/resource", "uri": "todo://{id}" }, "argument": { "name": "id", "value": "" } }),
+            "resources:read",
+        ),
+        (
+            "completion/complete",
+            json!({ "ref": { "type": "ref/prompt", "name": "plan_todos" }, "argument": { "name": "hours", "value": "" } }),
+            "prompts:read",
+        ),
+    ] {
+        let response = viewer.request(method, params).await;
+        assert_eq!(response["error"]["code"], -32003, "{}: {}", method, response);
+        assert_eq!(response["error"]["data"]["scope"], scope);
+    }
+
+    let mut admin = server.client().with_token(ADMIN_KEY);
+    admin.initialize().await;
+    let response = admin.request("resources/read", json!({ "uri": "todo://list" })).await;
+    assert!(response["result"]["contents"].is_array(), "{}", response);
+    let response = admin.request("resources/list", json!({})).await;
+    assert!(!response["result"]["resources"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn sessions_are_bound_to_their_key() {
+    let server = server().await;
+    let mut admin = server.client().with_token(ADMIN_KEY);
+    admin.initialize().await;
+    let session = admin.session.clone().unwrap();
+
+    // 別のキーからは存在しないセッションとして扱う
+    let viewer = format!("Bearer {}", VIEWER_KEY);
+    let response = server
+        .post_raw(&[("authorization", &viewer), ("mcp-session-id", &session)], ping())
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = server
+        .post_raw(
+            &[("authorization", &viewer), ("mcp-session-id", &session)],
+            r#"{"jsonrpc":"2.0","id":1,"result":{"action":"accept","content":{}}}"#,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let response = server
+        .get(
+            "/mcp",
+            &[("authorization", &viewer), ("mcp-session-id", &session), ("accept", "text/event-stream")],
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+
+    let request = axum::http::Request::builder()
+        .method("DELETE")
+        .uri("/mcp")
+        .header("authorization", &viewer)
+        .header("mcp-session-id", &session)
+        .body(axum::body::Body::empty())
+        .unwrap();
+    assert_eq!(server.send(request).await.status, StatusCode::NOT_FOUND);
+
+    // 作成したキーからは引き続き使える
+    let response = admin.request("ping", json!({})).await;
+    assert!(response["result"].is_object(), "{}", response);
+}
+
+#[tokio::test]
+async fn sessions_are_limited_per_key() {
+    let server = TestServer::with_options(TestOptions {
+        api_keys: Some(keys()),
+        max_sessions_per_key: Some(2),
+        ..Default::default()
+    })
+    .await;
+    let mut first = server.client().with_token(ADMIN_KEY);
+    first.initialize().await;
+    server.client().with_token(ADMIN_KEY).initialize().await;
+
+    let admin = format!("Bearer {}", ADMIN_KEY);
+    let response = server.post_raw(&[("authorization", &admin)], initialize()).await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.header("mcp-session-id").is_none());
+    let body = response.json();
+    assert_eq!(body["id"], 1);
+    assert_eq!(body["error"]["code"], -32000);
+    assert_eq!(body["error"]["data"]["maxSessions"], 2);
+
+    // 上限はキーごと
+    server.client().with_token(VIEWER_KEY).initialize().await;
+
+    // 閉じたセッションは数えない
+    let request = axum::http::Request::builder()
+        .method("DELETE")
+        .uri("/mcp")
+        .header("authorization", &admin)
+        .header("mcp-session-id", first.session.as_deref().unwrap())
+        .body(axum::body::Body::empty())
+        .unwrap();
+    assert_eq!(server.send(request).await.status, StatusCode::NO_CONTENT);
+    server.client().with_token(ADMIN_KEY).initialize().await;
+}
+
+#[test]
+fn key_names_must_be_unique_and_not_reserved() {
+    let load = |keys_toml: &str, legacy_key: Option<&str>| {
+        let path = temp_dir().join("keys.toml");
+        std::fs::write(&path, keys_toml).unwrap();
+        ApiKeyStore::load(Some(path.to_str().unwrap()), legacy_key)
+    };
+    let key = |name: &str, secret: &str| {
+        format!("[[keys]]\nname = \"{}\"\nhash = \"{}\"\nscopes = [\"*\"]\n", name, hash_key(secret))
+    };
+
+    let error = load(&(key("admin", ADMIN_KEY) + &key("admin", VIEWER_KEY)), None).unwrap_err();
+    assert!(error.to_string().contains("duplicate API key name: admin"), "{}", error);
+
+    // 旧来の API_KEY は "default" という名前になる
+    let error = load(&key("default", ADMIN_KEY), Some(VIEWER_KEY)).unwrap_err();
+    assert!(error.to_string().contains("duplicate API key name: default"), "{}", error);
+
+    // OAuth の呼び出し元 (oauth:<sub>) と区別できなくなる
+    let error = load(&key("oauth:alice", ADMIN_KEY), None).unwrap_err();
+    assert!(error.to_string().contains("reserved"), "{}", error);
+
+    let store = load(&(key("admin", ADMIN_KEY) + &key("viewer", VIEWER_KEY)), None).unwrap();
+    assert_eq!(store.key_count(), 2);
+}
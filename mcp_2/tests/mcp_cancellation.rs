@@ -0,0 +1,134 @@
+// ツールの時間切れとキャンセル (notifications/cancelled)
+mod common;
+
+use axum::body::to_bytes;
+use axum::http::StatusCode;
+use common::{registry_with_wait, TestOptions, TestServer};
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// 時間切れ
+const TOOL_TIMEOUT: i64 = -32004;
+
+fn call_wait(id: i64) -> serde_json::Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": "wait", "arguments": {} }
+    })
+}
+
+#[tokio::test]
+async fn tool_timeout_aborts_the_handler() {
+    let (tools, _release) = registry_with_wait();
+    let server = TestServer::with_options(TestOptions {
+        tools: Some(tools),
+        tool_timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let error = client.call_tool_error("wait", json!({})).await;
+    assert_eq!(error["code"], TOOL_TIMEOUT);
+    assert_eq!(error["data"]["tool"], "wait");
+
+    // すぐに終わるツールはそのまま完了する
+    let result = client.call_tool("add", json!({ "a": 1, "b": 2 })).await;
+    assert_eq!(result["content"][0]["text"], "Result: 3");
+}
+
+#[tokio::test]
+async fn per_tool_timeout_overrides_the_global_timeout() {
+    let (tools, _release) = registry_with_wait();
+    let server = TestServer::with_options(TestOptions {
+        tools: Some(tools),
+        tool_timeouts: HashMap::from([("wait".to_string(), Duration::from_millis(100))]),
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    // 既定の上限 (30 秒) を待たずに時間切れになる
+    let started = std::time::Instant::now();
+    let error = client.call_tool_error("wait", json!({})).await;
+    assert_eq!(error["code"], TOOL_TIMEOUT);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn cancelled_request_is_aborted_without_response() {
+    let (tools, _release) = registry_with_wait();
+    let server = TestServer::with_options(TestOptions {
+        tools: Some(tools),
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let stream = client.post_streaming(&call_wait(7)).await;
+    assert_eq!(stream.status(), StatusCode::OK);
+
+    // ツールが処理中になるのを待ってからキャンセルする
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let response = client
+        .notify("notifications/cancelled", json!({ "requestId": 7, "reason": "user abort" }))
+        .await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+
+    // wait は解放していないので、ストリームが終わるのはハンドラーが中断された場合だけ
+    let events = tokio::time::timeout(Duration::from_secs(5), to_bytes(stream.into_body(), usize::MAX))
+        .await
+        .expect("cancelled tool call must end the stream")
+        .unwrap();
+    let events = String::from_utf8_lossy(&events);
+    assert!(!events.contains("\"id\":7"), "{}", events);
+
+    // セッションは引き続き使える
+    let result = client.call_tool("add", json!({ "a": 2, "b": 2 })).await;
+    assert_eq!(result["content"][0]["text"], "Result: 4");
+}
+
+#[tokio::test]
+async fn duplicate_in_flight_id_is_rejected() {
+    let (tools, release) = registry_with_wait();
+    let server = TestServer::with_options(TestOptions {
+        tools: Some(tools),
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let stream = client.post_streaming(&call_wait(7)).await;
+    assert_eq!(stream.status(), StatusCode::OK);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // 処理中の id を再利用したリクエストは拒否し、先のリクエストの登録は残す
+    let response = client.post(&call_wait(7)).await;
+    let body = response.json();
+    assert_eq!(body["error"]["code"], -32600, "{}", body);
+    assert_eq!(body["id"], 7);
+
+    let response = client
+        .notify("notifications/cancelled", json!({ "requestId": 7, "reason": "user abort" }))
+        .await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+    let events = tokio::time::timeout(Duration::from_secs(5), to_bytes(stream.into_body(), usize::MAX))
+        .await
+        .expect("the first request must still be cancellable")
+        .unwrap();
+    assert!(!String::from_utf8_lossy(&events).contains("\"id\":7"));
+
+    // 終わった id は再利用できる
+    let stream = client.post_streaming(&call_wait(7)).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    release.notify_one();
+    let events = to_bytes(stream.into_body(), usize::MAX).await.unwrap();
+    assert!(String::from_utf8_lossy(&events).contains("released"));
+}
@@ -0,0 +1,207 @@
+// サーバー → クライアントのリクエスト (elicitation/create, sampling/createMessage)
+mod common;
+
+use axum::http::StatusCode;
+use common::{text, McpClient, TestOptions, TestServer};
+use rust_remoto_mcp_2::mod_ratelimit::{Rate, RateLimitConfig};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+// GET /mcp 相当のストリームを開き、同じセッションで応答を返すクライアントを作る
+async fn connect<'a>(
+    server: &'a TestServer,
+    capabilities: Value,
+) -> (McpClient<'a>, McpClient<'a>, UnboundedReceiver<Value>) {
+    let mut client = server.client();
+    client.initialize_with(capabilities).await;
+    let session = server.state.sessions.get(client.session.as_deref().unwrap()).unwrap();
+    let stream = session.open_stream();
+    let mut responder = server.client();
+    responder.session = client.session.clone();
+    (client, responder, stream)
+}
+
+// ストリームで受け取ったリクエストに応答する (reply は result または error を含むオブジェクト)
+async fn answer(stream: &mut UnboundedReceiver<Value>, responder: &McpClient<'_>, method: &str, reply: Value) -> Value {
+    let request = stream.recv().await.unwrap();
+    assert_eq!(request["method"], method, "{}", request);
+    let mut response = json!({ "jsonrpc": "2.0", "id": request["id"] });
+    response.as_object_mut().unwrap().extend(reply.as_object().unwrap().clone());
+    let posted = responder.post(&response).await;
+    assert_eq!(posted.status, StatusCode::ACCEPTED);
+    request
+}
+
+#[tokio::test]
+async fn purchase_above_threshold_is_confirmed() {
+    let server = TestServer::with_purchase_confirmation(1000).await;
+    let (mut client, responder, mut stream) = connect(&server, json!({ "elicitation": {} })).await;
+
+    // 確認不要の金額
+    client.call_tool("purchase", json!({ "name": "coffee", "price": 450, "quantity": 2 })).await;
+    assert!(stream.try_recv().is_err());
+
+    let (result, request) = tokio::join!(
+        client.call_tool("purchase", json!({ "name": "chair", "price": 800, "quantity": 2 })),
+        answer(
+            &mut stream,
+            &responder,
+            "elicitation/create",
+            json!({ "result": { "action": "accept", "content": { "confirm": true } } })
+        )
+    );
+    assert!(request["params"]["message"].as_str().unwrap().contains("1600円"));
+    assert_eq!(request["params"]["requestedSchema"]["required"], json!(["confirm"]));
+    assert_eq!(result["structuredContent"]["name"], "chair");
+
+    let list = client.call_tool("purchase_list", json!({})).await;
+    assert_eq!(list["structuredContent"]["total"], 2);
+}
+
+#[tokio::test]
+async fn declined_purchase_is_not_recorded() {
+    let server = TestServer::with_purchase_confirmation(1000).await;
+    let (mut client, responder, mut stream) = connect(&server, json!({ "elicitation": {} })).await;
+
+    let (error, _) = tokio::join!(
+        client.call_tool_error("purchase", json!({ "name": "chair", "price": 5000 })),
+        answer(&mut stream, &responder, "elicitation/create", json!({ "result": { "action": "decline" } }))
+    );
+    assert_eq!(error["code"], -32007);
+    assert_eq!(error["data"]["action"], "decline");
+
+    // accept でも confirm が false なら登録しない
+    let (error, _) = tokio::join!(
+        client.call_tool_error("purchase", json!({ "name": "chair", "price": 5000 })),
+        answer(
+            &mut stream,
+            &responder,
+            "elicitation/create",
+            json!({ "result": { "action": "accept", "content": { "confirm": false } } })
+        )
+    );
+    assert_eq!(error["code"], -32007);
+
+    let list = client.call_tool("purchase_list", json!({})).await;
+    assert_eq!(list["structuredContent"]["total"], 0);
+}
+
+// 応答の POST は同時実行数・キーごとのレートに数えない (ツール呼び出しが permit を持ったまま待つため)
+#[tokio::test]
+async fn confirmation_is_not_rate_limited() {
+    let server = TestServer::with_options(TestOptions {
+        purchase_confirm_above: Some(1000),
+        rate_limit: RateLimitConfig {
+            // initialize, notifications/initialized, tools/call の 3 回分
+            per_key: Some(Rate::parse("3/min").unwrap()),
+            max_in_flight: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let (mut client, responder, mut stream) = connect(&server, json!({ "elicitation": {} })).await;
+
+    let (result, _) = tokio::join!(
+        client.call_tool("purchase", json!({ "name": "chair", "price": 5000 })),
+        answer(
+            &mut stream,
+            &responder,
+            "elicitation/create",
+            json!({ "result": { "action": "accept", "content": { "confirm": true } } })
+        )
+    );
+    assert_eq!(result["structuredContent"]["name"], "chair");
+
+    // リクエストはキーのレートを使い切っている
+    let limited = client.post(&json!({ "jsonrpc": "2.0", "id": 99, "method": "ping" })).await;
+    assert_eq!(limited.status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+// 確認の待ち時間はツールの実行時間の上限に含まれる
+#[tokio::test]
+async fn unanswered_confirmation_times_out() {
+    let server = TestServer::with_options(TestOptions {
+        purchase_confirm_above: Some(1000),
+        tool_timeouts: HashMap::from([("purchase".to_string(), Duration::from_millis(200))]),
+        ..Default::default()
+    })
+    .await;
+    let (mut client, responder, mut stream) = connect(&server, json!({ "elicitation": {} })).await;
+
+    let error = client.call_tool_error("purchase", json!({ "name": "chair", "price": 5000 })).await;
+    assert_eq!(error["code"], -32004);
+    let request = stream.recv().await.unwrap();
+    assert_eq!(request["method"], "elicitation/create");
+    let cancelled = stream.recv().await.unwrap();
+    assert_eq!(cancelled["method"], "notifications/cancelled");
+    assert_eq!(cancelled["params"]["requestId"], request["id"]);
+
+    // 遅れた応答は受け付けるが、purchase は登録しない
+    let late = responder
+        .post(&json!({ "jsonrpc": "2.0", "id": request["id"], "result": { "action": "accept", "content": { "confirm": true } } }))
+        .await;
+    assert_eq!(late.status, StatusCode::ACCEPTED);
+    let list = client.call_tool("purchase_list", json!({})).await;
+    assert_eq!(list["structuredContent"]["total"], 0);
+}
+
+#[tokio::test]
+async fn confirmation_requires_elicitation_support() {
+    let server = TestServer::with_purchase_confirmation(1000).await;
+    let (mut client, _responder, mut stream) = connect(&server, json!({})).await;
+
+    let error = client.call_tool_error("purchase", json!({ "name": "chair", "price": 5000 })).await;
+    assert_eq!(error["code"], -32006);
+    assert_eq!(error["data"]["method"], "elicitation/create");
+    assert!(stream.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn insights_are_generated_by_client_sampling() {
+    let server = TestServer::new().await;
+    let (mut client, responder, mut stream) = connect(&server, json!({ "sampling": {} })).await;
+    client
+        .call_tool("purchase", json!({ "name": "coffee", "price": 450, "purchased_at": "2025-01-10" }))
+        .await;
+
+    let (result, request) = tokio::join!(
+        client.call_tool("purchase_insights", json!({ "month": "2025-01", "max_tokens": 200 })),
+        answer(
+            &mut stream,
+            &responder,
+            "sampling/createMessage",
+            json!({ "result": {
+                "role": "assistant",
+                "content": { "type": "text", "text": "コーヒーの購入が中心です。" },
+                "model": "test-model"
+            } })
+        )
+    );
+    let params = &request["params"];
+    assert_eq!(params["maxTokens"], 200);
+    assert!(params["messages"][0]["content"]["text"].as_str().unwrap().contains("coffee"));
+    assert_eq!(text(&result), "コーヒーの購入が中心です。");
+    assert_eq!(result["structuredContent"]["model"], "test-model");
+
+    // クライアントのエラー応答はツールのエラーになる
+    let (error, _) = tokio::join!(
+        client.call_tool_error("purchase_insights", json!({})),
+        answer(
+            &mut stream,
+            &responder,
+            "sampling/createMessage",
+            json!({ "error": { "code": -1, "message": "User rejected sampling request" } })
+        )
+    );
+    assert_eq!(error["code"], -32006);
+    assert_eq!(error["data"]["error"]["message"], "User rejected sampling request");
+
+    // 月の形式が不正ならクライアントへ依頼しない
+    let error = client.call_tool_error("purchase_insights", json!({ "month": "2025/01" })).await;
+    assert_eq!(error["code"], -32602);
+    assert_eq!(error["message"], "month must be YYYY-MM");
+    assert!(stream.try_recv().is_err());
+}
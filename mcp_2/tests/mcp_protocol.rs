@@ -0,0 +1,360 @@
+// JSON-RPC / MCP プロトコルの契約 (initialize, tools/list, 不正なリクエスト)
+mod common;
+
+use axum::http::StatusCode;
+use common::{TestOptions, TestServer, PROTOCOL_VERSION};
+use serde_json::{json, Value};
+
+// initialize → notifications/initialized 済みのセッション id
+async fn initialized_session(server: &TestServer) -> String {
+    let mut client = server.client();
+    client.initialize().await;
+    client.session.unwrap()
+}
+
+#[tokio::test]
+async fn initialize_returns_session_and_capabilities() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    let response = client.initialize().await;
+
+    let result = &response["result"];
+    assert_eq!(result["protocolVersion"], PROTOCOL_VERSION);
+    assert_eq!(result["serverInfo"]["name"], "MCP Server Test");
+    assert_eq!(result["capabilities"]["resources"]["subscribe"], true);
+    assert!(result["capabilities"]["tools"].is_object());
+    assert!(result["capabilities"]["prompts"].is_object());
+    assert!(result["capabilities"]["logging"].is_object());
+    assert!(result["capabilities"]["completions"].is_object());
+}
+
+#[tokio::test]
+async fn initialize_offers_latest_version_for_unknown_version() {
+    let server = TestServer::new().await;
+    let response = server
+        .client()
+        .post(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": { "protocolVersion": "1999-01-01", "capabilities": {} }
+        }))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["result"]["protocolVersion"], PROTOCOL_VERSION);
+}
+
+#[tokio::test]
+async fn second_initialize_is_rejected() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let response = client
+        .request("initialize", json!({ "protocolVersion": PROTOCOL_VERSION, "capabilities": {} }))
+        .await;
+    assert_eq!(response["error"]["code"], -32600);
+}
+
+#[tokio::test]
+async fn requests_before_initialized_are_rejected() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    let response = client
+        .post(&json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "method": "initialize",
+            "params": { "protocolVersion": PROTOCOL_VERSION, "capabilities": {} }
+        }))
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    client.session = response.header("mcp-session-id").map(str::to_string);
+
+    // notifications/initialized を送るまでは受け付けない
+    let response = client.request("tools/list", json!({})).await;
+    assert_eq!(response["error"]["code"], -32600);
+    assert_eq!(response["error"]["message"], "Server not initialized");
+
+    // ping は初期化前でも応答する
+    let response = client.request("ping", json!({})).await;
+    assert_eq!(response["result"], json!({}));
+
+    let response = client.notify("notifications/initialized", json!({})).await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+    let response = client.request("tools/list", json!({})).await;
+    assert!(response["result"]["tools"].is_array());
+}
+
+#[tokio::test]
+async fn requests_without_session_are_rejected() {
+    let server = TestServer::new().await;
+    for body in [
+        r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"[{"jsonrpc":"2.0","id":3,"method":"initialize","params":{}}]"#,
+    ] {
+        let response = server.post_raw(&[], body).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST, "{}", body);
+        assert_eq!(response.json()["error"]["code"], -32000);
+    }
+}
+
+#[tokio::test]
+async fn tools_list_contains_every_registered_tool() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let response = client.request("tools/list", json!({})).await;
+    let tools = response["result"]["tools"].as_array().unwrap();
+    let names: Vec<&str> = tools.iter().map(|t| t["name"].as_str().unwrap()).collect();
+    assert_eq!(
+        names,
+        [
+            "echo",
+            "add",
+            "add_todo",
+            "list_todos",
+            "complete_todo",
+            "delete_todo",
+            "search_todos",
+            "purchase",
+            "purchase_list",
+            "purchase_summary",
+            "purchase_insights",
+            "purchase_delete",
+            "purchase_export"
+        ]
+    );
+    for tool in tools {
+        assert_eq!(tool["inputSchema"]["type"], "object", "{}", tool["name"]);
+        assert!(tool["description"].is_string());
+        assert!(tool["annotations"]["readOnlyHint"].is_boolean());
+    }
+}
+
+#[tokio::test]
+async fn malformed_json_is_a_parse_error() {
+    let server = TestServer::new().await;
+    let response = server.post_raw(&[], "{\"jsonrpc\": \"2.0\", ").await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    let body = response.json();
+    assert_eq!(body["error"]["code"], -32700);
+    assert_eq!(body["id"], Value::Null);
+}
+
+#[tokio::test]
+async fn invalid_requests_are_rejected() {
+    let server = TestServer::new().await;
+    let session = initialized_session(&server).await;
+    let headers = [("mcp-session-id", session.as_str())];
+
+    // method が無い
+    let response = server.post_raw(&headers, r#"{"jsonrpc":"2.0","id":1}"#).await;
+    assert_eq!(response.json()["error"]["code"], -32600);
+    assert_eq!(response.json()["id"], 1);
+
+    // jsonrpc のバージョン違い
+    let response = server
+        .post_raw(&headers, r#"{"jsonrpc":"1.0","id":2,"method":"ping"}"#)
+        .await;
+    assert_eq!(response.json()["error"]["code"], -32600);
+
+    // 空のバッチ
+    let response = server.post_raw(&headers, "[]").await;
+    assert_eq!(response.json()["error"]["code"], -32600);
+
+    // id: null は通知ではなく、id: null のエラー応答を返す
+    for body in [
+        r#"{"jsonrpc":"2.0","id":null,"method":"ping"}"#,
+        r#"{"jsonrpc":"2.0","id":true,"method":"ping"}"#,
+    ] {
+        let response = server.post_raw(&headers, body).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", body);
+        let body = response.json();
+        assert_eq!(body["error"]["code"], -32600);
+        assert!(body.as_object().unwrap().contains_key("id"));
+        assert_eq!(body["id"], Value::Null);
+    }
+
+    // バッチ内でも他の要素とは別に応答する
+    let response = server
+        .post_raw(
+            &headers,
+            r#"[{"jsonrpc":"2.0","id":null,"method":"ping"},{"jsonrpc":"2.0","id":3,"method":"ping"}]"#,
+        )
+        .await;
+    let responses = response.json();
+    assert_eq!(responses.as_array().unwrap().len(), 2);
+    assert_eq!(responses[0]["error"]["code"], -32600);
+    assert_eq!(responses[1]["id"], 3);
+}
+
+#[tokio::test]
+async fn unknown_method_is_method_not_found() {
+    let server = TestServer::new().await;
+    let session = initialized_session(&server).await;
+    let response = server
+        .post_raw(&[("mcp-session-id", &session)], r#"{"jsonrpc":"2.0","id":"a","method":"no/such/method"}"#)
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["error"]["code"], -32601);
+    assert_eq!(response.json()["id"], "a");
+}
+
+#[tokio::test]
+async fn batch_returns_responses_for_requests_only() {
+    let server = TestServer::new().await;
+    let session = initialized_session(&server).await;
+    let response = server
+        .post_raw(
+            &[("mcp-session-id", &session)],
+            r#"[
+                {"jsonrpc":"2.0","id":1,"method":"ping"},
+                {"jsonrpc":"2.0","method":"notifications/initialized"},
+                {"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"add","arguments":{"a":1,"b":2}}},
+                {"jsonrpc":"2.0","id":3}
+            ]"#,
+        )
+        .await;
+    let body = response.json();
+    let responses = body.as_array().unwrap();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0]["result"], json!({}));
+    assert_eq!(responses[1]["result"]["content"][0]["text"], "Result: 3");
+    assert_eq!(responses[2]["error"]["code"], -32600);
+}
+
+#[tokio::test]
+async fn notifications_are_accepted_without_body() {
+    let server = TestServer::new().await;
+    let session = initialized_session(&server).await;
+    let response = server
+        .post_raw(&[("mcp-session-id", &session)], r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#)
+        .await;
+    assert_eq!(response.status, StatusCode::ACCEPTED);
+    assert!(response.body.is_empty());
+}
+
+#[tokio::test]
+async fn unknown_session_is_not_found() {
+    let server = TestServer::new().await;
+    let response = server
+        .post_raw(&[("mcp-session-id", "no-such-session")], r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#)
+        .await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn mismatched_protocol_version_header_is_rejected() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+    let session = client.session.clone().unwrap();
+
+    let response = server
+        .post_raw(
+            &[("mcp-session-id", &session), ("mcp-protocol-version", "2024-11-05")],
+            r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+    let response = server
+        .post_raw(
+            &[("mcp-session-id", &session), ("mcp-protocol-version", PROTOCOL_VERSION)],
+            r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#,
+        )
+        .await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn invalid_tool_arguments_report_violations() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let error = client
+        .call_tool_error("purchase", json!({ "name": "", "price": -1, "extra": true }))
+        .await;
+    assert_eq!(error["code"], -32602);
+    assert_eq!(error["data"]["tool"], "purchase");
+    let paths: Vec<&str> = error["data"]["violations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v["path"].as_str().unwrap())
+        .collect();
+    assert!(paths.contains(&"/name"), "{:?}", paths);
+    assert!(paths.contains(&"/price"), "{:?}", paths);
+
+    let error = client.call_tool_error("no_such_tool", json!({})).await;
+    assert_eq!(error["code"], -32602);
+}
+
+#[tokio::test]
+async fn deleted_session_is_gone() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+    let session = client.session.clone().unwrap();
+
+    let request = axum::http::Request::builder()
+        .method("DELETE")
+        .uri("/mcp")
+        .header("mcp-session-id", &session)
+        .body(axum::body::Body::empty())
+        .unwrap();
+    assert_eq!(server.send(request).await.status, StatusCode::NO_CONTENT);
+
+    let response = client.post(&json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" })).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn idle_sessions_expire() {
+    let server = TestServer::with_options(TestOptions {
+        session_idle_timeout: Some(std::time::Duration::from_millis(200)),
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+    let session = client.session.clone().unwrap();
+
+    // 使われている間は期限が延びる
+    for _ in 0..3 {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        let response = client.request("ping", json!({})).await;
+        assert!(response["result"].is_object(), "{}", response);
+    }
+
+    tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+    assert_eq!(server.state.sessions.sweep(), 1);
+    assert!(server.state.sessions.get(&session).is_none());
+    let response = client.post(&json!({ "jsonrpc": "2.0", "id": 1, "method": "ping" })).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn unknown_methods_share_one_metrics_label() {
+    let server = TestServer::new().await;
+    let session = initialized_session(&server).await;
+
+    // -32601 以外のエラー (不正なバージョン) でもメソッド名をラベルにしない
+    for body in [
+        r#"{"jsonrpc":"2.0","id":1,"method":"x/unknown-1"}"#,
+        r#"{"jsonrpc":"1.0","id":2,"method":"x/unknown-2"}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"ping"}"#,
+    ] {
+        server.post_raw(&[("mcp-session-id", &session)], body).await;
+    }
+
+    let metrics = server.get("/metrics", &[]).await.body;
+    assert!(!metrics.contains("x/unknown"), "{}", metrics);
+    assert!(metrics.contains(r#"mcp_requests_total{method="other",outcome="error"} 2"#), "{}", metrics);
+    assert!(metrics.contains(r#"mcp_requests_total{method="ping",outcome="ok"} 1"#), "{}", metrics);
+}
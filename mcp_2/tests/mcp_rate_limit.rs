@@ -0,0 +1,248 @@
+// レート制限 (キーごと・ツールごと・同時実行数)
+mod common;
+
+use axum::body::to_bytes;
+use axum::http::StatusCode;
+use common::{registry_with_wait, TestOptions, TestServer};
+use rust_remoto_mcp_2::mod_auth::hash_key;
+use rust_remoto_mcp_2::mod_ratelimit::{Rate, RateLimitConfig};
+use serde_json::json;
+use std::collections::HashMap;
+
+const LIMITED_KEY: &str = "limited-secret";
+const OTHER_KEY: &str = "other-secret";
+
+fn ping(id: i64) -> serde_json::Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": "ping" })
+}
+
+fn tool_rates(rates: &[(&str, &str)]) -> HashMap<String, Rate> {
+    rates
+        .iter()
+        .map(|(tool, rate)| (tool.to_string(), Rate::parse(rate).unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+async fn key_limit_rejects_requests_over_the_rate() {
+    let keys = format!(
+        r#"
+[[keys]]
+name = "limited"
+hash = "{}"
+scopes = ["*"]
+rate_limit = "5/min"
+
+[[keys]]
+name = "other"
+hash = "{}"
+scopes = ["*"]
+"#,
+        hash_key(LIMITED_KEY),
+        hash_key(OTHER_KEY)
+    );
+    let server = TestServer::with_options(TestOptions {
+        api_keys: Some(keys),
+        rate_limit: RateLimitConfig {
+            per_key: Some(Rate::parse("100/min").unwrap()),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    // initialize と notifications/initialized で 2 回分
+    let mut client = server.client().with_token(LIMITED_KEY);
+    client.initialize().await;
+    for id in 1..=3 {
+        let response = client.post(&ping(id)).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    let response = client.post(&ping(4)).await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.header("retry-after").is_some());
+    let body = response.json();
+    assert_eq!(body["error"]["code"], -32029);
+    assert_eq!(body["error"]["data"]["limit"], "key");
+
+    // 別のキーは既定のレート (per_key) で独立して数える
+    let mut other = server.client().with_token(OTHER_KEY);
+    other.initialize().await;
+    assert_eq!(other.post(&ping(1)).await.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn tool_limit_applies_per_tool() {
+    let server = TestServer::with_options(TestOptions {
+        rate_limit: RateLimitConfig {
+            per_tool: tool_rates(&[("add", "1/min")]),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    client.call_tool("add", json!({ "a": 1, "b": 2 })).await;
+    let response = client
+        .post(&json!({
+            "jsonrpc": "2.0",
+            "id": 10,
+            "method": "tools/call",
+            "params": { "name": "add", "arguments": { "a": 1, "b": 2 } }
+        }))
+        .await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    let body = response.json();
+    assert_eq!(body["id"], 10);
+    assert_eq!(body["error"]["data"]["limit"], "tool");
+    assert_eq!(body["error"]["data"]["tool"], "add");
+
+    // 制限の無いツールは呼び出せる
+    client.call_tool("echo", json!({ "message": "hi" })).await;
+}
+
+fn call(id: i64, name: &str, arguments: serde_json::Value) -> serde_json::Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "tools/call",
+        "params": { "name": name, "arguments": arguments }
+    })
+}
+
+#[tokio::test]
+async fn rejected_batch_does_not_consume_tool_tokens() {
+    let server = TestServer::with_options(TestOptions {
+        rate_limit: RateLimitConfig {
+            per_tool: tool_rates(&[("add", "1/min"), ("echo", "2/min")]),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    // echo の残りは 1 回分のため、2 回呼び出すバッチ全体を拒否する
+    client.call_tool("echo", json!({ "message": "a" })).await;
+    let response = client
+        .post(&json!([
+            call(1, "add", json!({ "a": 1, "b": 2 })),
+            call(2, "echo", json!({ "message": "b" })),
+            call(3, "echo", json!({ "message": "c" }))
+        ]))
+        .await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.json()["error"]["data"]["tool"], "echo");
+
+    // 拒否されたバッチではトークンを消費しない
+    client.call_tool("add", json!({ "a": 1, "b": 2 })).await;
+    client.call_tool("echo", json!({ "message": "d" })).await;
+}
+
+#[tokio::test]
+async fn tool_limit_does_not_consume_key_tokens() {
+    let server = TestServer::with_options(TestOptions {
+        rate_limit: RateLimitConfig {
+            // initialize と notifications/initialized で 2 回分
+            per_key: Some(Rate::parse("4/min").unwrap()),
+            per_tool: tool_rates(&[("add", "1/min")]),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    client.call_tool("add", json!({ "a": 1, "b": 2 })).await;
+    let response = client.post(&call(10, "add", json!({ "a": 1, "b": 2 }))).await;
+    assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.json()["error"]["data"]["limit"], "tool");
+
+    // ツールの制限で拒否されたリクエストはキーのトークンを使わない
+    let response = client.post(&ping(11)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    let response = client.post(&ping(12)).await;
+    assert_eq!(response.json()["error"]["data"]["limit"], "key");
+}
+
+#[tokio::test]
+async fn batch_over_tool_capacity_is_rejected() {
+    let server = TestServer::with_options(TestOptions {
+        rate_limit: RateLimitConfig {
+            per_tool: tool_rates(&[("echo", "2/min")]),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    // 容量 (2 回) を超えるバッチは待っても通らないため、retryAfter を返さない
+    let response = client
+        .post(&json!([
+            call(1, "echo", json!({ "message": "a" })),
+            call(2, "echo", json!({ "message": "b" })),
+            call(3, "echo", json!({ "message": "c" }))
+        ]))
+        .await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(response.header("retry-after").is_none());
+    let body = response.json();
+    assert_eq!(body["error"]["code"], -32600);
+    assert_eq!(body["error"]["data"], json!({ "tool": "echo", "calls": 3, "capacity": 2 }));
+
+    // トークンは消費していない
+    let response = client
+        .post(&json!([
+            call(4, "echo", json!({ "message": "d" })),
+            call(5, "echo", json!({ "message": "e" }))
+        ]))
+        .await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+}
+
+#[tokio::test]
+async fn in_flight_limit_holds_until_sse_stream_ends() {
+    let (tools, release) = registry_with_wait();
+    let server = TestServer::with_options(TestOptions {
+        tools: Some(tools),
+        rate_limit: RateLimitConfig {
+            max_in_flight: Some(1),
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    // SSE の tools/call はすぐに応答ヘッダーを返すが、ストリームが終わるまで枠を使う
+    let stream = client
+        .post_streaming(&json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "wait", "arguments": {} }
+        }))
+        .await;
+    assert_eq!(stream.status(), StatusCode::OK);
+
+    let response = client.post(&ping(2)).await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    let body = response.json();
+    assert_eq!(body["error"]["code"], -32029);
+    assert_eq!(body["error"]["data"]["limit"], "in_flight");
+
+    release.notify_one();
+    let events = to_bytes(stream.into_body(), usize::MAX).await.unwrap();
+    assert!(String::from_utf8_lossy(&events).contains("released"));
+
+    let response = client.post(&ping(3)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+}
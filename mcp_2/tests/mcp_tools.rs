@@ -0,0 +1,205 @@
+// tools/call (全ツール)
+mod common;
+
+use common::{text, TestServer};
+use rust_remoto_mcp_2::mod_purchase::Purchase;
+use serde_json::json;
+
+#[tokio::test]
+async fn echo_and_add() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let result = client.call_tool("echo", json!({ "message": "hello" })).await;
+    assert_eq!(text(&result), "Echo: hello");
+
+    let result = client.call_tool("add", json!({ "a": 1.5, "b": 2 })).await;
+    assert_eq!(text(&result), "Result: 3.5");
+}
+
+#[tokio::test]
+async fn todo_lifecycle() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let result = client.call_tool("add_todo", json!({ "title": "buy milk" })).await;
+    assert_eq!(text(&result), "Todo added successfully with ID: 1");
+    client.call_tool("add_todo", json!({ "title": "write 100% tests" })).await;
+
+    let result = client.call_tool("list_todos", json!({})).await;
+    assert!(text(&result).contains("[ ] 1: buy milk"), "{}", text(&result));
+    assert!(text(&result).contains("[ ] 2: write 100% tests"));
+
+    // LIKE のワイルドカードは文字として扱う
+    let result = client.call_tool("search_todos", json!({ "query": "100%" })).await;
+    assert_eq!(text(&result), "[ ] 2: write 100% tests");
+    let result = client.call_tool("search_todos", json!({ "query": "%" })).await;
+    assert_eq!(text(&result), "[ ] 2: write 100% tests");
+
+    let result = client.call_tool("complete_todo", json!({ "id": 1 })).await;
+    assert_eq!(text(&result), "Todo completed: [x] 1: buy milk");
+
+    let result = client.call_tool("list_todos", json!({ "status": "done" })).await;
+    assert_eq!(text(&result), "[x] 1: buy milk");
+    let result = client.call_tool("list_todos", json!({ "status": "open" })).await;
+    assert_eq!(text(&result), "[ ] 2: write 100% tests");
+
+    let result = client.call_tool("delete_todo", json!({ "id": 1 })).await;
+    assert_eq!(text(&result), "Todo deleted: 1");
+    let result = client.call_tool("list_todos", json!({ "status": "all" })).await;
+    assert_eq!(text(&result), "[ ] 2: write 100% tests");
+}
+
+#[tokio::test]
+async fn todo_filters_and_limits() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    for title in ["task a", "task b", "task c"] {
+        client.call_tool("add_todo", json!({ "title": title })).await;
+    }
+    client.call_tool("complete_todo", json!({ "id": 1 })).await;
+
+    // list_todos は未完了のみ、search_todos は全件が既定
+    let result = client.call_tool("list_todos", json!({})).await;
+    assert!(!text(&result).contains("task a"), "{}", text(&result));
+    let result = client.call_tool("search_todos", json!({ "query": "task" })).await;
+    assert_eq!(text(&result).lines().count(), 3, "{}", text(&result));
+
+    // 整数値の小数は整数として扱う (既定の 50 件にしない)
+    let result = client.call_tool("list_todos", json!({ "status": "all", "limit": 1.0 })).await;
+    assert_eq!(text(&result).lines().count(), 1, "{}", text(&result));
+    let result = client.call_tool("search_todos", json!({ "query": "task", "limit": 2.0 })).await;
+    assert_eq!(text(&result).lines().count(), 2, "{}", text(&result));
+
+    let error = client.call_tool_error("list_todos", json!({ "limit": 1.5 })).await;
+    assert_eq!(error["code"], -32602);
+    let error = client.call_tool_error("search_todos", json!({ "query": "task", "limit": 0 })).await;
+    assert_eq!(error["code"], -32602);
+}
+
+#[tokio::test]
+async fn missing_todo_is_invalid_params() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let error = client.call_tool_error("complete_todo", json!({ "id": 99 })).await;
+    assert_eq!(error["code"], -32602);
+    assert_eq!(error["message"], "Todo not found: 99");
+    let error = client.call_tool_error("delete_todo", json!({ "id": 99 })).await;
+    assert_eq!(error["code"], -32602);
+}
+
+#[tokio::test]
+async fn purchase_list_summary_delete() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    let result = client
+        .call_tool(
+            "purchase",
+            json!({ "name": "coffee", "price": 450, "quantity": 2, "purchased_at": "2025-01-10" }),
+        )
+        .await;
+    let coffee = &result["structuredContent"];
+    assert_eq!(coffee["name"], "coffee");
+    assert_eq!(coffee["price"], 450);
+    assert_eq!(coffee["quantity"], 2);
+    assert_eq!(coffee["currency"], "JPY");
+    assert_eq!(coffee["purchased_at"], "2025-01-10 00:00:00");
+    let coffee_id = coffee["id"].as_i64().unwrap();
+
+    client
+        .call_tool(
+            "purchase",
+            json!({ "name": "book", "price": 20, "currency": "usd", "purchased_at": "2025-02-01 12:30:00" }),
+        )
+        .await;
+
+    let result = client.call_tool("purchase_list", json!({})).await;
+    let list = &result["structuredContent"];
+    assert_eq!(list["total"], 2);
+    assert_eq!(list["items"][0]["name"], "book");
+    assert_eq!(list["items"][0]["currency"], "USD");
+
+    let result = client
+        .call_tool("purchase_list", json!({ "from": "2025-01-01", "to": "2025-01-31" }))
+        .await;
+    assert_eq!(result["structuredContent"]["total"], 1);
+    assert_eq!(result["structuredContent"]["items"][0]["name"], "coffee");
+
+    let result = client.call_tool("purchase_summary", json!({ "group_by": "month" })).await;
+    assert!(result["structuredContent"].is_object(), "{}", result);
+    let rows = result["structuredContent"]["rows"].as_array().unwrap();
+    assert_eq!(rows.len(), 2);
+
+    let result = client.call_tool("purchase_export", json!({})).await;
+    let csv = text(&result);
+    assert!(csv.starts_with("id,name,price,quantity,currency,purchased_at\n"), "{}", csv);
+    assert_eq!(csv.lines().count(), 3);
+    assert_eq!(result["structuredContent"]["count"], 2);
+
+    let result = client.call_tool("purchase_delete", json!({ "id": coffee_id })).await;
+    assert_eq!(result["structuredContent"]["id"], coffee_id);
+    let result = client.call_tool("purchase_list", json!({})).await;
+    assert_eq!(result["structuredContent"]["total"], 1);
+
+    let error = client.call_tool_error("purchase_delete", json!({ "id": coffee_id })).await;
+    assert_eq!(error["code"], -32602);
+}
+
+#[tokio::test]
+async fn tool_calls_are_audited() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+
+    client.call_tool("echo", json!({ "message": "secret" })).await;
+    client.call_tool_error("complete_todo", json!({ "id": 1 })).await;
+
+    // 監査ログはバックグラウンドで書き込まれる
+    let mut entries = Vec::new();
+    for _ in 0..50 {
+        let (items, _) = server
+            .state
+            .audit
+            .query(&Default::default(), 10, 0)
+            .await
+            .unwrap();
+        if items.len() == 2 {
+            entries = items;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].tool, "complete_todo");
+    assert_eq!(entries[0].outcome, "error");
+    assert_eq!(entries[0].error_code, Some(-32602));
+    assert_eq!(entries[1].tool, "echo");
+    assert_eq!(entries[1].outcome, "ok");
+    assert_eq!(entries[1].caller, "local");
+    assert!(!entries[1].arguments_digest.contains("secret"));
+}
+
+// 移行前のデータなど、purchased_at が日付形式でなくても表示できる
+#[test]
+fn purchase_line_tolerates_non_ascii_dates() {
+    let purchase = |purchased_at: &str| Purchase {
+        id: 1,
+        name: "coffee".to_string(),
+        price: 450,
+        quantity: 2,
+        currency: "JPY".to_string(),
+        purchased_at: purchased_at.to_string(),
+        created_at: String::new(),
+        updated_at: String::new(),
+    };
+    assert!(purchase("2025-01-10 09:30:00").line().starts_with("#1 2025-01-10 coffee "));
+    assert!(purchase("2025年01月10日").line().starts_with("#1 2025年01月10日 coffee "));
+}
@@ -0,0 +1,147 @@
+// 上流 MCP サーバーへの転送 (ゲートウェイ)
+mod common;
+
+use common::{text, TestServer};
+use rust_remoto_mcp_2::mod_auth::hash_key;
+use rust_remoto_mcp_2::mod_tools;
+use rust_remoto_mcp_2::mod_upstream::{self, UpstreamConfig, UPSTREAM_ERROR};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+const GATEWAY_KEY: &str = "gateway-secret";
+
+async fn upstream() -> TestServer {
+    TestServer::with_api_keys(&format!(
+        r#"
+[[keys]]
+name = "gateway"
+hash = "{}"
+scopes = ["tools:*"]
+"#,
+        hash_key(GATEWAY_KEY)
+    ))
+    .await
+}
+
+fn config(name: &str, url: String, token: &str) -> UpstreamConfig {
+    UpstreamConfig {
+        name: name.to_string(),
+        url,
+        token: Some(token.to_string()),
+        token_env: None,
+        headers: BTreeMap::new(),
+        timeout_secs: None,
+        read_only_tools: Vec::new(),
+    }
+}
+
+async fn gateway(configs: &[UpstreamConfig]) -> TestServer {
+    let mut tools = mod_tools::default_registry();
+    mod_upstream::register_tools(&mut tools, configs).await.unwrap();
+    TestServer::with_tools(tools).await
+}
+
+#[tokio::test]
+async fn upstream_tools_are_namespaced() {
+    let upstream = upstream().await;
+    let addr = upstream.serve().await;
+    let mut up = config("up", format!("http://{}/mcp", addr), GATEWAY_KEY);
+    up.read_only_tools = vec!["purchase_export".to_string()];
+    let gateway = gateway(&[up]).await;
+    let mut client = gateway.client();
+    client.initialize().await;
+
+    let response = client.request("tools/list", json!({})).await;
+    let tools = response["result"]["tools"].as_array().unwrap();
+    let names: Vec<&str> = tools.iter().map(|t| t["name"].as_str().unwrap()).collect();
+    assert!(names.contains(&"echo"));
+    assert!(names.contains(&"up.echo"));
+    assert!(names.contains(&"up.purchase_export"));
+    assert_eq!(names.len(), mod_tools::default_registry().names().len() * 2);
+
+    let export = tools.iter().find(|t| t["name"] == "up.purchase_export").unwrap();
+    assert_eq!(export["annotations"]["readOnlyHint"], true);
+    assert!(export["outputSchema"].is_object());
+
+    // 上流が readOnlyHint を付けていても、設定に無いツールは読み取り専用にしない
+    let list = tools.iter().find(|t| t["name"] == "up.list_todos").unwrap();
+    assert_eq!(list["annotations"]["readOnlyHint"], false);
+}
+
+#[tokio::test]
+async fn tool_calls_are_forwarded_with_upstream_credentials() {
+    let upstream = upstream().await;
+    let addr = upstream.serve().await;
+    let gateway = gateway(&[config("up", format!("http://{}/mcp", addr), GATEWAY_KEY)]).await;
+    let mut client = gateway.client();
+    client.initialize().await;
+
+    let result = client.call_tool("up.add", json!({ "a": 1, "b": 2 })).await;
+    assert_eq!(text(&result), "Result: 3");
+
+    client.call_tool("up.add_todo", json!({ "title": "from gateway" })).await;
+    let mut direct = upstream.client().with_token(GATEWAY_KEY);
+    direct.initialize().await;
+    let result = direct.call_tool("list_todos", json!({})).await;
+    assert_eq!(text(&result), "[ ] 1: from gateway");
+
+    // 上流の監査ログには上流用のキー名が記録される
+    let mut callers = Vec::new();
+    for _ in 0..50 {
+        let (items, _) = upstream.state.audit.query(&Default::default(), 10, 0).await.unwrap();
+        if items.len() == 2 {
+            callers = items.into_iter().map(|e| e.caller).collect();
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(callers, ["gateway", "gateway"]);
+}
+
+#[tokio::test]
+async fn upstream_errors_keep_code_and_name_the_upstream() {
+    let upstream = upstream().await;
+    let addr = upstream.serve().await;
+    let gateway = gateway(&[config("up", format!("http://{}/mcp", addr), GATEWAY_KEY)]).await;
+    let mut client = gateway.client();
+    client.initialize().await;
+
+    let error = client.call_tool_error("up.complete_todo", json!({ "id": 99 })).await;
+    assert_eq!(error["code"], -32602);
+    assert_eq!(error["message"], "Todo not found: 99");
+    assert_eq!(error["data"]["upstream"], "up");
+
+    // 上流のスキーマでゲートウェイ側でも検証する
+    let error = client.call_tool_error("up.add", json!({ "a": "x" })).await;
+    assert_eq!(error["code"], -32602);
+    assert_eq!(error["data"]["tool"], "up.add");
+}
+
+#[tokio::test]
+async fn connection_failures_are_upstream_errors() {
+    let upstream = upstream().await;
+    let addr = upstream.serve().await;
+    let client = std::sync::Arc::new(
+        mod_upstream::UpstreamClient::new(&config("up", format!("http://{}/nope", addr), GATEWAY_KEY)).unwrap(),
+    );
+    let error = client.list_tools().await.unwrap_err();
+    assert_eq!(error.code, UPSTREAM_ERROR);
+    assert_eq!(error.data.unwrap()["upstream"], "up");
+}
+
+#[tokio::test]
+async fn unreachable_or_rejected_upstreams_are_skipped() {
+    let upstream = upstream().await;
+    let addr = upstream.serve().await;
+    let gateway = gateway(&[
+        config("bad", format!("http://{}/mcp", addr), "wrong-key"),
+        config("down", "http://127.0.0.1:1/mcp".to_string(), GATEWAY_KEY),
+    ])
+    .await;
+    let mut client = gateway.client();
+    client.initialize().await;
+
+    let response = client.request("tools/list", json!({})).await;
+    let tools = response["result"]["tools"].as_array().unwrap();
+    assert_eq!(tools.len(), mod_tools::default_registry().names().len());
+}
@@ -0,0 +1,156 @@
+// completion/complete と logging/setLevel
+mod common;
+
+use common::TestServer;
+use rust_remoto_mcp_2::mod_logging;
+use serde_json::{json, Value};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+async fn purchase(client: &mut common::McpClient<'_>, name: &str, purchased_at: &str) {
+    client
+        .call_tool("purchase", json!({ "name": name, "price": 100, "purchased_at": purchased_at }))
+        .await;
+}
+
+async fn complete(client: &mut common::McpClient<'_>, reference: Value, argument: &str, value: &str) -> Value {
+    let response = client
+        .request(
+            "completion/complete",
+            json!({ "ref": reference, "argument": { "name": argument, "value": value } }),
+        )
+        .await;
+    assert!(response.get("error").is_none(), "{}", response);
+    response["result"]["completion"].clone()
+}
+
+#[tokio::test]
+async fn completes_resource_template_arguments() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+    purchase(&mut client, "coffee", "2025-01-10").await;
+    purchase(&mut client, "cola", "2025-01-11").await;
+    purchase(&mut client, "coffee", "2025-02-01").await;
+    purchase(&mut client, "bread", "2025-02-02").await;
+
+    // 品名は購入回数の多い順
+    let item = json!({ "type": "ref/resource", "uri": "purchase://item/{name}" });
+    let completion = complete(&mut client, item.clone(), "name", "co").await;
+    assert_eq!(completion["values"], json!(["coffee", "cola"]));
+    assert_eq!(completion["hasMore"], false);
+    let completion = complete(&mut client, item, "name", "%").await;
+    assert_eq!(completion["values"], json!([]));
+
+    let id = json!({ "type": "ref/resource", "uri": "purchase://{id}" });
+    let completion = complete(&mut client, id, "id", "").await;
+    assert_eq!(completion["values"], json!(["4", "3", "2", "1"]));
+
+    // 補完した品名でリソースを読める
+    let response = client
+        .request("resources/read", json!({ "uri": "purchase://item/coffee" }))
+        .await;
+    let body: Value = serde_json::from_str(response["result"]["contents"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(body["total"], 2);
+
+    let response = client
+        .request(
+            "completion/complete",
+            json!({ "ref": { "type": "ref/unknown" }, "argument": { "name": "x", "value": "" } }),
+        )
+        .await;
+    assert_eq!(response["error"]["code"], -32602);
+}
+
+// 前方一致は件数の上限より前に絞り込む (新しい 100 件以外の todo も候補になる)
+#[tokio::test]
+async fn completes_todo_ids_beyond_the_first_page() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+    for n in 1..=120 {
+        server.state.todos.add(&format!("todo {}", n)).await.unwrap();
+    }
+    server.state.todos.complete(19).await.unwrap();
+
+    let todo = json!({ "type": "ref/resource", "uri": "todo://{id}" });
+    let completion = complete(&mut client, todo.clone(), "id", "1").await;
+    let values = completion["values"].as_array().unwrap();
+    // 1, 10〜18, 100〜120 (19 は完了済み)
+    assert_eq!(values.len(), 31, "{}", completion);
+    assert_eq!(values.last().unwrap(), "1");
+    assert!(!values.contains(&json!("19")));
+    assert_eq!(completion["hasMore"], false);
+
+    let completion = complete(&mut client, todo, "id", "").await;
+    assert_eq!(completion["values"].as_array().unwrap().len(), 100);
+    assert_eq!(completion["hasMore"], true);
+}
+
+#[tokio::test]
+async fn completes_prompt_arguments() {
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+    purchase(&mut client, "coffee", "2024-12-24").await;
+    purchase(&mut client, "coffee", "2025-01-10").await;
+    purchase(&mut client, "coffee", "2025-02-01").await;
+
+    let summarize = json!({ "type": "ref/prompt", "name": "summarize_purchases" });
+    let completion = complete(&mut client, summarize.clone(), "month", "2025").await;
+    assert_eq!(completion["values"], json!(["2025-02", "2025-01"]));
+    let completion = complete(&mut client, summarize, "unknown", "").await;
+    assert_eq!(completion["values"], json!([]));
+
+    let plan = json!({ "type": "ref/prompt", "name": "plan_todos" });
+    let completion = complete(&mut client, plan.clone(), "hours", "").await;
+    assert_eq!(completion["values"], json!(["1", "2", "4", "8"]));
+    let completion = complete(&mut client, plan, "date", "").await;
+    assert_eq!(completion["values"].as_array().unwrap().len(), 7);
+
+    let response = client
+        .request(
+            "completion/complete",
+            json!({ "ref": { "type": "ref/prompt", "name": "missing" }, "argument": { "name": "x", "value": "" } }),
+        )
+        .await;
+    assert_eq!(response["error"]["code"], -32602);
+}
+
+#[tokio::test]
+async fn forwards_logs_at_requested_level() {
+    tracing_subscriber::registry().with(mod_logging::layer()).try_init().ok();
+    let server = TestServer::new().await;
+    let mut client = server.client();
+    client.initialize().await;
+    let session = server.state.sessions.get(client.session.as_deref().unwrap()).unwrap();
+    let mut stream = session.open_stream();
+
+    // setLevel するまでは送らない
+    client.call_tool("list_todos", json!({})).await;
+    assert!(stream.try_recv().is_err());
+
+    let response = client.request("logging/setLevel", json!({ "level": "warning" })).await;
+    assert_eq!(response["result"], json!({}));
+    client.call_tool("list_todos", json!({})).await;
+    assert!(stream.try_recv().is_err());
+
+    // info 以上: リクエスト処理中のログが届く
+    client.request("logging/setLevel", json!({ "level": "info" })).await;
+    client.call_tool("list_todos", json!({})).await;
+    let mut messages = Vec::new();
+    while let Ok(message) = stream.try_recv() {
+        messages.push(message);
+    }
+    assert!(!messages.is_empty());
+    assert!(messages.iter().all(|m| m["method"] == "notifications/message"));
+    let received = messages
+        .iter()
+        .find(|m| m["params"]["data"]["message"].as_str().is_some_and(|s| s.contains("method=tools/call")))
+        .expect("request log");
+    assert_eq!(received["params"]["level"], "info");
+    assert_eq!(received["params"]["logger"], "rust_remoto_mcp_2::mod_rpc");
+
+    let response = client.request("logging/setLevel", json!({ "level": "verbose" })).await;
+    assert_eq!(response["error"]["code"], -32602);
+}
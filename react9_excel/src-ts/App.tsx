@@ -58,6 +58,26 @@ function App() {
     location.href = "/download"
   }
 
+  const importTask = async (e) => {
+    const file = e.target.files?.[0];
+    e.target.value = "";
+    if (!file) {
+      return;
+    }
+    try {
+      const report = await itemsApi.importExcel(file);
+      console.log(report);
+      const rejected = report.rows
+        .filter((row) => row.status === "rejected")
+        .map((row) => `row ${row.row}: ${row.errors.join(", ")}`);
+      alert(`inserted: ${report.inserted}, updated: ${report.updated}, rejected: ${report.rejected}`
+        + (rejected.length > 0 ? "\n" + rejected.join("\n") : ""));
+    } catch (err) {
+      alert(err.message);
+    }
+    await fetchItems();
+  }
+
   return (
     <div className="container mx-auto p-4">
       <h1 className="text-2xl font-bold mb-4">Todo App</h1>
@@ -77,6 +97,12 @@ function App() {
             downLoad
           </button>
         </div>
+        <div class="flex-1 text-end text-white m-1">
+          <label className="bg-blue-500 text-white px-4 py-2 rounded mb-4 inline-block cursor-pointer">
+            import
+            <input type="file" accept=".xlsx" className="hidden" onChange={importTask} />
+          </label>
+        </div>
       </div>
       <TodoList todos={todos} onEdit={handleEdit} onDelete={handleDelete} />
       {isDialogOpen && (
@@ -61,4 +61,19 @@ export const itemsApi = {
       throw new Error('Failed to delete item');
     }
   },
+
+  // xlsx を取り込む (id があれば更新、無ければ追加)
+  importExcel: async (file: File): Promise<any> => {
+    const formData = new FormData();
+    formData.append('file', file);
+    const response = await fetch(`${API_BASE}/import`, {
+      method: 'POST',
+      body: formData,
+    });
+    const body = await response.json();
+    if (!response.ok) {
+      throw new Error(body.message || 'Failed to import items');
+    }
+    return body.report;
+  },
 };
\ No newline at end of file
@@ -0,0 +1,200 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use umya_spreadsheet::helper::coordinate::string_from_column_index;
+use umya_spreadsheet::{Pane, PaneStateValues, PaneValues, Spreadsheet, Worksheet};
+
+use super::models::TodoResponse;
+
+const SHEET_NAME: &str = "todos";
+const DATE_FORMAT: &str = "yyyy-mm-dd";
+const DATETIME_FORMAT: &str = "yyyy-mm-dd hh:mm:ss";
+
+// 列の見出しと幅 (見出しは /api/import の列名と同じ)
+const COLUMNS: &[(&str, f64)] = &[
+    ("id", 8.0),
+    ("title", 30.0),
+    ("content", 50.0),
+    ("completed", 12.0),
+    ("content_type", 14.0),
+    ("is_public", 11.0),
+    ("food_orange", 13.0),
+    ("food_apple", 12.0),
+    ("food_banana", 13.0),
+    ("food_melon", 12.0),
+    ("food_grape", 12.0),
+    ("pub_date1", 12.0),
+    ("pub_date2", 12.0),
+    ("pub_date3", 12.0),
+    ("pub_date4", 12.0),
+    ("pub_date5", 12.0),
+    ("pub_date6", 12.0),
+    ("qty1", 8.0),
+    ("qty2", 8.0),
+    ("qty3", 8.0),
+    ("qty4", 8.0),
+    ("qty5", 8.0),
+    ("qty6", 8.0),
+    ("created_at", 20.0),
+    ("updated_at", 20.0),
+];
+
+// セルに書く値 (None は空欄)
+enum Value<'a> {
+    Number(f64),
+    Text(&'a str),
+    Bool(bool),
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+}
+
+// todos 全件を1シートに書き出す
+//
+// 1行目は見出し (固定・オートフィルタ付き)。
+// 真偽値は TRUE/FALSE、pub_date* と作成・更新日時は Excel の日付、qty* は表記が変わらない数値だけ数値で書く。
+pub fn build_workbook(todos: &[TodoResponse]) -> Spreadsheet {
+    let mut book = umya_spreadsheet::new_file();
+    let Some(sheet) = book.get_sheet_mut(&0) else {
+        return book;
+    };
+    sheet.set_name(SHEET_NAME);
+
+    for (index, (header, width)) in COLUMNS.iter().enumerate() {
+        let col = index as u32 + 1;
+        sheet.get_cell_mut((col, 1)).set_value(*header);
+        sheet.get_style_mut((col, 1)).get_font_mut().set_bold(true);
+        sheet.get_column_dimension_by_number_mut(&col).set_width(*width);
+    }
+
+    for (index, todo) in todos.iter().enumerate() {
+        let row = index as u32 + 2;
+        for (col, value) in row_values(todo).into_iter().enumerate() {
+            if let Some(value) = value {
+                write_cell(sheet, col as u32 + 1, row, value);
+            }
+        }
+    }
+
+    freeze_header(sheet);
+    let last_row = todos.len() as u32 + 1;
+    sheet.set_auto_filter(format!("A1:{}{}", string_from_column_index(&(COLUMNS.len() as u32)), last_row));
+    book
+}
+
+// COLUMNS と同じ順に並べる
+fn row_values(todo: &TodoResponse) -> Vec<Option<Value<'_>>> {
+    vec![
+        Some(Value::Number(todo.id as f64)),
+        text(&todo.title),
+        text(&todo.content),
+        Some(Value::Bool(todo.completed.unwrap_or(false))),
+        text(&todo.content_type),
+        Some(Value::Bool(todo.is_public)),
+        Some(Value::Bool(todo.food_orange)),
+        Some(Value::Bool(todo.food_apple)),
+        Some(Value::Bool(todo.food_banana)),
+        Some(Value::Bool(todo.food_melon)),
+        Some(Value::Bool(todo.food_grape)),
+        date(&todo.pub_date1),
+        date(&todo.pub_date2),
+        date(&todo.pub_date3),
+        date(&todo.pub_date4),
+        date(&todo.pub_date5),
+        date(&todo.pub_date6),
+        qty(&todo.qty1),
+        qty(&todo.qty2),
+        qty(&todo.qty3),
+        qty(&todo.qty4),
+        qty(&todo.qty5),
+        qty(&todo.qty6),
+        todo.created_at.map(Value::DateTime),
+        todo.updated_at.map(Value::DateTime),
+    ]
+}
+
+fn text(value: &Option<String>) -> Option<Value<'_>> {
+    value.as_deref().map(Value::Text)
+}
+
+fn date(value: &Option<NaiveDate>) -> Option<Value<'static>> {
+    value.map(Value::Date)
+}
+
+// 数量は TEXT 列なので、数値にしても表記が変わらないものだけ数値にする
+// ("007" や "1.50" は取り込み直したときに元の文字列に戻らないため文字列のまま)
+fn qty(value: &Option<String>) -> Option<Value<'_>> {
+    let value = value.as_deref()?.trim();
+    if value.is_empty() {
+        return None;
+    }
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() && number.to_string() == value => Some(Value::Number(number)),
+        _ => Some(Value::Text(value)),
+    }
+}
+
+fn write_cell(sheet: &mut Worksheet, col: u32, row: u32, value: Value) {
+    match value {
+        Value::Number(number) => {
+            sheet.get_cell_mut((col, row)).set_value_number(number);
+        }
+        Value::Text(text) => {
+            sheet.get_cell_mut((col, row)).set_value_string(text);
+        }
+        Value::Bool(flag) => {
+            sheet.get_cell_mut((col, row)).set_value_bool(flag);
+        }
+        Value::Date(date) => {
+            sheet.get_cell_mut((col, row)).set_value_number(excel_serial(date.and_time(Default::default())));
+            sheet.get_style_mut((col, row)).get_number_format_mut().set_format_code(DATE_FORMAT);
+        }
+        Value::DateTime(datetime) => {
+            sheet.get_cell_mut((col, row)).set_value_number(excel_serial(datetime));
+            sheet.get_style_mut((col, row)).get_number_format_mut().set_format_code(DATETIME_FORMAT);
+        }
+    }
+}
+
+// Excel (1900 年形式) のシリアル値。1899-12-30 を 0 とする
+fn excel_serial(datetime: NaiveDateTime) -> f64 {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30)
+        .unwrap_or_default()
+        .and_time(Default::default());
+    let elapsed = datetime - epoch;
+    elapsed.num_milliseconds() as f64 / 86_400_000.0
+}
+
+// 1行目をスクロールしても表示したままにする
+fn freeze_header(sheet: &mut Worksheet) {
+    let mut pane = Pane::default();
+    pane.set_vertical_split(1.0)
+        .set_state(PaneStateValues::Frozen)
+        .set_active_pane(PaneValues::BottomLeft);
+    pane.get_top_left_cell_mut().set_coordinate("A2");
+    if let Some(view) = sheet.get_sheet_views_mut().get_sheet_view_list_mut().first_mut() {
+        view.set_pane(pane);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 数値なら Some(true)、文字列なら Some(false)、空欄なら None
+    fn is_number(value: &str) -> Option<bool> {
+        match qty(&Some(value.to_string())) {
+            Some(Value::Number(_)) => Some(true),
+            Some(_) => Some(false),
+            None => None,
+        }
+    }
+
+    #[test]
+    fn qty_keeps_text_that_would_change_as_a_number() {
+        for value in ["3", "1.5", "-2", "0"] {
+            assert_eq!(is_number(value), Some(true), "{}", value);
+        }
+        for value in ["007", "1.50", "1e3", "+1", "12個", "NaN", "inf"] {
+            assert_eq!(is_number(value), Some(false), "{}", value);
+        }
+        assert_eq!(is_number("  "), None);
+    }
+}
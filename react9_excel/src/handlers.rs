@@ -1,18 +1,12 @@
 use axum::{
-    body::Body,
-    extract::{Path, State},
-    http::{header, StatusCode, HeaderName},
+    extract::{Multipart, State},
+    http::StatusCode,
     response::{Json, IntoResponse, Response},
 };
-use chrono::Utc;
-use serde_json::{json, Value};
+use excel_common::Xlsx;
+use serde_json::json;
 
-use sqlx::{PgPool, Row};
-use std::fs;
-use std::sync::Arc;
-//use std::path::Path;
 use umya_spreadsheet::*;
-use uuid::Uuid;
 
 pub async fn get_todos(State(state): State<super::models::AppState>) -> Result<String, StatusCode> {
     println!("# /api/list");
@@ -233,135 +227,111 @@ pub async fn update_todo(
     })))
 }
 
-pub async fn dowload_handler(State(state): State<super::models::AppState>) -> impl IntoResponse {
-    println!("# /test");
-
-    let todSql = "SELECT id, title, content 
-    FROM todos ORDER BY id DESC
-    ";
-    println!("# todSql={}", &todSql);
-    //TodoXlsItems
-
-    let todo_items: Vec<super::models::TodoXlsItems> = sqlx::query_as::<_, super::models::TodoXlsItems>(&todSql)
-        .fetch_all(&state.pool)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR).unwrap();
-
-    //println!("Mapped structs > {:?}", todo_items);
-
-    let inPath = std::path::Path::new("input.xlsx");
-    let mut book = reader::xlsx::read(inPath).unwrap();  // 普通に読み込み
-
-    let sheet = book.get_sheet_by_name_mut("Sheet1").unwrap();
-
-    let mut countNum = 2;
-    for v in &todo_items {
-        println!("countNum={}", countNum);
-        //println!("{:?}", v);
-        let cell_num = countNum.to_string();
-        let cell_pos_a = format!("{}{}", "A", cell_num);
-        let cell_pos_b = format!("{}{}", "B", cell_num);
-        let cell_pos_c = format!("{}{}", "C", cell_num);
-        let cell_val_a = v.id.to_string();
-        let cell_val_b = &v.title;
-        let cell_val_c = &v.content;
-        println!("cell_pos_a={}", &cell_pos_a);
-        println!("cell_val_a={}", &cell_val_a);
-        //println!("cell_val_b={}", &cell_val_b.to_string().unwrap());
-        println!("cell_val_b={}", &cell_val_b.clone().unwrap());
-        sheet.get_cell_mut(cell_pos_a.to_string()).set_value(&cell_val_a);
-        sheet.get_cell_mut(cell_pos_b.to_string()).set_value(&cell_val_b.clone().unwrap());
-        sheet.get_cell_mut(cell_pos_c.to_string()).set_value(&cell_val_c.clone().unwrap());
-        countNum += 1;
+// xlsx を取り込み、id をキーに追加・更新する (multipart の "file" フィールド)
+pub async fn import_excel(
+    State(state): State<super::models::AppState>,
+    mut multipart: Multipart,
+) -> Response {
+    println!("# /api/import");
+
+    let mut bytes = None;
+    loop {
+        match multipart.next_field().await {
+            Ok(Some(field)) if field.name() == Some("file") => match field.bytes().await {
+                Ok(data) => {
+                    bytes = Some(data);
+                    break;
+                }
+                Err(e) => return import_error(StatusCode::BAD_REQUEST, format!("failed to read upload: {}", e)),
+            },
+            Ok(Some(_)) => continue,
+            Ok(None) => break,
+            Err(e) => return import_error(StatusCode::BAD_REQUEST, format!("invalid multipart body: {}", e)),
+        }
+    }
+    let Some(bytes) = bytes else {
+        return import_error(StatusCode::BAD_REQUEST, "file field is required".to_string());
+    };
+
+    // xlsx の展開と解析は重いため blocking スレッドで行う
+    let sheet = match tokio::task::spawn_blocking(move || super::import::read_workbook(&bytes)).await {
+        Ok(Ok(sheet)) => sheet,
+        Ok(Err(e)) => return import_error(StatusCode::BAD_REQUEST, e.0),
+        Err(e) => {
+            eprintln!("Failed to read import file: {:?}", e);
+            return import_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to read the file".to_string());
+        }
+    };
+    match super::import::upsert(&state.pool, sheet).await {
+        Ok(report) => {
+            println!("import: inserted={}, updated={}, rejected={}", report.inserted, report.updated, report.rejected);
+            Json(json!({
+                "ret": 200,
+                "message": "Import finished",
+                "report": report,
+            })).into_response()
+        }
+        Err(super::import::UpsertError::Row(row, e)) => {
+            eprintln!("Failed to import todos (row {}): {:?}", row, e);
+            import_error(StatusCode::INTERNAL_SERVER_ERROR, format!("database error at row {}, nothing was imported", row))
+        }
+        Err(super::import::UpsertError::Transaction(e)) => {
+            eprintln!("Failed to import todos: {:?}", e);
+            import_error(StatusCode::INTERNAL_SERVER_ERROR, "database error, nothing was imported".to_string())
+        }
     }
-    let file_name = format!("{}.xlsx", Uuid::new_v4());
-    let path = std::path::Path::new(&file_name);
+}
 
-    match writer::xlsx::write(&book, path) {
-        Ok(_) => {
-            let file_content = match fs::read(path) {
-                Ok(content) => content,
-                Err(_) => {
-                    return Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .body(Body::from("Failed to read Excel file."))
-                        .unwrap();
-                }
-            };
+fn import_error(status: StatusCode, message: String) -> Response {
+    (status, Json(json!({
+        "ret": status.as_u16(),
+        "message": message,
+    }))).into_response()
+}
 
-            let _ = fs::remove_file(path);
+// todos 全件を xlsx でダウンロード (見出し行付き、/api/import で取り込める形式)
+pub async fn dowload_handler(State(state): State<super::models::AppState>) -> Response {
+    println!("# /download");
 
-            let headers = [
-                (
-                    header::CONTENT_TYPE,
-                    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
-                ),
-                (
-                    header::CONTENT_DISPOSITION,
-                    "attachment; filename=\"edit_result.xlsx\"",
-                ),
-            ];
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(headers[0].0.clone(), headers[0].1)
-                .header(headers[1].0.clone(), headers[1].1)
-                .body(Body::from(file_content))
-                .unwrap()
+    let sql = "SELECT id, title, content , completed , content_type ,
+    is_public, food_orange, food_apple, food_banana, food_melon, food_grape, 
+    pub_date1, pub_date2, pub_date3, pub_date4, pub_date5, pub_date6,
+    qty1, qty2, qty3, qty4, qty5, qty6,
+    created_at, updated_at
+    FROM todos ORDER BY id
+    ";
+    let todo_items = match sqlx::query_as::<_, super::models::TodoResponse>(sql)
+        .fetch_all(&state.pool)
+        .await
+    {
+        Ok(items) => items,
+        Err(e) => {
+            eprintln!("Failed to fetch todos: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch todos.").into_response();
         }
-        Err(_) => Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(Body::from("Failed to create Excel file."))
-            .unwrap(),
-    }
+    };
+
+    let book = super::export::build_workbook(&todo_items);
+    Xlsx::render(book, "todos.xlsx").await
 }
 
-pub async fn edit_download_excel() -> impl IntoResponse {
-    let inPath = std::path::Path::new("input.xlsx");
-    let mut book = reader::xlsx::read(inPath).unwrap();  // 普通に読み込み
+pub async fn edit_download_excel() -> Response {
+    let in_path = std::path::Path::new("input.xlsx");
+    let mut book = match reader::xlsx::read(in_path) {
+        Ok(book) => book,  // 普通に読み込み
+        Err(e) => {
+            eprintln!("Failed to read input.xlsx: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read Excel file.").into_response();
+        }
+    };
 
-    let sheet = book.get_sheet_by_name_mut("Sheet1").unwrap();
+    let Some(sheet) = book.get_sheet_by_name_mut("Sheet1") else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Sheet1 not found.").into_response();
+    };
     // セル A1 に文字列
     sheet.get_cell_mut("A2").set_value("こんにちは、Rust!");
     sheet.get_cell_mut("B2").set_value("123");
     sheet.get_cell_mut("C2").set_value("C-123");
 
-    let file_name = format!("{}.xlsx", Uuid::new_v4());
-    let path = std::path::Path::new(&file_name);
-
-    match writer::xlsx::write(&book, path) {
-        Ok(_) => {
-            let file_content = match fs::read(path) {
-                Ok(content) => content,
-                Err(_) => {
-                    return Response::builder()
-                        .status(StatusCode::INTERNAL_SERVER_ERROR)
-                        .body(Body::from("Failed to read Excel file."))
-                        .unwrap();
-                }
-            };
-
-            let _ = fs::remove_file(path);
-
-            let headers = [
-                (
-                    header::CONTENT_TYPE,
-                    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
-                ),
-                (
-                    header::CONTENT_DISPOSITION,
-                    "attachment; filename=\"edit_result.xlsx\"",
-                ),
-            ];
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(headers[0].0.clone(), headers[0].1)
-                .header(headers[1].0.clone(), headers[1].1)
-                .body(Body::from(file_content))
-                .unwrap()
-        }
-        Err(_) => Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(Body::from("Failed to create Excel file."))
-            .unwrap(),
-    }
-}
\ No newline at end of file
+    Xlsx::render(book, "edit_result.xlsx").await
+}
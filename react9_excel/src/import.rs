@@ -0,0 +1,583 @@
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use std::io::Cursor;
+use umya_spreadsheet::{reader, Cell, CellRawValue, Worksheet};
+
+use super::models::{CreateTodo, UpdateTodo};
+
+// 1回の取り込みで扱う最大行数
+const MAX_ROWS: u32 = 10_000;
+
+// 取り込みに使う列 (1行目の見出し、大文字小文字は区別しない)
+const COLUMNS: &[&str] = &[
+    "id", "title", "content", "completed", "content_type", "is_public",
+    "food_orange", "food_apple", "food_banana", "food_melon", "food_grape",
+    "pub_date1", "pub_date2", "pub_date3", "pub_date4", "pub_date5", "pub_date6",
+    "qty1", "qty2", "qty3", "qty4", "qty5", "qty6",
+];
+
+// DB が設定する列 (シートにあっても取り込まず、ignored_columns にも含めない)
+const READ_ONLY_COLUMNS: &[&str] = &["created_at", "updated_at"];
+
+// ファイル全体の問題 (400 で返す)
+#[derive(Debug)]
+pub struct ImportError(pub String);
+
+// シートの1行 (id があれば UpdateTodo として値を持つ)
+#[derive(Debug)]
+pub struct ImportRow {
+    pub row: u32,
+    pub id: Option<i32>,
+    pub todo: UpdateTodo,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ImportSheet {
+    pub name: String,
+    pub ignored_columns: Vec<String>,
+    pub rows: Vec<ImportRow>,
+}
+
+// 書き込み中の DB エラー (トランザクションはロールバックされる)
+#[derive(Debug)]
+pub enum UpsertError {
+    // 行の書き込みに失敗した (シートの行番号)
+    Row(u32, sqlx::Error),
+    // トランザクションの開始・確定、採番の更新に失敗した
+    Transaction(sqlx::Error),
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowReport {
+    pub row: u32,
+    pub id: Option<i32>,
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub sheet: String,
+    pub inserted: usize,
+    pub updated: usize,
+    pub rejected: usize,
+    pub ignored_columns: Vec<String>,
+    pub rows: Vec<RowReport>,
+}
+
+// xlsx の先頭シートを読み、見出し行に従って各行を検証する
+pub fn read_workbook(bytes: &[u8]) -> Result<ImportSheet, ImportError> {
+    let book = reader::xlsx::read_reader(Cursor::new(bytes), true)
+        .map_err(|e| ImportError(format!("invalid xlsx file: {:?}", e)))?;
+    let sheet = book
+        .get_sheet(&0)
+        .ok_or_else(|| ImportError("workbook has no sheet".to_string()))?;
+
+    let (max_col, max_row) = sheet.get_highest_column_and_row();
+    if max_row > MAX_ROWS + 1 {
+        return Err(ImportError(format!("too many rows: {} (max {})", max_row - 1, MAX_ROWS)));
+    }
+
+    // 見出し → 列番号
+    let mut columns: Vec<(u32, &'static str)> = Vec::new();
+    let mut ignored_columns = Vec::new();
+    for col in 1..=max_col {
+        let header = cell_text(sheet.get_cell((col, 1))).unwrap_or_default().to_lowercase();
+        if header.is_empty() || READ_ONLY_COLUMNS.contains(&header.as_str()) {
+            continue;
+        }
+        match COLUMNS.iter().find(|c| **c == header) {
+            Some(column) if columns.iter().any(|(_, c)| c == column) => {
+                return Err(ImportError(format!("duplicate column: {}", column)));
+            }
+            Some(column) => columns.push((col, column)),
+            None => ignored_columns.push(header),
+        }
+    }
+    if !columns.iter().any(|(_, c)| *c == "id" || *c == "title") {
+        return Err(ImportError("header row must contain id or title column".to_string()));
+    }
+
+    let mut rows = Vec::new();
+    let mut seen_ids: HashMap<i32, u32> = HashMap::new();
+    for row in 2..=max_row {
+        // 空行は読み飛ばす
+        if columns.iter().all(|(col, _)| cell_text(sheet.get_cell((*col, row))).is_none()) {
+            continue;
+        }
+        let mut parsed = read_row(sheet, row, &columns);
+        if let Some(id) = parsed.id {
+            if let Some(first) = seen_ids.get(&id) {
+                parsed.errors.push(format!("id: duplicate id {} (row {})", id, first));
+            } else {
+                seen_ids.insert(id, row);
+            }
+        }
+        rows.push(parsed);
+    }
+
+    Ok(ImportSheet {
+        name: sheet.get_name().to_string(),
+        ignored_columns,
+        rows,
+    })
+}
+
+fn read_row(sheet: &Worksheet, row: u32, columns: &[(u32, &'static str)]) -> ImportRow {
+    let mut parsed = ImportRow {
+        row,
+        id: None,
+        todo: empty_todo(),
+        errors: Vec::new(),
+    };
+    for (col, column) in columns {
+        let cell = sheet.get_cell((*col, row));
+        if let Err(reason) = set_field(&mut parsed, column, cell) {
+            parsed.errors.push(format!("{}: {}", column, reason));
+        }
+    }
+    parsed
+}
+
+fn empty_todo() -> UpdateTodo {
+    UpdateTodo {
+        id: 0,
+        title: None,
+        content: None,
+        completed: None,
+        content_type: None,
+        is_public: None,
+        food_orange: None,
+        food_apple: None,
+        food_banana: None,
+        food_melon: None,
+        food_grape: None,
+        pub_date1: None,
+        pub_date2: None,
+        pub_date3: None,
+        pub_date4: None,
+        pub_date5: None,
+        pub_date6: None,
+        qty1: None,
+        qty2: None,
+        qty3: None,
+        qty4: None,
+        qty5: None,
+        qty6: None,
+    }
+}
+
+// 見出しに対応するフィールドへセルの値を入れる (空欄は None)
+fn set_field(parsed: &mut ImportRow, column: &str, cell: Option<&Cell>) -> Result<(), String> {
+    let todo = &mut parsed.todo;
+    match column {
+        "id" => {
+            parsed.id = cell_id(cell)?;
+            todo.id = parsed.id.unwrap_or_default();
+        }
+        "title" => todo.title = cell_text(cell),
+        "content" => todo.content = cell_text(cell),
+        "content_type" => todo.content_type = cell_text(cell),
+        "completed" => todo.completed = cell_bool(cell)?,
+        "is_public" => todo.is_public = cell_bool(cell)?,
+        "food_orange" => todo.food_orange = cell_bool(cell)?,
+        "food_apple" => todo.food_apple = cell_bool(cell)?,
+        "food_banana" => todo.food_banana = cell_bool(cell)?,
+        "food_melon" => todo.food_melon = cell_bool(cell)?,
+        "food_grape" => todo.food_grape = cell_bool(cell)?,
+        "pub_date1" => todo.pub_date1 = cell_date(cell)?,
+        "pub_date2" => todo.pub_date2 = cell_date(cell)?,
+        "pub_date3" => todo.pub_date3 = cell_date(cell)?,
+        "pub_date4" => todo.pub_date4 = cell_date(cell)?,
+        "pub_date5" => todo.pub_date5 = cell_date(cell)?,
+        "pub_date6" => todo.pub_date6 = cell_date(cell)?,
+        "qty1" => todo.qty1 = cell_text(cell),
+        "qty2" => todo.qty2 = cell_text(cell),
+        "qty3" => todo.qty3 = cell_text(cell),
+        "qty4" => todo.qty4 = cell_text(cell),
+        "qty5" => todo.qty5 = cell_text(cell),
+        "qty6" => todo.qty6 = cell_text(cell),
+        _ => {}
+    }
+    Ok(())
+}
+
+fn cell_text(cell: Option<&Cell>) -> Option<String> {
+    let value = cell?.get_value();
+    let value = value.trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn cell_id(cell: Option<&Cell>) -> Result<Option<i32>, String> {
+    let id = match cell.map(Cell::get_raw_value) {
+        Some(CellRawValue::Numeric(n)) if n.fract() == 0.0 && *n >= 1.0 && *n <= i32::MAX as f64 => *n as i32,
+        Some(CellRawValue::Numeric(n)) => return Err(format!("must be a positive integer: {}", n)),
+        _ => match cell_text(cell) {
+            None => return Ok(None),
+            Some(text) => text
+                .parse::<i32>()
+                .ok()
+                .filter(|id| *id > 0)
+                .ok_or_else(|| format!("must be a positive integer: {}", text))?,
+        },
+    };
+    Ok(Some(id))
+}
+
+// TRUE/FALSE, 1/0, true/false, yes/no を受け付ける
+fn cell_bool(cell: Option<&Cell>) -> Result<Option<bool>, String> {
+    if let Some(CellRawValue::Bool(value)) = cell.map(Cell::get_raw_value) {
+        return Ok(Some(*value));
+    }
+    let Some(text) = cell_text(cell) else {
+        return Ok(None);
+    };
+    match text.to_lowercase().as_str() {
+        "true" | "1" | "yes" | "y" => Ok(Some(true)),
+        "false" | "0" | "no" | "n" => Ok(Some(false)),
+        _ => Err(format!("must be TRUE or FALSE: {}", text)),
+    }
+}
+
+// Excel の日付 (シリアル値) または YYYY-MM-DD / YYYY/MM/DD の文字列
+fn cell_date(cell: Option<&Cell>) -> Result<Option<NaiveDate>, String> {
+    if let Some(CellRawValue::Numeric(serial)) = cell.map(Cell::get_raw_value) {
+        return excel_date(*serial)
+            .map(Some)
+            .ok_or_else(|| format!("invalid date serial: {}", serial));
+    }
+    let Some(text) = cell_text(cell) else {
+        return Ok(None);
+    };
+    NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(&text, "%Y/%m/%d"))
+        .map(Some)
+        .map_err(|_| format!("must be a date (YYYY-MM-DD): {}", text))
+}
+
+// 1900 年形式のシリアル値 (1900-03-01 以降) を日付に変換
+fn excel_date(serial: f64) -> Option<NaiveDate> {
+    if !(61.0..=2_958_465.0).contains(&serial) {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(1899, 12, 30)?.checked_add_signed(Duration::days(serial.trunc() as i64))
+}
+
+// 新規行は title 必須
+fn to_create(todo: UpdateTodo) -> Result<CreateTodo, String> {
+    let title = todo.title.ok_or_else(|| "title: required for new rows".to_string())?;
+    Ok(CreateTodo {
+        title,
+        content: todo.content.unwrap_or_default(),
+        completed: todo.completed,
+        content_type: todo.content_type,
+        is_public: todo.is_public,
+        food_orange: todo.food_orange,
+        food_apple: todo.food_apple,
+        food_banana: todo.food_banana,
+        food_melon: todo.food_melon,
+        food_grape: todo.food_grape,
+        pub_date1: todo.pub_date1,
+        pub_date2: todo.pub_date2,
+        pub_date3: todo.pub_date3,
+        pub_date4: todo.pub_date4,
+        pub_date5: todo.pub_date5,
+        pub_date6: todo.pub_date6,
+        qty1: todo.qty1,
+        qty2: todo.qty2,
+        qty3: todo.qty3,
+        qty4: todo.qty4,
+        qty5: todo.qty5,
+        qty6: todo.qty6,
+    })
+}
+
+// 検証を通った行を1トランザクションで書き込む
+//
+// id が既存なら更新 (空欄の列は変更しない)、無ければ追加 (id 指定があればその id で追加)。
+// DB エラーが起きた場合は全体をロールバックする。
+pub async fn upsert(pool: &PgPool, sheet: ImportSheet) -> Result<ImportReport, UpsertError> {
+    let mut tx = pool.begin().await.map_err(UpsertError::Transaction)?;
+    let mut report = ImportReport {
+        sheet: sheet.name,
+        inserted: 0,
+        updated: 0,
+        rejected: 0,
+        ignored_columns: sheet.ignored_columns,
+        rows: Vec::new(),
+    };
+    let mut explicit_ids = false;
+
+    for parsed in sheet.rows {
+        let row = parsed.row;
+        let has_id = parsed.id.is_some();
+        let (id, status, errors) = match upsert_row(&mut tx, parsed).await.map_err(|e| UpsertError::Row(row, e))? {
+            Ok((id, inserted)) => {
+                if inserted {
+                    report.inserted += 1;
+                } else {
+                    report.updated += 1;
+                }
+                (Some(id), if inserted { "inserted" } else { "updated" }, Vec::new())
+            }
+            Err((id, errors)) => {
+                report.rejected += 1;
+                (id, "rejected", errors)
+            }
+        };
+        explicit_ids |= has_id && status == "inserted";
+        report.rows.push(RowReport { row, id, status, errors });
+    }
+
+    // id を指定して追加した場合は SERIAL の採番を追いつかせる
+    if explicit_ids {
+        sqlx::query("SELECT setval(pg_get_serial_sequence('todos', 'id'), (SELECT MAX(id) FROM todos))")
+            .execute(&mut *tx)
+            .await
+            .map_err(UpsertError::Transaction)?;
+    }
+    tx.commit().await.map_err(UpsertError::Transaction)?;
+    Ok(report)
+}
+
+type RowOutcome = Result<(i32, bool), (Option<i32>, Vec<String>)>;
+
+async fn upsert_row(tx: &mut Transaction<'_, Postgres>, parsed: ImportRow) -> Result<RowOutcome, sqlx::Error> {
+    if !parsed.errors.is_empty() {
+        return Ok(Err((parsed.id, parsed.errors)));
+    }
+
+    let exists = match parsed.id {
+        Some(id) => sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM todos WHERE id = $1)")
+            .bind(id)
+            .fetch_one(&mut **tx)
+            .await?,
+        None => false,
+    };
+    if exists {
+        update_todo(tx, &parsed.todo).await?;
+        return Ok(Ok((parsed.todo.id, false)));
+    }
+
+    let id = parsed.id;
+    match to_create(parsed.todo) {
+        Ok(todo) => Ok(Ok((insert_todo(tx, id, &todo).await?, true))),
+        Err(error) => Ok(Err((id, vec![error]))),
+    }
+}
+
+async fn insert_todo(tx: &mut Transaction<'_, Postgres>, id: Option<i32>, todo: &CreateTodo) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar::<_, i32>(
+        "INSERT INTO todos (id, title, content, completed, content_type, is_public, food_orange, food_apple, food_banana, food_melon, food_grape, pub_date1, pub_date2, pub_date3, pub_date4, pub_date5, pub_date6, qty1, qty2, qty3, qty4, qty5, qty6)
+        VALUES (COALESCE($1, nextval(pg_get_serial_sequence('todos', 'id'))), $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23)
+        RETURNING id"
+    )
+    .bind(id)
+    .bind(&todo.title)
+    .bind(&todo.content)
+    .bind(todo.completed.unwrap_or(false))
+    .bind(&todo.content_type)
+    .bind(todo.is_public.unwrap_or(false))
+    .bind(todo.food_orange.unwrap_or(false))
+    .bind(todo.food_apple.unwrap_or(false))
+    .bind(todo.food_banana.unwrap_or(false))
+    .bind(todo.food_melon.unwrap_or(false))
+    .bind(todo.food_grape.unwrap_or(false))
+    .bind(todo.pub_date1)
+    .bind(todo.pub_date2)
+    .bind(todo.pub_date3)
+    .bind(todo.pub_date4)
+    .bind(todo.pub_date5)
+    .bind(todo.pub_date6)
+    .bind(&todo.qty1)
+    .bind(&todo.qty2)
+    .bind(&todo.qty3)
+    .bind(&todo.qty4)
+    .bind(&todo.qty5)
+    .bind(&todo.qty6)
+    .fetch_one(&mut **tx)
+    .await
+}
+
+// 値のある列だけを更新する
+async fn update_todo(tx: &mut Transaction<'_, Postgres>, todo: &UpdateTodo) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE todos SET title = COALESCE($1, title), content = COALESCE($2, content), completed = COALESCE($3, completed), content_type = COALESCE($4, content_type), is_public = COALESCE($5, is_public),
+        food_orange = COALESCE($6, food_orange), food_apple = COALESCE($7, food_apple), food_banana = COALESCE($8, food_banana), food_melon = COALESCE($9, food_melon), food_grape = COALESCE($10, food_grape),
+        pub_date1 = COALESCE($11, pub_date1), pub_date2 = COALESCE($12, pub_date2), pub_date3 = COALESCE($13, pub_date3), pub_date4 = COALESCE($14, pub_date4), pub_date5 = COALESCE($15, pub_date5), pub_date6 = COALESCE($16, pub_date6),
+        qty1 = COALESCE($17, qty1), qty2 = COALESCE($18, qty2), qty3 = COALESCE($19, qty3), qty4 = COALESCE($20, qty4), qty5 = COALESCE($21, qty5), qty6 = COALESCE($22, qty6),
+        updated_at = now() WHERE id = $23"
+    )
+    .bind(&todo.title)
+    .bind(&todo.content)
+    .bind(todo.completed)
+    .bind(&todo.content_type)
+    .bind(todo.is_public)
+    .bind(todo.food_orange)
+    .bind(todo.food_apple)
+    .bind(todo.food_banana)
+    .bind(todo.food_melon)
+    .bind(todo.food_grape)
+    .bind(todo.pub_date1)
+    .bind(todo.pub_date2)
+    .bind(todo.pub_date3)
+    .bind(todo.pub_date4)
+    .bind(todo.pub_date5)
+    .bind(todo.pub_date6)
+    .bind(&todo.qty1)
+    .bind(&todo.qty2)
+    .bind(&todo.qty3)
+    .bind(&todo.qty4)
+    .bind(&todo.qty5)
+    .bind(&todo.qty6)
+    .bind(todo.id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1行目が見出し、2行目以降がデータのブック (None は空欄)
+    fn workbook(headers: &[&str], rows: &[Vec<Option<CellRawValue>>]) -> Vec<u8> {
+        let mut book = umya_spreadsheet::new_file();
+        let sheet = book.get_sheet_mut(&0).unwrap();
+        for (col, header) in headers.iter().enumerate() {
+            sheet.get_cell_mut((col as u32 + 1, 1)).set_value(*header);
+        }
+        for (row, values) in rows.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                let cell = sheet.get_cell_mut((col as u32 + 1, row as u32 + 2));
+                match value {
+                    Some(CellRawValue::Numeric(n)) => {
+                        cell.set_value_number(*n);
+                    }
+                    Some(CellRawValue::Bool(b)) => {
+                        cell.set_value_bool(*b);
+                    }
+                    Some(value) => {
+                        cell.set_value_string(value.to_string());
+                    }
+                    None => {}
+                }
+            }
+        }
+        excel_common::to_bytes(&book).unwrap()
+    }
+
+    fn number(n: f64) -> Option<CellRawValue> {
+        Some(CellRawValue::Numeric(n))
+    }
+
+    fn text(s: &str) -> Option<CellRawValue> {
+        Some(CellRawValue::String(s.into()))
+    }
+
+    fn boolean(b: bool) -> Option<CellRawValue> {
+        Some(CellRawValue::Bool(b))
+    }
+
+    fn read(headers: &[&str], rows: &[Vec<Option<CellRawValue>>]) -> ImportSheet {
+        read_workbook(&workbook(headers, rows)).unwrap()
+    }
+
+    #[test]
+    fn ids_may_be_numbers_or_text() {
+        let sheet = read(
+            &["id", "title"],
+            &[
+                vec![number(3.0), text("numeric")],
+                vec![text("12"), text("text")],
+                vec![None, text("new")],
+                vec![number(1.5), text("fraction")],
+                vec![text("abc"), text("not a number")],
+                vec![number(0.0), text("zero")],
+            ],
+        );
+        let ids: Vec<Option<i32>> = sheet.rows.iter().map(|r| r.id).collect();
+        assert_eq!(ids, [Some(3), Some(12), None, None, None, None]);
+        assert!(sheet.rows[..3].iter().all(|r| r.errors.is_empty()));
+        assert_eq!(sheet.rows[3].errors, ["id: must be a positive integer: 1.5"]);
+        assert_eq!(sheet.rows[4].errors, ["id: must be a positive integer: abc"]);
+        assert_eq!(sheet.rows[5].errors, ["id: must be a positive integer: 0"]);
+    }
+
+    #[test]
+    fn booleans_accept_cells_and_text() {
+        let sheet = read(
+            &["title", "completed", "is_public"],
+            &[
+                vec![text("a"), boolean(true), text("no")],
+                vec![text("b"), text("TRUE"), text("1")],
+                vec![text("c"), None, text("maybe")],
+            ],
+        );
+        let todo = &sheet.rows[0].todo;
+        assert_eq!((todo.completed, todo.is_public), (Some(true), Some(false)));
+        let todo = &sheet.rows[1].todo;
+        assert_eq!((todo.completed, todo.is_public), (Some(true), Some(true)));
+        assert_eq!(sheet.rows[2].todo.completed, None);
+        assert_eq!(sheet.rows[2].errors, ["is_public: must be TRUE or FALSE: maybe"]);
+    }
+
+    #[test]
+    fn dates_accept_serials_and_strings() {
+        let sheet = read(
+            &["title", "pub_date1", "pub_date2", "pub_date3"],
+            &[
+                vec![text("a"), number(45658.0), text("2025-01-02"), text("2025/01/03")],
+                vec![text("b"), number(10.0), text("2025-02-30"), None],
+            ],
+        );
+        let todo = &sheet.rows[0].todo;
+        assert_eq!(todo.pub_date1, NaiveDate::from_ymd_opt(2025, 1, 1));
+        assert_eq!(todo.pub_date2, NaiveDate::from_ymd_opt(2025, 1, 2));
+        assert_eq!(todo.pub_date3, NaiveDate::from_ymd_opt(2025, 1, 3));
+        assert!(sheet.rows[0].errors.is_empty());
+        assert_eq!(
+            sheet.rows[1].errors,
+            ["pub_date1: invalid date serial: 10", "pub_date2: must be a date (YYYY-MM-DD): 2025-02-30"]
+        );
+    }
+
+    #[test]
+    fn duplicate_ids_reject_later_rows() {
+        let sheet = read(
+            &["id", "title"],
+            &[vec![number(5.0), text("first")], vec![text("5"), text("second")]],
+        );
+        assert!(sheet.rows[0].errors.is_empty());
+        assert_eq!(sheet.rows[1].errors, ["id: duplicate id 5 (row 2)"]);
+    }
+
+    #[test]
+    fn unknown_headers_are_ignored() {
+        let sheet = read(
+            &["Title", "memo", "created_at", "qty1"],
+            &[vec![text("a"), text("note"), text("2025-01-01 00:00:00"), text("007")]],
+        );
+        assert_eq!(sheet.ignored_columns, ["memo"]);
+        let todo = &sheet.rows[0].todo;
+        assert_eq!(todo.title.as_deref(), Some("a"));
+        assert_eq!(todo.qty1.as_deref(), Some("007"));
+        assert!(sheet.rows[0].errors.is_empty());
+    }
+
+    #[test]
+    fn header_problems_reject_the_file() {
+        let error = read_workbook(&workbook(&["memo"], &[])).unwrap_err();
+        assert_eq!(error.0, "header row must contain id or title column");
+        let error = read_workbook(&workbook(&["title", "TITLE"], &[])).unwrap_err();
+        assert_eq!(error.0, "duplicate column: title");
+    }
+}
@@ -1,5 +1,5 @@
 use axum::{
-    extract::State,
+    extract::{DefaultBodyLimit, State},
     http::StatusCode,
     response::{Json, Html, IntoResponse},
     routing::{get, post},
@@ -17,6 +17,8 @@ use tokio;
 use tower_http::services::ServeDir;
 mod models;
 mod handlers;
+mod export;
+mod import;
 
 #[derive(Debug, Serialize, Deserialize)]
 struct User {
@@ -67,6 +69,7 @@ async fn main() {
         .route("/api/create", post(handlers::create_todo))
         .route("/api/delete", post(handlers::delete_todo))
         .route("/api/update", post(handlers::update_todo))
+        .route("/api/import", post(handlers::import_excel).layer(DefaultBodyLimit::max(10 * 1024 * 1024)))
         .route("/edit_download", get(handlers::edit_download_excel)) 
         .route("/download", get(handlers::dowload_handler)) 
         .route("/", get(root))
@@ -100,7 +100,7 @@ pub struct UpdateTodo {
 #[derive(Debug, Serialize , Deserialize, FromRow)]
 pub struct TodoResponse {
     pub id: i32,
-    pub title: String,
+    pub title: Option<String>,
     pub content: Option<String>,
     pub completed: Option<bool>,
     pub content_type: Option<String>,
@@ -110,7 +110,7 @@ pub struct TodoResponse {
     pub food_banana: bool,
     pub food_melon: bool,
     pub food_grape: bool,
-    pub_date1: Option<NaiveDate>,
+    pub pub_date1: Option<NaiveDate>,
     pub pub_date2: Option<NaiveDate>,
     pub pub_date3: Option<NaiveDate>,
     pub pub_date4: Option<NaiveDate>,
@@ -125,10 +125,3 @@ pub struct TodoResponse {
     pub created_at: Option<NaiveDateTime>,
     pub updated_at: Option<NaiveDateTime> 
 }
-
-#[derive(Debug, Serialize , Deserialize, FromRow)]
-pub struct TodoXlsItems {
-    pub id: i32,
-    pub title: Option<String>,
-    pub content: Option<String>,
-}
\ No newline at end of file